@@ -0,0 +1,29 @@
+//! Accounts the way the cluster stores and filters them
+
+use anchor_client::solana_client::rpc_filter::RpcFilterType;
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::rent::Rent;
+use anchor_lang::{AccountSerialize, Owner};
+use anyhow::Result;
+
+/// Serialize an anchor account with its discriminator, owned by its program and rent exempt
+pub fn anchor_account<T: AccountSerialize + Owner>(value: &T) -> Result<Account> {
+    let mut data = vec![];
+    value.try_serialize(&mut data)?;
+    Ok(Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: T::owner(),
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+/// Filters are evaluated the way the RPC node does, token account filters never match
+pub fn filter_matches(filter: &RpcFilterType, data: &[u8]) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+        _ => false,
+    }
+}
@@ -0,0 +1,5 @@
+//! Helpers shared by the keeper and the mock cluster
+
+pub mod account;
+pub mod mint;
+pub mod pda;
@@ -0,0 +1,41 @@
+//! Layout of the SPL mints, the keeper only reads their decimals
+
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::rent::Rent;
+use anchor_lang::prelude::Pubkey;
+use std::str::FromStr;
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+/// Size of an SPL mint, token 2022 mints with extensions are longer
+pub const MINT_LEN: usize = 82;
+/// mint_authority (36) then supply (8)
+pub const MINT_DECIMALS_OFFSET: usize = 44;
+pub const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+
+/// Decimals of an initialized mint of the token or token 2022 program
+pub fn parse_mint_decimals(account: &Account) -> Option<u8> {
+    let owner = account.owner.to_string();
+    if owner != TOKEN_PROGRAM_ID && owner != TOKEN_2022_PROGRAM_ID {
+        return None;
+    }
+    if account.data.len() < MINT_LEN || account.data[MINT_IS_INITIALIZED_OFFSET] == 0 {
+        return None;
+    }
+    Some(account.data[MINT_DECIMALS_OFFSET])
+}
+
+/// Initialized mint of the token program, without authorities nor supply
+pub fn mint_account(decimals: u8) -> Account {
+    let mut data = vec![0u8; MINT_LEN];
+    data[MINT_DECIMALS_OFFSET] = decimals;
+    data[MINT_IS_INITIALIZED_OFFSET] = 1;
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: Pubkey::from_str(TOKEN_PROGRAM_ID).unwrap(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
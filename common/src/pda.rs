@@ -0,0 +1,33 @@
+//! Addresses of the gauge program accounts
+
+use anchor_lang::prelude::Pubkey;
+
+pub fn find_gauge_factory_addr(base: &Pubkey) -> Pubkey {
+    let (gauge_factory, _bump) =
+        Pubkey::find_program_address(&[b"GaugeFactory".as_ref(), base.as_ref()], &gauge::id());
+    gauge_factory
+}
+
+pub fn find_epoch_gauge_addr(gauge: &Pubkey, voting_epoch: u32) -> Pubkey {
+    let (epoch_gauge, _bump) = Pubkey::find_program_address(
+        &[
+            b"EpochGauge".as_ref(),
+            gauge.as_ref(),
+            voting_epoch.to_le_bytes().as_ref(),
+        ],
+        &gauge::id(),
+    );
+    epoch_gauge
+}
+
+pub fn find_bribe_addr(gauge_factory: &Pubkey, bribe_index: u32) -> Pubkey {
+    let (bribe, _bump) = Pubkey::find_program_address(
+        &[
+            b"Bribe".as_ref(),
+            gauge_factory.as_ref(),
+            bribe_index.to_le_bytes().as_ref(),
+        ],
+        &gauge::id(),
+    );
+    bribe
+}
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS public.bribe_gauge_factory_bribe_index_idx;
+DROP INDEX IF EXISTS public.epoch_gauge_gauge_factory_voting_epoch_idx;
+DROP INDEX IF EXISTS public.crawl_config_gauge_factory_idx;
+
+ALTER TABLE public.bribe DROP COLUMN IF EXISTS gauge_factory;
+ALTER TABLE public.epoch_gauge DROP COLUMN IF EXISTS gauge_factory;
+ALTER TABLE public.crawl_config DROP COLUMN IF EXISTS gauge_factory;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- rows written before are claimed by the default gauge factory at startup
+ALTER TABLE public.crawl_config ADD COLUMN IF NOT EXISTS gauge_factory VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE public.epoch_gauge ADD COLUMN IF NOT EXISTS gauge_factory VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE public.bribe ADD COLUMN IF NOT EXISTS gauge_factory VARCHAR NOT NULL DEFAULT '';
+
+-- the legacy table had no key and may hold several rows: keep the one crawled the furthest up
+DELETE FROM public.crawl_config c
+USING (
+    SELECT ctid, ROW_NUMBER() OVER (
+        PARTITION BY gauge_factory ORDER BY voting_epoch_up DESC, voting_epoch_down ASC
+    ) AS rank
+    FROM public.crawl_config
+) ranked
+WHERE c.ctid = ranked.ctid AND ranked.rank > 1;
+
+CREATE UNIQUE INDEX IF NOT EXISTS crawl_config_gauge_factory_idx ON public.crawl_config (gauge_factory);
+CREATE INDEX IF NOT EXISTS epoch_gauge_gauge_factory_voting_epoch_idx ON public.epoch_gauge (gauge_factory, voting_epoch);
+CREATE INDEX IF NOT EXISTS bribe_gauge_factory_bribe_index_idx ON public.bribe (gauge_factory, bribe_index);
@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- one row per (gauge, voting epoch) checked by the crawler, found tells whether the epoch gauge exists on chain
+CREATE TABLE IF NOT EXISTS public.crawl_coverage (
+    gauge_factory VARCHAR NOT NULL,
+    gauge VARCHAR NOT NULL,
+    voting_epoch BIGINT NOT NULL,
+    found BOOL NOT NULL,
+    checked_at BIGINT NOT NULL,
+    CONSTRAINT crawl_coverage_pk PRIMARY KEY(gauge_factory, gauge, voting_epoch)
+);
+
+-- epoch gauges crawled before are covered
+INSERT INTO public.crawl_coverage (gauge_factory, gauge, voting_epoch, found, checked_at)
+SELECT gauge_factory, gauge, voting_epoch, true, 0 FROM public.epoch_gauge
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- fields of stored rows which differ from the chain, found by the verifier
+CREATE TABLE IF NOT EXISTS public.consistency_issue (
+    id BIGSERIAL PRIMARY KEY,
+    gauge_factory VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    field VARCHAR NOT NULL,
+    db_value VARCHAR NOT NULL,
+    chain_value VARCHAR NOT NULL,
+    detected_at BIGINT NOT NULL,
+    resolved_at BIGINT,
+    repaired BOOL NOT NULL DEFAULT false
+);
+
+-- a field has at most one open issue
+CREATE UNIQUE INDEX IF NOT EXISTS consistency_issue_open_idx ON public.consistency_issue (address, field) WHERE resolved_at IS NULL;
+CREATE INDEX IF NOT EXISTS consistency_issue_gauge_factory_idx ON public.consistency_issue (gauge_factory, detected_at);
@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- actions taken through the admin routes
+CREATE TABLE IF NOT EXISTS public.admin_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    gauge_factory VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    params VARCHAR NOT NULL,
+    remote_addr VARCHAR NOT NULL,
+    outcome VARCHAR NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS admin_audit_log_gauge_factory_idx ON public.admin_audit_log (gauge_factory, created_at);
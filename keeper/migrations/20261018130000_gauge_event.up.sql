@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- lifecycle of the gauges of a factory: created, disabled, enabled, removed
+CREATE TABLE IF NOT EXISTS public.gauge_event (
+    id BIGSERIAL PRIMARY KEY,
+    gauge_factory VARCHAR NOT NULL,
+    gauge VARCHAR NOT NULL,
+    event_type VARCHAR NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS gauge_event_gauge_factory_gauge_idx ON public.gauge_event (gauge_factory, gauge, id);
+CREATE INDEX IF NOT EXISTS gauge_event_gauge_factory_created_at_idx ON public.gauge_event (gauge_factory, created_at);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS public.gauge_event_gauge_factory_event_type_idx;
+ALTER TABLE public.gauge_event DROP COLUMN IF EXISTS new_value;
+ALTER TABLE public.gauge_event DROP COLUMN IF EXISTS old_value;
+ALTER TABLE public.gauge_event DROP COLUMN IF EXISTS slot;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- events are stamped with the cluster slot, key changes carry the previous and the new key
+ALTER TABLE public.gauge_event ADD COLUMN IF NOT EXISTS slot BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE public.gauge_event ADD COLUMN IF NOT EXISTS old_value VARCHAR;
+ALTER TABLE public.gauge_event ADD COLUMN IF NOT EXISTS new_value VARCHAR;
+
+CREATE INDEX IF NOT EXISTS gauge_event_gauge_factory_event_type_idx ON public.gauge_event (gauge_factory, event_type, created_at);
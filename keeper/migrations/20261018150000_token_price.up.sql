@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- USD price of the tokens during each voting epoch, the latest snapshot of the epoch is kept
+CREATE TABLE IF NOT EXISTS public.token_price (
+    gauge_factory VARCHAR NOT NULL,
+    voting_epoch BIGINT NOT NULL,
+    token_mint VARCHAR NOT NULL,
+    price_usd DOUBLE PRECISION NOT NULL,
+    updated_at BIGINT NOT NULL,
+    CONSTRAINT token_price_pk PRIMARY KEY(gauge_factory, voting_epoch, token_mint)
+);
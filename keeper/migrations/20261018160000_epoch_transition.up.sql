@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Voting epochs started by the crank, with the trigger_next_epoch transaction
+CREATE TABLE IF NOT EXISTS public.epoch_transition (
+    gauge_factory VARCHAR NOT NULL,
+    voting_epoch BIGINT NOT NULL,
+    signature VARCHAR NOT NULL,
+    started_at BIGINT NOT NULL,
+    CONSTRAINT epoch_transition_pk PRIMARY KEY(gauge_factory, voting_epoch)
+);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE public.token DROP COLUMN IF EXISTS name;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- name from the metaplex metadata of the mint
+ALTER TABLE public.token ADD COLUMN IF NOT EXISTS name VARCHAR NOT NULL DEFAULT '';
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS public.epoch_cache_invalidation;
+DROP TABLE IF EXISTS public.paused_task;
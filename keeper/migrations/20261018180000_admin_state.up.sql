@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- tasks paused by an operator, every replica reads them so the pause reaches the leader
+CREATE TABLE IF NOT EXISTS public.paused_task (
+    gauge_factory VARCHAR NOT NULL,
+    task VARCHAR NOT NULL,
+    paused_at BIGINT NOT NULL,
+    CONSTRAINT paused_task_pk PRIMARY KEY(gauge_factory, task)
+);
+
+-- bumped by every invalidation of the epoch cache, replicas drop their cache when it changes
+CREATE TABLE IF NOT EXISTS public.epoch_cache_invalidation (
+    gauge_factory VARCHAR NOT NULL,
+    generation BIGINT NOT NULL,
+    invalidated_at BIGINT NOT NULL,
+    CONSTRAINT epoch_cache_invalidation_pk PRIMARY KEY(gauge_factory)
+);
@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- last recorded status of every gauge, the scans are compared with it
+CREATE TABLE IF NOT EXISTS public.gauge_snapshot (
+    gauge_factory VARCHAR NOT NULL,
+    gauge VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    CONSTRAINT gauge_snapshot_pk PRIMARY KEY(gauge_factory, gauge)
+);
+
+-- factories which already recorded events keep their baseline
+INSERT INTO public.gauge_snapshot (gauge_factory, gauge, status)
+SELECT DISTINCT ON (gauge_factory, gauge) gauge_factory, gauge, event_type FROM public.gauge_event
+WHERE event_type IN ('created', 'disabled', 'enabled', 'removed')
+ORDER BY gauge_factory, gauge, id DESC
+ON CONFLICT DO NOTHING;
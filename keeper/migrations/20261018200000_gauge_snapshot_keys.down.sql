@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE public.gauge_snapshot DROP COLUMN IF EXISTS token_b_fee_key;
+ALTER TABLE public.gauge_snapshot DROP COLUMN IF EXISTS token_a_fee_key;
+ALTER TABLE public.gauge_snapshot DROP COLUMN IF EXISTS quarry;
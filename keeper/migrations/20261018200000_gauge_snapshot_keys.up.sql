@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- last recorded quarry and fee keys of every gauge, empty until the next scan records them
+ALTER TABLE public.gauge_snapshot ADD COLUMN IF NOT EXISTS quarry VARCHAR;
+ALTER TABLE public.gauge_snapshot ADD COLUMN IF NOT EXISTS token_a_fee_key VARCHAR;
+ALTER TABLE public.gauge_snapshot ADD COLUMN IF NOT EXISTS token_b_fee_key VARCHAR;
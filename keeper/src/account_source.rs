@@ -0,0 +1,322 @@
+//! Source of on-chain accounts and sink of crank transactions, either the RPC providers or
+//! an in-memory set of accounts, so the crawler and crank logic can run against fixtures
+
+use crate::client_pool::EndpointHealth;
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anchor_client::solana_client::rpc_response::RpcSimulateTransactionResult;
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::hash::Hash;
+use anchor_client::solana_sdk::rent::Rent;
+use anchor_client::solana_sdk::signature::Signature;
+use anchor_client::solana_sdk::sysvar::{self, clock::Clock};
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{AccountDeserialize, AccountSerialize, Discriminator, Owner};
+use anyhow::Result;
+use async_trait::async_trait;
+use dao_common::account::{anchor_account, filter_matches};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::task::spawn_blocking;
+
+#[async_trait]
+pub trait AccountSource: Send + Sync {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>>;
+
+    /// Accounts aligned with the keys, None for an account which does not exist
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>>;
+
+    /// Keys of the program accounts matching the filters, without their data
+    async fn get_program_account_keys(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<Pubkey>>;
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64>;
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64>;
+
+    async fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    async fn send_and_confirm_transaction(&self, tx: &Transaction) -> Result<Signature>;
+
+    async fn simulate_transaction(&self, tx: &Transaction) -> Result<RpcSimulateTransactionResult>;
+
+    /// Health of the RPC providers behind the source, if any
+    fn get_health(&self) -> Vec<EndpointHealth> {
+        vec![]
+    }
+}
+
+impl dyn AccountSource {
+    /// Fetch and decode an anchor account, failing if it does not exist
+    pub async fn get_anchor_account<T: AccountDeserialize>(&self, pubkey: &Pubkey) -> Result<T> {
+        let account = self
+            .get_account(pubkey)
+            .await?
+            .ok_or_else(|| anyhow::Error::msg(format!("account {} not found", pubkey)))?;
+        Ok(T::try_deserialize(&mut account.data.as_ref())?)
+    }
+
+    /// Fetch and decode anchor accounts, aligned with the keys. Each key is None when the account
+    /// does not exist, or an error when it can not be decoded, so one bad account does not hide the others.
+    pub async fn get_anchor_accounts<T: AccountDeserialize>(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> Result<Vec<Result<Option<T>>>> {
+        let accounts = self.get_multiple_accounts(pubkeys).await?;
+        Ok(accounts
+            .into_iter()
+            .map(|account| match account {
+                Some(account) => Ok(Some(T::try_deserialize(&mut account.data.as_ref())?)),
+                None => Ok(None),
+            })
+            .collect())
+    }
+
+    /// Fetch and decode all anchor accounts of a type, matching its discriminator and the filters.
+    /// A full scan can return thousands of accounts, they are decoded off the async workers.
+    pub async fn get_anchor_program_accounts<T>(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, T)>>
+    where
+        T: AccountDeserialize + Discriminator + Send + 'static,
+    {
+        let mut all_filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            &T::discriminator(),
+        ))];
+        all_filters.extend(filters);
+        let accounts = self.get_program_accounts(program_id, all_filters).await?;
+
+        spawn_blocking(move || -> Result<Vec<(Pubkey, T)>> {
+            let mut decoded = vec![];
+            for (pubkey, account) in accounts.into_iter() {
+                decoded.push((pubkey, T::try_deserialize(&mut account.data.as_ref())?));
+            }
+            Ok(decoded)
+        })
+        .await?
+    }
+}
+
+/// Accounts held in memory, seeded by tests or fixtures. Transactions are recorded, not executed,
+/// so a test changes the accounts itself to play what the program would do.
+#[derive(Default)]
+pub struct MemoryAccountSource {
+    accounts: Mutex<BTreeMap<Pubkey, Account>>,
+    sent_transactions: Mutex<Vec<Transaction>>,
+    simulated_transactions: Mutex<Vec<Transaction>>,
+}
+
+impl MemoryAccountSource {
+    pub fn new() -> Self {
+        MemoryAccountSource::default()
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.accounts.lock().unwrap().insert(pubkey, account);
+    }
+
+    pub fn remove_account(&self, pubkey: &Pubkey) {
+        self.accounts.lock().unwrap().remove(pubkey);
+    }
+
+    /// Serialize an anchor account with its discriminator, owned by its program and rent exempt
+    pub fn set_anchor_account<T: AccountSerialize + Owner>(
+        &self,
+        pubkey: Pubkey,
+        value: &T,
+    ) -> Result<()> {
+        self.set_account(pubkey, anchor_account(value)?);
+        Ok(())
+    }
+
+    pub fn set_clock(&self, clock: &Clock) -> Result<()> {
+        let data = bincode::serialize(clock)?;
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: sysvar::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.set_account(sysvar::clock::id(), account);
+        Ok(())
+    }
+
+    /// Fund a wallet, like the crank payer
+    pub fn set_balance(&self, pubkey: Pubkey, lamports: u64) {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(pubkey).or_insert_with(|| Account {
+            lamports: 0,
+            data: vec![],
+            owner: system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        });
+        account.lamports = lamports;
+    }
+
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.sent_transactions.lock().unwrap().clone()
+    }
+
+    pub fn simulated_transactions(&self) -> Vec<Transaction> {
+        self.simulated_transactions.lock().unwrap().clone()
+    }
+
+    fn matching_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &[RpcFilterType],
+    ) -> Vec<(Pubkey, Account)> {
+        let accounts = self.accounts.lock().unwrap();
+        accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| filters.iter().all(|x| filter_matches(x, &account.data)))
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl AccountSource for MemoryAccountSource {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.accounts.lock().unwrap().get(pubkey).cloned())
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let accounts = self.accounts.lock().unwrap();
+        Ok(pubkeys.iter().map(|x| accounts.get(x).cloned()).collect())
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        Ok(self.matching_accounts(program_id, &filters))
+    }
+
+    async fn get_program_account_keys(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<Pubkey>> {
+        Ok(self
+            .matching_accounts(program_id, &filters)
+            .into_iter()
+            .map(|x| x.0)
+            .collect())
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        let accounts = self.accounts.lock().unwrap();
+        Ok(accounts.get(pubkey).map(|x| x.lamports).unwrap_or(0))
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(Rent::default().minimum_balance(data_len))
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(Hash::default())
+    }
+
+    async fn send_and_confirm_transaction(&self, tx: &Transaction) -> Result<Signature> {
+        self.sent_transactions.lock().unwrap().push(tx.clone());
+        Ok(tx.signatures.get(0).cloned().unwrap_or_default())
+    }
+
+    async fn simulate_transaction(&self, tx: &Transaction) -> Result<RpcSimulateTransactionResult> {
+        self.simulated_transactions.lock().unwrap().push(tx.clone());
+        Ok(RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(vec![]),
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_account_source {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_program_accounts_filters() {
+        let source = MemoryAccountSource::new();
+        let program_id = Pubkey::new_unique();
+        let owned = |data: Vec<u8>, owner: Pubkey| Account {
+            lamports: 1,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let matching = Pubkey::new_unique();
+        source.set_account(matching, owned(vec![1, 2, 3, 4], program_id));
+        // wrong size, wrong bytes, wrong owner
+        source.set_account(Pubkey::new_unique(), owned(vec![1, 2, 3], program_id));
+        source.set_account(Pubkey::new_unique(), owned(vec![1, 9, 3, 4], program_id));
+        source.set_account(
+            Pubkey::new_unique(),
+            owned(vec![1, 2, 3, 4], Pubkey::new_unique()),
+        );
+
+        let filters = vec![
+            RpcFilterType::DataSize(4),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(1, &[2, 3])),
+        ];
+        let keys = source
+            .get_program_account_keys(&program_id, filters)
+            .await
+            .unwrap();
+        assert_eq!(keys, vec![matching]);
+    }
+
+    #[tokio::test]
+    async fn test_get_anchor_accounts_aligned_with_keys() {
+        let source = MemoryAccountSource::new();
+        let quarry_pk = Pubkey::new_unique();
+        let missing_pk = Pubkey::new_unique();
+        source
+            .set_anchor_account(
+                quarry_pk,
+                &quarry::Quarry {
+                    rewards_share: 42,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let chain: &dyn AccountSource = &source;
+        let quarries = chain
+            .get_anchor_accounts::<quarry::Quarry>(&[missing_pk, quarry_pk])
+            .await
+            .unwrap();
+        assert!(quarries[0].as_ref().unwrap().is_none());
+        assert_eq!(
+            quarries[1]
+                .as_ref()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .rewards_share,
+            42
+        );
+    }
+}
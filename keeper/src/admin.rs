@@ -0,0 +1,312 @@
+//! Operator actions on the crawlers, served by the authenticated admin routes
+
+use crate::core::{current_unix_timestamp, find_bribe_addr, find_epoch_gauge_addr, Core};
+use crate::database::*;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Background tasks of a gauge factory which can be paused
+pub const PAUSABLE_TASKS: &[&str] = &[
+    "process_monitor_gauge_factory",
+    "process_monitor_gauge",
+    "process_crawl_epoch_up",
+    "process_crawl_epoch_down",
+    "process_crawl_bribe",
+    "process_cache_latest_epoches",
+    "process_backfill_epoch_gauges",
+    "process_verify_consistency",
+    "process_snapshot_token_prices",
+    "process_discover_tokens",
+    "process_sync_gauge",
+];
+
+/// Most epochs or bribe indexes re-crawled by a single request
+pub const MAX_RECRAWL_RANGE: i64 = 100;
+
+/// The paused tasks are read again from the database at this interval, for the pauses made
+/// on other replicas
+const PAUSED_TASKS_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Background tasks paused by an operator, they skip their runs until resumed. The pauses are
+/// stored in the database, this is the last state read from it
+#[derive(Default)]
+pub struct TaskControl {
+    paused: Mutex<HashSet<String>>,
+    refreshed_at: Mutex<Option<Instant>>,
+}
+
+impl TaskControl {
+    pub fn is_paused(&self, task: &str) -> bool {
+        self.paused.lock().unwrap().contains(task)
+    }
+
+    pub fn set_paused(&self, tasks: Vec<String>) {
+        *self.paused.lock().unwrap() = tasks.into_iter().collect();
+        *self.refreshed_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Whether the paused tasks should be read again from the database
+    pub fn is_stale(&self) -> bool {
+        match *self.refreshed_at.lock().unwrap() {
+            Some(refreshed_at) => refreshed_at.elapsed() >= PAUSED_TASKS_REFRESH_INTERVAL,
+            None => true,
+        }
+    }
+
+    pub fn pause(&self, task: &str) {
+        self.paused.lock().unwrap().insert(task.to_string());
+    }
+
+    pub fn resume(&self, task: &str) {
+        self.paused.lock().unwrap().remove(task);
+    }
+
+    pub fn get_paused(&self) -> Vec<String> {
+        let mut paused: Vec<String> = self.paused.lock().unwrap().iter().cloned().collect();
+        paused.sort();
+        paused
+    }
+}
+
+fn check_pausable(task: &str) -> Result<()> {
+    if !PAUSABLE_TASKS.contains(&task) {
+        return Err(anyhow::Error::msg(format!("unknown task {}", task)));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlConfigRequest {
+    pub voting_epoch_up: i64,
+    pub voting_epoch_down: i64,
+}
+
+/// Inclusive range of voting epochs or bribe indexes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeRequest {
+    pub from: i64,
+    pub to: i64,
+}
+
+impl RangeRequest {
+    pub fn validate(&self) -> Result<()> {
+        if self.from < 0 || self.from > self.to {
+            return Err(anyhow::Error::msg("invalid range"));
+        }
+        if self.to - self.from >= MAX_RECRAWL_RANGE {
+            return Err(anyhow::Error::msg(format!(
+                "range is larger than {}",
+                MAX_RECRAWL_RANGE
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TasksInfo {
+    pub tasks: Vec<String>,
+    pub paused: Vec<String>,
+}
+
+impl Core {
+    pub async fn admin_reset_crawl_config(&self, request: &CrawlConfigRequest) -> Result<String> {
+        if request.voting_epoch_down < -1 || request.voting_epoch_up < request.voting_epoch_down {
+            return Err(anyhow::Error::msg("invalid crawl config"));
+        }
+        update_crawl_config(
+            &self.pg_pool,
+            &self.get_gauge_factory_key(),
+            request.voting_epoch_up,
+            request.voting_epoch_down,
+        )
+        .await?;
+        Ok(String::from("crawl config reset"))
+    }
+
+    /// Fetch the epoch gauges of every gauge for the epochs again, overwriting the stored ones
+    pub async fn admin_recrawl_epochs(&self, request: &RangeRequest) -> Result<String> {
+        request.validate()?;
+        let gauges: Vec<Pubkey> = {
+            let state = self.state.lock().unwrap();
+            state
+                .get_gauges()
+                .iter()
+                .map(|x| Pubkey::from_str(&x.pubkey))
+                .collect::<std::result::Result<Vec<Pubkey>, _>>()?
+        };
+        let mut pairs: Vec<(Pubkey, u32)> = vec![];
+        for voting_epoch in request.from..=request.to {
+            for gauge in gauges.iter() {
+                pairs.push((*gauge, u32::try_from(voting_epoch)?));
+            }
+        }
+
+        let epoch_pubkeys: Vec<Pubkey> = pairs
+            .iter()
+            .map(|(gauge, voting_epoch)| find_epoch_gauge_addr(gauge, *voting_epoch))
+            .collect();
+        let accounts = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&epoch_pubkeys)
+            .await?;
+
+        let mut epoch_gauges = vec![];
+        let mut coverage = vec![];
+        for ((gauge, voting_epoch), account) in pairs.iter().zip(accounts.into_iter()) {
+            let account = account?;
+            coverage.push((*gauge, i64::from(*voting_epoch), account.is_some()));
+            if let Some(epoch_gauge) = account {
+                epoch_gauges.push(epoch_gauge);
+            }
+        }
+
+        let gauge_factory_key = self.get_gauge_factory_key();
+        save_epoch_gauges(&self.pg_pool, &gauge_factory_key, &epoch_gauges).await?;
+        save_crawl_coverage(
+            &self.pg_pool,
+            &gauge_factory_key,
+            &coverage,
+            current_unix_timestamp(),
+        )
+        .await?;
+        Ok(format!("{} epoch gauges re-crawled", epoch_gauges.len()))
+    }
+
+    /// Fetch the bribes of the indexes again, overwriting the stored ones
+    pub async fn admin_recrawl_bribes(&self, request: &RangeRequest) -> Result<String> {
+        request.validate()?;
+        let gauge_factory = self.get_gauge_factory_addr();
+        let mut bribe_pubkeys = vec![];
+        for bribe_index in request.from..=request.to {
+            bribe_pubkeys.push(find_bribe_addr(&gauge_factory, u32::try_from(bribe_index)?));
+        }
+        let accounts = self
+            .chain
+            .get_anchor_accounts::<gauge::Bribe>(&bribe_pubkeys)
+            .await?;
+
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let mut num_bribes = 0;
+        for (bribe, account) in bribe_pubkeys.iter().zip(accounts.into_iter()) {
+            if let Some(bribe_state) = account? {
+                save_bribe(&self.pg_pool, &gauge_factory_key, *bribe, &bribe_state).await?;
+                num_bribes += 1;
+            }
+        }
+        Ok(format!("{} bribes re-crawled", num_bribes))
+    }
+
+    /// Drop the cached epochs of every replica: this one right away, the others at their next
+    /// cache run, which sees the new generation. They are served from the database until then
+    pub async fn admin_invalidate_epoch_cache(&self) -> Result<String> {
+        let generation = invalidate_epoch_cache(
+            &self.pg_pool,
+            &self.get_gauge_factory_key(),
+            current_unix_timestamp(),
+        )
+        .await?;
+        let mut epochs = self.epochs.lock().unwrap();
+        epochs.invalidate(generation);
+        Ok(String::from("epoch cache invalidated"))
+    }
+
+    pub async fn admin_pause_task(&self, task: &str) -> Result<String> {
+        check_pausable(task)?;
+        save_paused_task(
+            &self.pg_pool,
+            &self.get_gauge_factory_key(),
+            task,
+            current_unix_timestamp(),
+        )
+        .await?;
+        self.tasks.pause(task);
+        Ok(format!("task {} paused", task))
+    }
+
+    pub async fn admin_resume_task(&self, task: &str) -> Result<String> {
+        check_pausable(task)?;
+        delete_paused_task(&self.pg_pool, &self.get_gauge_factory_key(), task).await?;
+        self.tasks.resume(task);
+        Ok(format!("task {} resumed", task))
+    }
+
+    async fn refresh_paused_tasks(&self) -> Result<()> {
+        let paused = get_paused_tasks(&self.pg_pool, &self.get_gauge_factory_key()).await?;
+        self.tasks.set_paused(paused);
+        Ok(())
+    }
+
+    /// Whether the task is paused. The pauses are read again from the database every
+    /// PAUSED_TASKS_REFRESH_INTERVAL, so a pause made on any replica reaches the leader,
+    /// and survives restarts. The last state read is used when the database can not be read
+    pub async fn is_task_paused(&self, task: &str) -> bool {
+        if self.tasks.is_stale() {
+            if let Err(err) = self.refresh_paused_tasks().await {
+                println!("cannot read paused tasks {}", err);
+            }
+        }
+        self.tasks.is_paused(task)
+    }
+
+    pub async fn admin_get_tasks(&self) -> Result<TasksInfo> {
+        self.refresh_paused_tasks().await?;
+        Ok(TasksInfo {
+            tasks: PAUSABLE_TASKS.iter().map(|x| x.to_string()).collect(),
+            paused: self.tasks.get_paused(),
+        })
+    }
+
+    /// Record an admin action and its outcome in the audit log
+    pub async fn record_admin_action(
+        &self,
+        action: &str,
+        params: &str,
+        remote_addr: &str,
+        result: &Result<String>,
+    ) -> Result<()> {
+        let outcome = match result {
+            Ok(message) => message.clone(),
+            Err(err) => format!("error: {}", err),
+        };
+        insert_admin_audit_log(
+            &self.pg_pool,
+            &self.get_gauge_factory_key(),
+            action,
+            params,
+            remote_addr,
+            &outcome,
+            current_unix_timestamp(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test_admin {
+    use super::*;
+
+    #[test]
+    fn test_task_control() {
+        let tasks = TaskControl::default();
+        assert!(tasks.is_stale());
+
+        tasks.set_paused(vec![String::from("process_crawl_bribe")]);
+        assert!(!tasks.is_stale());
+        assert!(tasks.is_paused("process_crawl_bribe"));
+
+        // a pause or a resume of this replica applies right away
+        tasks.pause("process_sync_gauge");
+        tasks.resume("process_crawl_bribe");
+        assert_eq!(tasks.get_paused(), vec![String::from("process_sync_gauge")]);
+
+        *tasks.refreshed_at.lock().unwrap() = Some(Instant::now() - PAUSED_TASKS_REFRESH_INTERVAL);
+        assert!(tasks.is_stale());
+    }
+}
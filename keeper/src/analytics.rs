@@ -0,0 +1,794 @@
+//! Analytics derived from the stored epoch gauges, computed here once for every API consumer
+
+use crate::core::Core;
+use crate::database::*;
+use crate::state::{GaugeState, RewarderState};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+const DAYS_PER_YEAR: u64 = 365;
+/// Epochs of the efficiency series of a gauge
+const MAX_EFFICIENCY_EPOCHS: i64 = 52;
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct GaugeVoteAnalytics {
+    pub gauge_pk: String,
+    /// From 1, by voting power
+    pub rank: u32,
+    pub voting_power: u64,
+    /// Share of the votes of the epoch, between 0 and 1
+    pub share: f64,
+    pub previous_voting_power: u64,
+    pub previous_share: f64,
+    pub voting_power_delta: i64,
+    /// None when the gauge had no votes in the previous epoch
+    pub voting_power_delta_percent: Option<f64>,
+    /// Rewards of the quarry once the epoch is the rewards epoch, at the current rewarder rate
+    pub annual_rewards: u64,
+    pub daily_rewards: u64,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EpochVoteAnalytics {
+    pub epoch: u64,
+    pub total_power: u64,
+    pub previous_total_power: u64,
+    pub annual_rewards_rate: u64,
+    pub gauges: Vec<GaugeVoteAnalytics>,
+}
+
+/// Voting power of every gauge of the epoch
+fn voting_powers(epoch_gauges: &[EpochGauge]) -> Result<HashMap<String, u64>> {
+    let mut powers = HashMap::new();
+    for epoch_gauge in epoch_gauges.iter() {
+        powers.insert(
+            epoch_gauge.gauge.clone(),
+            epoch_gauge.total_power.parse::<u64>()?,
+        );
+    }
+    Ok(powers)
+}
+
+fn share(power: u64, total_power: u128) -> f64 {
+    if total_power == 0 {
+        return 0.0;
+    }
+    power as f64 / total_power as f64
+}
+
+/// Part of the annual rewards rate going to a share of the total power
+pub fn split_rewards(annual_rewards_rate: u64, power: u64, total_power: u128) -> Result<u64> {
+    if total_power == 0 {
+        return Ok(0);
+    }
+    Ok(u64::try_from(
+        u128::from(annual_rewards_rate) * u128::from(power) / total_power,
+    )?)
+}
+
+/// Rewards share and annual rewards of the quarry of a gauge once the shares are synced
+#[derive(Default, PartialEq, Debug, Clone, Copy)]
+pub struct QuarryEmission {
+    pub rewards_share: u64,
+    pub annual_rewards: u64,
+}
+
+#[derive(Default, PartialEq, Debug, Clone)]
+pub struct EmissionSplit {
+    /// Rewards shares of all the quarries of the rewarder once synced
+    pub total_rewards_shares: u128,
+    /// By gauge, gauges gone from the factory are not synced and get nothing
+    pub gauges: HashMap<String, QuarryEmission>,
+}
+
+impl EmissionSplit {
+    pub fn get(&self, gauge: &str) -> QuarryEmission {
+        self.gauges.get(gauge).cloned().unwrap_or_default()
+    }
+}
+
+/// Split of the rewarder rate once the quarries of the gauges are synced with the votes of an
+/// epoch, like the sync_gauge crank: disabled gauges get no share, and the quarries of the
+/// rewarder outside of the gauges keep theirs
+pub fn split_emissions(
+    rewarder: &RewarderState,
+    gauges: &[GaugeState],
+    quarries: &HashMap<Pubkey, quarry::Quarry>,
+    powers: &HashMap<String, u64>,
+) -> Result<EmissionSplit> {
+    let mut gauge_shares: u128 = 0;
+    let mut synced_gauge_shares: u128 = 0;
+    let mut rewards_shares = vec![];
+    for gauge in gauges.iter() {
+        gauge_shares += quarries
+            .get(&Pubkey::from_str(&gauge.quarry)?)
+            .map(|x| u128::from(x.rewards_share))
+            .unwrap_or(0);
+        let rewards_share = if gauge.is_disabled {
+            0
+        } else {
+            powers.get(&gauge.pubkey).cloned().unwrap_or(0)
+        };
+        synced_gauge_shares += u128::from(rewards_share);
+        rewards_shares.push((gauge.pubkey.clone(), rewards_share));
+    }
+
+    let total_rewards_shares = (u128::from(rewarder.total_rewards_shares) + synced_gauge_shares)
+        .saturating_sub(gauge_shares);
+    let mut split = HashMap::new();
+    for (gauge, rewards_share) in rewards_shares {
+        let annual_rewards = split_rewards(
+            rewarder.annual_rewards_rate,
+            rewards_share,
+            total_rewards_shares,
+        )?;
+        split.insert(
+            gauge,
+            QuarryEmission {
+                rewards_share,
+                annual_rewards,
+            },
+        );
+    }
+    Ok(EmissionSplit {
+        total_rewards_shares,
+        gauges: split,
+    })
+}
+
+/// Share, rank and change against the previous epoch of every gauge voted in either epoch
+pub fn compute_vote_analytics(
+    epoch: u64,
+    powers: &HashMap<String, u64>,
+    previous_powers: &HashMap<String, u64>,
+    annual_rewards_rate: u64,
+    emissions: &EmissionSplit,
+) -> Result<EpochVoteAnalytics> {
+    let total_power: u128 = powers.values().map(|x| u128::from(*x)).sum();
+    let previous_total_power: u128 = previous_powers.values().map(|x| u128::from(*x)).sum();
+
+    let mut gauges: Vec<(&String, u64)> = powers
+        .iter()
+        .map(|(gauge, power)| (gauge, *power))
+        .chain(
+            previous_powers
+                .keys()
+                .filter(|x| !powers.contains_key(*x))
+                .map(|gauge| (gauge, 0)),
+        )
+        .collect();
+    // ties are ranked by address, so the ranks are stable between requests
+    gauges.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut analytics = vec![];
+    for (i, (gauge, voting_power)) in gauges.into_iter().enumerate() {
+        let previous_voting_power = previous_powers.get(gauge).cloned().unwrap_or(0);
+        let voting_power_delta =
+            i64::try_from(i128::from(voting_power) - i128::from(previous_voting_power))?;
+        let voting_power_delta_percent = if previous_voting_power == 0 {
+            None
+        } else {
+            Some(voting_power_delta as f64 * 100.0 / previous_voting_power as f64)
+        };
+        let annual_rewards = emissions.get(gauge).annual_rewards;
+        analytics.push(GaugeVoteAnalytics {
+            gauge_pk: gauge.clone(),
+            rank: u32::try_from(i + 1)?,
+            voting_power,
+            share: share(voting_power, total_power),
+            previous_voting_power,
+            previous_share: share(previous_voting_power, previous_total_power),
+            voting_power_delta,
+            voting_power_delta_percent,
+            annual_rewards,
+            daily_rewards: annual_rewards / DAYS_PER_YEAR,
+        });
+    }
+
+    Ok(EpochVoteAnalytics {
+        epoch,
+        total_power: u64::try_from(total_power)?,
+        previous_total_power: u64::try_from(previous_total_power)?,
+        annual_rewards_rate,
+        gauges: analytics,
+    })
+}
+
+/// Where the price of a value comes from
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    /// Recorded during the voting period of the epoch
+    Snapshot,
+    /// Latest recorded price, the epoch has no snapshot of the token
+    Latest,
+}
+
+impl PriceSource {
+    /// Source of a value summed from several tokens, latest as soon as one token uses it
+    fn merge(source: Option<PriceSource>, other: PriceSource) -> Option<PriceSource> {
+        match source {
+            Some(PriceSource::Latest) => Some(PriceSource::Latest),
+            _ => Some(other),
+        }
+    }
+}
+
+/// Decimals and USD price of a token
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TokenValue {
+    pub decimals: u32,
+    pub price_usd: f64,
+    pub source: PriceSource,
+}
+
+impl TokenValue {
+    pub fn usd(&self, amount: u64) -> f64 {
+        amount as f64 / 10f64.powi(self.decimals as i32) * self.price_usd
+    }
+}
+
+/// Token prices of the epochs, an epoch without a snapshot of a token uses its latest price,
+/// reported as the source of the value
+#[derive(Default, Debug, Clone)]
+pub struct PriceBook {
+    pub decimals: HashMap<String, u32>,
+    pub snapshots: HashMap<(i64, String), f64>,
+    pub latest: HashMap<String, f64>,
+}
+
+impl PriceBook {
+    pub fn new(tokens: &[Token], snapshots: &[TokenPrice], latest: &[TokenPrice]) -> Result<Self> {
+        let mut decimals = HashMap::new();
+        for token in tokens.iter() {
+            decimals.insert(token.address.clone(), u32::try_from(token.decimals)?);
+        }
+        Ok(PriceBook {
+            decimals,
+            snapshots: snapshots
+                .iter()
+                .map(|x| ((x.voting_epoch, x.token_mint.clone()), x.price_usd))
+                .collect(),
+            latest: latest
+                .iter()
+                .map(|x| (x.token_mint.clone(), x.price_usd))
+                .collect(),
+        })
+    }
+
+    pub fn token_value(&self, voting_epoch: i64, mint: &str) -> Option<TokenValue> {
+        let decimals = *self.decimals.get(mint)?;
+        let (price_usd, source) = match self.snapshots.get(&(voting_epoch, mint.to_string())) {
+            Some(price_usd) => (price_usd, PriceSource::Snapshot),
+            None => (self.latest.get(mint)?, PriceSource::Latest),
+        };
+        Some(TokenValue {
+            decimals,
+            price_usd: *price_usd,
+            source,
+        })
+    }
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct GaugeEfficiency {
+    pub gauge_pk: String,
+    pub voting_epoch: i64,
+    pub total_power: u64,
+    /// Bribes paid for the epoch
+    pub bribe_value_usd: f64,
+    pub fee_value_usd: f64,
+    /// None when no token of the value is priced
+    pub bribe_price_source: Option<PriceSource>,
+    pub fee_price_source: Option<PriceSource>,
+    /// None when the gauge has no votes
+    pub bribe_usd_per_vote: Option<f64>,
+    pub fee_usd_per_vote: Option<f64>,
+    /// Mints left out of the values, their decimals or price are unknown
+    pub unpriced_mints: Vec<String>,
+}
+
+/// USD value of the bribes and fees of a gauge in an epoch, per unit of voting power.
+/// The fees are left out when the mints of the gauge are unknown
+pub fn compute_gauge_efficiency(
+    epoch_gauge: &EpochGauge,
+    fee_mints: Option<(&str, &str)>,
+    bribes: &[Bribe],
+    prices: &PriceBook,
+) -> Result<GaugeEfficiency> {
+    let voting_epoch = epoch_gauge.voting_epoch;
+    let total_power = epoch_gauge.total_power.parse::<u64>()?;
+    let mut unpriced_mints = vec![];
+    let mut value_of = |mint: &str, amount: u64, source: &mut Option<PriceSource>| -> f64 {
+        match prices.token_value(voting_epoch, mint) {
+            Some(token) => {
+                *source = PriceSource::merge(*source, token.source);
+                token.usd(amount)
+            }
+            None => {
+                unpriced_mints.push(mint.to_string());
+                0.0
+            }
+        }
+    };
+
+    let mut bribe_value_usd = 0.0;
+    let mut bribe_price_source = None;
+    for bribe in bribes.iter().filter(|x| {
+        x.gauge == epoch_gauge.gauge
+            && x.bribe_rewards_epoch_start <= voting_epoch
+            && x.bribe_rewards_epoch_end >= voting_epoch
+    }) {
+        bribe_value_usd += value_of(
+            &bribe.token_mint,
+            bribe.reward_each_epoch.parse::<u64>()?,
+            &mut bribe_price_source,
+        );
+    }
+    let mut fee_value_usd = 0.0;
+    let mut fee_price_source = None;
+    if let Some((token_a_mint, token_b_mint)) = fee_mints {
+        fee_value_usd += value_of(
+            token_a_mint,
+            epoch_gauge.token_a_fee.parse::<u64>()?,
+            &mut fee_price_source,
+        );
+        fee_value_usd += value_of(
+            token_b_mint,
+            epoch_gauge.token_b_fee.parse::<u64>()?,
+            &mut fee_price_source,
+        );
+    }
+    unpriced_mints.sort();
+    unpriced_mints.dedup();
+
+    let per_vote = |value: f64| {
+        if total_power == 0 {
+            None
+        } else {
+            Some(value / total_power as f64)
+        }
+    };
+    Ok(GaugeEfficiency {
+        gauge_pk: epoch_gauge.gauge.clone(),
+        voting_epoch,
+        total_power,
+        bribe_value_usd,
+        fee_value_usd,
+        bribe_price_source,
+        fee_price_source,
+        bribe_usd_per_vote: per_vote(bribe_value_usd),
+        fee_usd_per_vote: per_vote(fee_value_usd),
+        unpriced_mints,
+    })
+}
+
+/// Cheapest votes first, gauges without bribes or votes last
+fn leaderboard_key(efficiency: &GaugeEfficiency) -> (u8, f64) {
+    match efficiency.bribe_usd_per_vote {
+        Some(value) if value > 0.0 => (0, value),
+        _ => (1, 0.0),
+    }
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct QuarryEmissionForecast {
+    pub quarry_pk: String,
+    pub gauge_pk: String,
+    pub rewards_share: u64,
+    pub annual_rewards: u64,
+    pub daily_rewards: u64,
+    /// Votes of the gauge in the current voting epoch, none for disabled gauges
+    pub projected_rewards_share: u64,
+    /// Share of the rewards of the rewarder, between 0 and 1
+    pub projected_share: f64,
+    pub projected_annual_rewards: u64,
+    pub projected_daily_rewards: u64,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EmissionForecast {
+    /// Voting epoch becoming the rewards epoch
+    pub voting_epoch: u64,
+    /// Unix timestamp the voting epoch ends at, the quarries are synced by the crank after it
+    pub next_epoch_starts_at: u64,
+    pub annual_rewards_rate: u64,
+    pub total_rewards_shares: u64,
+    pub projected_total_rewards_shares: u64,
+    pub quarries: Vec<QuarryEmissionForecast>,
+}
+
+/// Rewards shares and emissions of the quarries of the gauges once the votes of the voting epoch
+/// are synced. Quarries of the rewarder outside of the gauges keep their shares
+pub fn compute_emission_forecast(
+    voting_epoch: u64,
+    next_epoch_starts_at: u64,
+    rewarder: &RewarderState,
+    gauges: &[GaugeState],
+    quarries: &HashMap<Pubkey, quarry::Quarry>,
+    powers: &HashMap<String, u64>,
+) -> Result<EmissionForecast> {
+    let emissions = split_emissions(rewarder, gauges, quarries, powers)?;
+    let mut forecasts = vec![];
+    for gauge in gauges.iter() {
+        let quarry_state = quarries.get(&Pubkey::from_str(&gauge.quarry)?);
+        let annual_rewards = quarry_state.map(|x| x.annual_rewards_rate).unwrap_or(0);
+        let projected = emissions.get(&gauge.pubkey);
+        forecasts.push(QuarryEmissionForecast {
+            quarry_pk: gauge.quarry.clone(),
+            gauge_pk: gauge.pubkey.clone(),
+            rewards_share: quarry_state.map(|x| x.rewards_share).unwrap_or(0),
+            annual_rewards,
+            daily_rewards: annual_rewards / DAYS_PER_YEAR,
+            projected_rewards_share: projected.rewards_share,
+            projected_share: share(projected.rewards_share, emissions.total_rewards_shares),
+            projected_annual_rewards: projected.annual_rewards,
+            projected_daily_rewards: projected.annual_rewards / DAYS_PER_YEAR,
+        });
+    }
+    forecasts.sort_by(|a, b| {
+        b.projected_rewards_share
+            .cmp(&a.projected_rewards_share)
+            .then(a.quarry_pk.cmp(&b.quarry_pk))
+    });
+
+    Ok(EmissionForecast {
+        voting_epoch,
+        next_epoch_starts_at,
+        annual_rewards_rate: rewarder.annual_rewards_rate,
+        total_rewards_shares: rewarder.total_rewards_shares,
+        projected_total_rewards_shares: u64::try_from(emissions.total_rewards_shares)?,
+        quarries: forecasts,
+    })
+}
+
+impl Core {
+    /// Cached rewarder, gauges and quarries the emissions are split with
+    fn emission_inputs(
+        &self,
+    ) -> (
+        RewarderState,
+        Vec<GaugeState>,
+        HashMap<Pubkey, quarry::Quarry>,
+    ) {
+        let state = self.state.lock().unwrap();
+        (
+            state.rewarder.clone(),
+            state.get_gauges(),
+            state.quarries.clone(),
+        )
+    }
+
+    /// Emissions once the current voting epoch is the rewards epoch, from the votes cached
+    /// by process_cache_latest_epoches, so the forecast follows the votes during the epoch
+    pub async fn get_emission_forecast(&self) -> Result<EmissionForecast> {
+        let gauge_factory = self.get_gauge_factory();
+        let voting_epoch: u64 = gauge_factory.current_voting_epoch.into();
+        let powers: HashMap<String, u64> = self
+            .get_epoch_info(voting_epoch)
+            .await?
+            .into_iter()
+            .map(|x| (x.gauge_pk, x.voting_power))
+            .collect();
+        let (rewarder, gauges, quarries) = self.emission_inputs();
+        compute_emission_forecast(
+            voting_epoch,
+            gauge_factory.next_epoch_starts_at,
+            &rewarder,
+            &gauges,
+            &quarries,
+            &powers,
+        )
+    }
+
+    /// Token prices of the epochs, from the snapshots of process_snapshot_token_prices
+    async fn load_price_book(&self, from_epoch: i64, to_epoch: i64) -> Result<PriceBook> {
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let tokens = get_tokens(&self.pg_pool).await?;
+        let snapshots =
+            get_token_prices(&self.pg_pool, &gauge_factory_key, from_epoch, to_epoch).await?;
+        let latest = get_latest_token_prices(&self.pg_pool, &gauge_factory_key).await?;
+        PriceBook::new(&tokens, &snapshots, &latest)
+    }
+
+    fn fee_mints(&self) -> HashMap<String, (String, String)> {
+        self.get_gauges()
+            .into_iter()
+            .map(|x| (x.pubkey, (x.token_a_mint, x.token_b_mint)))
+            .collect()
+    }
+
+    /// Bribe and fee efficiency of a gauge over its latest epochs, oldest first
+    pub async fn get_gauge_efficiency_series(&self, gauge: &str) -> Result<Vec<GaugeEfficiency>> {
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let mut epoch_gauges = get_epoch_gauges_of_gauge(
+            &self.pg_pool,
+            &gauge_factory_key,
+            gauge,
+            MAX_EFFICIENCY_EPOCHS,
+        )
+        .await?;
+        epoch_gauges.reverse();
+        let (from_epoch, to_epoch) = match (epoch_gauges.first(), epoch_gauges.last()) {
+            (Some(first), Some(last)) => (first.voting_epoch, last.voting_epoch),
+            _ => return Ok(vec![]),
+        };
+        let bribes = get_bribes_of_gauge(&self.pg_pool, &gauge_factory_key, gauge).await?;
+        let prices = self.load_price_book(from_epoch, to_epoch).await?;
+        let fee_mints = self.fee_mints();
+
+        let mut series = vec![];
+        for epoch_gauge in epoch_gauges.iter() {
+            let mints = fee_mints
+                .get(&epoch_gauge.gauge)
+                .map(|(a, b)| (a.as_str(), b.as_str()));
+            series.push(compute_gauge_efficiency(
+                epoch_gauge,
+                mints,
+                &bribes,
+                &prices,
+            )?);
+        }
+        Ok(series)
+    }
+
+    /// Bribe cost per vote of every gauge of an epoch, the current voting epoch by default
+    pub async fn get_efficiency_leaderboard(
+        &self,
+        epoch: Option<u64>,
+    ) -> Result<Vec<GaugeEfficiency>> {
+        let epoch = match epoch {
+            Some(value) => i64::try_from(value)?,
+            None => self.get_gauge_factory().current_voting_epoch.into(),
+        };
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let epoch_gauges = get_epoch_gauges(&self.pg_pool, &gauge_factory_key, epoch).await?;
+        let bribes = get_bribes(&self.pg_pool, &gauge_factory_key, epoch).await?;
+        let prices = self.load_price_book(epoch, epoch).await?;
+        let fee_mints = self.fee_mints();
+
+        let mut leaderboard = vec![];
+        for epoch_gauge in epoch_gauges.iter() {
+            let mints = fee_mints
+                .get(&epoch_gauge.gauge)
+                .map(|(a, b)| (a.as_str(), b.as_str()));
+            leaderboard.push(compute_gauge_efficiency(
+                epoch_gauge,
+                mints,
+                &bribes,
+                &prices,
+            )?);
+        }
+        leaderboard.sort_by(|a, b| {
+            leaderboard_key(a)
+                .partial_cmp(&leaderboard_key(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(leaderboard)
+    }
+
+    pub async fn get_epoch_vote_analytics(&self, epoch: u64) -> Result<EpochVoteAnalytics> {
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let epoch_gauges =
+            get_epoch_gauges(&self.pg_pool, &gauge_factory_key, i64::try_from(epoch)?).await?;
+        let previous_epoch_gauges = if epoch > 0 {
+            get_epoch_gauges(&self.pg_pool, &gauge_factory_key, i64::try_from(epoch - 1)?).await?
+        } else {
+            vec![]
+        };
+        let powers = voting_powers(&epoch_gauges)?;
+        let (rewarder, gauges, quarries) = self.emission_inputs();
+        let emissions = split_emissions(&rewarder, &gauges, &quarries, &powers)?;
+        compute_vote_analytics(
+            epoch,
+            &powers,
+            &voting_powers(&previous_epoch_gauges)?,
+            rewarder.annual_rewards_rate,
+            &emissions,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_analytics {
+    use super::*;
+
+    fn powers(values: &[(&str, u64)]) -> HashMap<String, u64> {
+        values.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_compute_emission_forecast() {
+        let gauge = |pubkey: &str, quarry: Pubkey, is_disabled| GaugeState {
+            pubkey: pubkey.to_string(),
+            quarry: quarry.to_string(),
+            is_disabled,
+            ..Default::default()
+        };
+        let quarry_state = |rewards_share, annual_rewards_rate| quarry::Quarry {
+            rewards_share,
+            annual_rewards_rate,
+            ..Default::default()
+        };
+        let (quarry_a, quarry_b, quarry_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let gauges = vec![
+            gauge("a", quarry_a, false),
+            gauge("b", quarry_b, false),
+            gauge("c", quarry_c, true),
+        ];
+        let mut quarries = HashMap::new();
+        quarries.insert(quarry_a, quarry_state(100, 36_500));
+        quarries.insert(quarry_b, quarry_state(0, 0));
+        quarries.insert(quarry_c, quarry_state(100, 36_500));
+        // the last 100 shares are a quarry outside of the gauges
+        let rewarder = RewarderState {
+            pubkey: String::new(),
+            annual_rewards_rate: 109_500,
+            total_rewards_shares: 300,
+        };
+
+        let forecast = compute_emission_forecast(
+            3,
+            1000,
+            &rewarder,
+            &gauges,
+            &quarries,
+            &powers(&[("a", 50), ("b", 150), ("c", 10)]),
+        )
+        .unwrap();
+        // the disabled gauge loses its share
+        assert_eq!(forecast.projected_total_rewards_shares, 300);
+        let quarry_pks: Vec<String> = forecast
+            .quarries
+            .iter()
+            .map(|x| x.quarry_pk.clone())
+            .collect();
+        assert_eq!(
+            quarry_pks,
+            vec![
+                quarry_b.to_string(),
+                quarry_a.to_string(),
+                quarry_c.to_string()
+            ]
+        );
+        let b = &forecast.quarries[0];
+        assert_eq!(b.projected_rewards_share, 150);
+        assert_eq!(b.projected_share, 0.5);
+        assert_eq!(b.projected_annual_rewards, 54_750);
+        assert_eq!(b.projected_daily_rewards, 150);
+        let c = &forecast.quarries[2];
+        assert_eq!(c.daily_rewards, 100);
+        assert_eq!(c.projected_rewards_share, 0);
+        assert_eq!(c.projected_daily_rewards, 0);
+    }
+
+    #[test]
+    fn test_compute_gauge_efficiency() {
+        let bribe = |gauge: &str, token_mint: &str, reward_each_epoch: &str, start, end| Bribe {
+            address: String::new(),
+            gauge: gauge.to_string(),
+            token_mint: token_mint.to_string(),
+            reward_each_epoch: reward_each_epoch.to_string(),
+            briber: String::new(),
+            token_account_vault: String::new(),
+            bribe_rewards_epoch_start: start,
+            bribe_rewards_epoch_end: end,
+            bribe_index: 0,
+            gauge_factory: String::new(),
+        };
+        let bribes = vec![
+            bribe("g", "usdc", "5000000", 1, 3),
+            bribe("g", "sol", "2000000000", 2, 2),
+            // another epoch, another gauge and an unknown mint
+            bribe("g", "usdc", "7000000", 4, 4),
+            bribe("h", "usdc", "7000000", 2, 2),
+            bribe("g", "bonk", "1", 2, 2),
+        ];
+        let mut prices = PriceBook::default();
+        prices.decimals.insert(String::from("usdc"), 6);
+        prices.decimals.insert(String::from("sol"), 9);
+        prices.latest.insert(String::from("usdc"), 1.0);
+        prices.latest.insert(String::from("sol"), 30.0);
+        prices.snapshots.insert((2, String::from("sol")), 20.0);
+
+        let epoch_gauge = EpochGauge {
+            address: String::new(),
+            gauge: String::from("g"),
+            total_power: String::from("100"),
+            token_a_fee: String::from("1000000"),
+            token_b_fee: String::from("500000000"),
+            voting_epoch: 2,
+            gauge_factory: String::new(),
+        };
+        let efficiency =
+            compute_gauge_efficiency(&epoch_gauge, Some(("usdc", "sol")), &bribes, &prices)
+                .unwrap();
+        // 5 usdc and 2 sol at the price of the epoch
+        assert_eq!(efficiency.bribe_value_usd, 45.0);
+        assert_eq!(efficiency.bribe_usd_per_vote, Some(0.45));
+        assert_eq!(efficiency.fee_value_usd, 11.0);
+        // usdc has no snapshot of the epoch
+        assert_eq!(efficiency.bribe_price_source, Some(PriceSource::Latest));
+        assert_eq!(efficiency.fee_price_source, Some(PriceSource::Latest));
+        assert_eq!(efficiency.unpriced_mints, vec![String::from("bonk")]);
+
+        let sol_only = EpochGauge {
+            token_a_fee: String::from("0"),
+            ..epoch_gauge
+        };
+        let efficiency =
+            compute_gauge_efficiency(&sol_only, Some(("bonk", "sol")), &bribes[1..2], &prices)
+                .unwrap();
+        assert_eq!(efficiency.bribe_price_source, Some(PriceSource::Snapshot));
+        assert_eq!(efficiency.fee_price_source, Some(PriceSource::Snapshot));
+
+        let efficiency = compute_gauge_efficiency(&sol_only, None, &[], &prices).unwrap();
+        assert_eq!(efficiency.bribe_price_source, None);
+        assert_eq!(efficiency.fee_price_source, None);
+    }
+
+    #[test]
+    fn test_compute_vote_analytics() {
+        // d is gone from the factory, its quarry is not synced anymore
+        let gauges: Vec<GaugeState> = ["a", "b", "c"]
+            .iter()
+            .map(|x| GaugeState {
+                pubkey: x.to_string(),
+                quarry: Pubkey::new_unique().to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let rewarder = RewarderState {
+            pubkey: String::new(),
+            annual_rewards_rate: 365_000,
+            total_rewards_shares: 0,
+        };
+        let emissions = split_emissions(
+            &rewarder,
+            &gauges,
+            &HashMap::new(),
+            &powers(&[("a", 300), ("b", 100), ("c", 600)]),
+        )
+        .unwrap();
+        let analytics = compute_vote_analytics(
+            5,
+            &powers(&[("a", 300), ("b", 100), ("c", 600)]),
+            &powers(&[("a", 200), ("d", 200)]),
+            365_000,
+            &emissions,
+        )
+        .unwrap();
+        assert_eq!(analytics.total_power, 1000);
+        assert_eq!(analytics.previous_total_power, 400);
+
+        let ranked: Vec<(&str, u32)> = analytics
+            .gauges
+            .iter()
+            .map(|x| (x.gauge_pk.as_str(), x.rank))
+            .collect();
+        assert_eq!(ranked, vec![("c", 1), ("a", 2), ("b", 3), ("d", 4)]);
+
+        let a = &analytics.gauges[1];
+        assert_eq!(a.share, 0.3);
+        assert_eq!(a.previous_share, 0.5);
+        assert_eq!(a.voting_power_delta, 100);
+        assert_eq!(a.voting_power_delta_percent, Some(50.0));
+        assert_eq!(a.annual_rewards, 109_500);
+        assert_eq!(a.daily_rewards, 300);
+
+        // gone gauges lose all of their votes, new ones have no percent change
+        let d = &analytics.gauges[3];
+        assert_eq!(d.voting_power_delta, -200);
+        assert_eq!(d.voting_power_delta_percent, Some(-100.0));
+        assert_eq!(d.annual_rewards, 0);
+        assert_eq!(analytics.gauges[2].voting_power_delta_percent, None);
+    }
+}
@@ -0,0 +1,757 @@
+//! Pool of RPC providers with health scoring and failover
+
+use crate::account_source::AccountSource;
+use crate::metrics::Metrics;
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use anchor_client::solana_client::client_error::{ClientError, ClientErrorKind};
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use anchor_client::solana_client::rpc_filter::RpcFilterType;
+use anchor_client::solana_client::rpc_request::RpcError;
+use anchor_client::solana_client::rpc_response::RpcSimulateTransactionResult;
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_client::solana_sdk::hash::Hash;
+use anchor_client::solana_sdk::signature::Signature;
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// An endpoint with this many errors in a row is skipped until it recovers
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+/// An endpoint this many slots behind the best one is skipped, about a minute
+const MAX_SLOT_LAG: u64 = 150;
+/// Weight of the latest request in the health score
+const SCORE_DECAY: f64 = 0.9;
+/// First backoff of a provider answering 429, doubled on every 429 in a row
+const MIN_THROTTLE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_THROTTLE_BACKOFF: Duration = Duration::from_secs(60);
+/// Keys per getMultipleAccounts request, the RPC limit
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+/// getMultipleAccounts requests in flight at once for a single fetch
+const MAX_CONCURRENT_CHUNKS: usize = 4;
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointHealth {
+    /// Host of the provider, the full url may hold an api key
+    pub provider: String,
+    /// Moving average of the request success rate, from 0 to 1
+    pub score: f64,
+    pub consecutive_errors: u32,
+    pub slot: u64,
+    pub slot_lag: u64,
+    pub is_healthy: bool,
+    /// 429 answered in a row, the provider is backed off meanwhile
+    pub consecutive_throttles: u32,
+}
+
+struct RpcEndpoint {
+    client: RpcClient,
+    health: Mutex<EndpointHealth>,
+    /// Set when the provider answers 429, the endpoint is skipped until then
+    throttled_until: Mutex<Option<Instant>>,
+}
+
+impl RpcEndpoint {
+    fn is_healthy(&self) -> bool {
+        if self.throttle_remaining().is_some() {
+            return false;
+        }
+        let health = self.health.lock().unwrap();
+        health.consecutive_errors < MAX_CONSECUTIVE_ERRORS && health.slot_lag <= MAX_SLOT_LAG
+    }
+
+    fn throttle_remaining(&self) -> Option<Duration> {
+        let throttled_until = (*self.throttled_until.lock().unwrap())?;
+        let remaining = throttled_until.saturating_duration_since(Instant::now());
+        if remaining > Duration::from_secs(0) {
+            Some(remaining)
+        } else {
+            None
+        }
+    }
+
+    fn score(&self) -> f64 {
+        self.health.lock().unwrap().score
+    }
+
+    fn provider(&self) -> String {
+        self.health.lock().unwrap().provider.clone()
+    }
+
+    fn record_success(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.score = health.score * SCORE_DECAY + (1.0 - SCORE_DECAY);
+        health.consecutive_errors = 0;
+        health.consecutive_throttles = 0;
+    }
+
+    fn record_error(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.score *= SCORE_DECAY;
+        health.consecutive_errors += 1;
+    }
+
+    /// Back off exponentially from a provider answering 429
+    fn record_throttle(&self) -> Duration {
+        let consecutive_throttles = {
+            let mut health = self.health.lock().unwrap();
+            health.consecutive_throttles += 1;
+            health.consecutive_throttles
+        };
+        let backoff = MIN_THROTTLE_BACKOFF
+            .checked_mul(1 << consecutive_throttles.saturating_sub(1).min(16))
+            .unwrap_or(MAX_THROTTLE_BACKOFF)
+            .min(MAX_THROTTLE_BACKOFF);
+        *self.throttled_until.lock().unwrap() = Some(Instant::now() + backoff);
+        backoff
+    }
+}
+
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    /// Websocket provider of the cluster, for subscriptions
+    wss_provider: String,
+    commitment: CommitmentConfig,
+    /// Cursor of the round robin over healthy endpoints
+    next: AtomicUsize,
+    limiter: RateLimiter,
+    metrics: Arc<Metrics>,
+}
+
+impl RpcPool {
+    pub fn new(
+        providers: &[String],
+        wss_provider: String,
+        commitment: CommitmentConfig,
+        rate_limit: RateLimitConfig,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let clients = providers
+            .iter()
+            .map(|url| {
+                (
+                    provider_host(url),
+                    RpcClient::new_with_commitment(url.clone(), commitment),
+                )
+            })
+            .collect();
+        Self::with_clients(clients, wss_provider, commitment, rate_limit, metrics)
+    }
+
+    /// Pool over the given clients, keyed by the host of their provider
+    fn with_clients(
+        clients: Vec<(String, RpcClient)>,
+        wss_provider: String,
+        commitment: CommitmentConfig,
+        rate_limit: RateLimitConfig,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        assert!(!clients.is_empty(), "Requires at least one RPC provider");
+        let endpoints = clients
+            .into_iter()
+            .map(|(provider, client)| RpcEndpoint {
+                client,
+                health: Mutex::new(EndpointHealth {
+                    provider,
+                    score: 1.0,
+                    is_healthy: true,
+                    ..EndpointHealth::default()
+                }),
+                throttled_until: Mutex::new(None),
+            })
+            .collect();
+        RpcPool {
+            endpoints,
+            wss_provider,
+            commitment,
+            next: AtomicUsize::new(0),
+            limiter: RateLimiter::new(rate_limit, metrics.clone()),
+            metrics,
+        }
+    }
+
+    pub fn wss_provider(&self) -> &str {
+        &self.wss_provider
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.commitment
+    }
+
+    /// Endpoints in the order they should be tried. Healthy endpoints come first, either rotated
+    /// for read heavy calls or by best score. Unhealthy ones are kept as a last resort.
+    fn ordered_endpoints(&self, round_robin: bool) -> Vec<usize> {
+        let (mut healthy, mut unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| self.endpoints[i].is_healthy());
+
+        if round_robin && !healthy.is_empty() {
+            let offset = self.next.fetch_add(1, Ordering::Relaxed) % healthy.len();
+            healthy.rotate_left(offset);
+        } else {
+            healthy.sort_by(|&a, &b| {
+                self.endpoints[b]
+                    .score()
+                    .partial_cmp(&self.endpoints[a].score())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+        unhealthy.sort_by(|&a, &b| {
+            self.endpoints[b]
+                .score()
+                .partial_cmp(&self.endpoints[a].score())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Run the call on the endpoints in order, moving to the next one when the provider fails.
+    /// Errors which are not the provider fault, like a rejected transaction, are returned as is.
+    /// Every attempt goes through the rate limiter, and a throttled endpoint is only used
+    /// once its backoff is over.
+    async fn call<'a, T, F, Fut>(&'a self, method: &str, round_robin: bool, f: F) -> Result<T>
+    where
+        F: Fn(&'a RpcClient) -> Fut,
+        Fut: Future<Output = std::result::Result<T, ClientError>> + 'a,
+    {
+        let mut last_err = None;
+        for i in self.ordered_endpoints(round_robin) {
+            let endpoint = &self.endpoints[i];
+            if let Some(remaining) = endpoint.throttle_remaining() {
+                sleep(remaining).await;
+            }
+            self.limiter.acquire(method).await;
+            let provider = endpoint.provider();
+            let labels = [("provider", provider.as_str()), ("method", method)];
+            self.metrics.inc(
+                "keeper_rpc_requests_total",
+                "RPC requests sent, by provider and method",
+                &labels,
+                1.0,
+            );
+            match f(&endpoint.client).await {
+                Ok(value) => {
+                    endpoint.record_success();
+                    return Ok(value);
+                }
+                Err(err) => {
+                    self.metrics.inc(
+                        "keeper_rpc_errors_total",
+                        "RPC requests failed, by provider and method",
+                        &labels,
+                        1.0,
+                    );
+                    if !is_provider_error(&err) {
+                        endpoint.record_success();
+                        return Err(err.into());
+                    }
+                    println!("rpc {} failed on {}: {}", method, provider, err);
+                    if is_throttled(&err) {
+                        let backoff = endpoint.record_throttle();
+                        println!("rpc {} backing off for {:?}", provider, backoff);
+                        self.metrics.inc(
+                            "keeper_rpc_throttled_total",
+                            "RPC requests answered with 429, by provider and method",
+                            &labels,
+                            1.0,
+                        );
+                    } else {
+                        endpoint.record_error();
+                    }
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err
+            .map(anyhow::Error::from)
+            .unwrap_or_else(|| anyhow::Error::msg("no RPC provider available")))
+    }
+
+    pub async fn get_slot(&self) -> Result<u64> {
+        self.call("getSlot", false, |client| client.get_slot())
+            .await
+    }
+
+    /// Poll the slot of every endpoint, endpoints lagging behind the best one are skipped
+    pub async fn process_monitor_health(&self) {
+        let mut slots = vec![];
+        for endpoint in self.endpoints.iter() {
+            // leave throttled providers alone, their last slot is kept
+            if endpoint.throttle_remaining().is_some() {
+                slots.push(None);
+                continue;
+            }
+            self.limiter.acquire("getSlot").await;
+            match endpoint.client.get_slot().await {
+                Ok(slot) => {
+                    endpoint.record_success();
+                    slots.push(Some(slot));
+                }
+                Err(err) => {
+                    println!(
+                        "rpc health check failed on {}: {}",
+                        endpoint.provider(),
+                        err
+                    );
+                    endpoint.record_error();
+                    slots.push(None);
+                }
+            }
+        }
+
+        let max_slot = slots.iter().filter_map(|x| *x).max().unwrap_or(0);
+        for (endpoint, slot) in self.endpoints.iter().zip(slots.into_iter()) {
+            {
+                let mut health = endpoint.health.lock().unwrap();
+                if let Some(slot) = slot {
+                    health.slot = slot;
+                }
+                health.slot_lag = max_slot.saturating_sub(health.slot);
+            }
+            let is_healthy = endpoint.is_healthy();
+            let health = {
+                let mut health = endpoint.health.lock().unwrap();
+                health.is_healthy = is_healthy;
+                health.clone()
+            };
+            let labels = [("provider", health.provider.as_str())];
+            self.metrics.set(
+                "keeper_rpc_endpoint_score",
+                "Moving average of the RPC provider success rate",
+                &labels,
+                health.score,
+            );
+            self.metrics.set(
+                "keeper_rpc_endpoint_slot_lag",
+                "Slots the RPC provider is behind the best one",
+                &labels,
+                health.slot_lag as f64,
+            );
+            self.metrics.set(
+                "keeper_rpc_endpoint_healthy",
+                "Whether the RPC provider is used first",
+                &labels,
+                if health.is_healthy { 1.0 } else { 0.0 },
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl AccountSource for RpcPool {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        let response = self
+            .call("getAccountInfo", false, |client| {
+                client.get_account_with_commitment(pubkey, self.commitment)
+            })
+            .await?;
+        Ok(response.value)
+    }
+
+    /// Fetch any number of accounts, split in chunks under the RPC limit and fetched concurrently.
+    /// The result is aligned with the keys, None for an account which does not exist.
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let chunks: Vec<Vec<Option<Account>>> = stream::iter(pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS))
+            .map(|chunk| {
+                self.call("getMultipleAccounts", true, move |client| {
+                    client.get_multiple_accounts(chunk)
+                })
+            })
+            .buffered(MAX_CONCURRENT_CHUNKS)
+            .try_collect()
+            .await?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                commitment: Some(self.commitment),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        self.call("getProgramAccounts", true, |client| {
+            client.get_program_accounts_with_config(program_id, config.clone())
+        })
+        .await
+    }
+
+    /// Fetch only the keys of the program accounts matching the filters, without their data
+    async fn get_program_account_keys(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<Pubkey>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                commitment: Some(self.commitment),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let accounts = self
+            .call("getProgramAccounts", true, |client| {
+                client.get_program_accounts_with_config(program_id, config.clone())
+            })
+            .await?;
+        Ok(accounts.into_iter().map(|x| x.0).collect())
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.call("getBalance", false, |client| client.get_balance(pubkey))
+            .await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.call("getMinimumBalanceForRentExemption", false, |client| {
+            client.get_minimum_balance_for_rent_exemption(data_len)
+        })
+        .await
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.call("getLatestBlockhash", false, |client| {
+            client.get_latest_blockhash()
+        })
+        .await
+    }
+
+    /// The blockhash should come from the same call chain, a transaction resent to
+    /// another endpoint keeps its signature so it can not land twice
+    async fn send_and_confirm_transaction(&self, tx: &Transaction) -> Result<Signature> {
+        self.call("sendTransaction", false, |client| {
+            client.send_and_confirm_transaction(tx)
+        })
+        .await
+    }
+
+    async fn simulate_transaction(&self, tx: &Transaction) -> Result<RpcSimulateTransactionResult> {
+        let response = self
+            .call("simulateTransaction", false, |client| {
+                client.simulate_transaction(tx)
+            })
+            .await?;
+        Ok(response.value)
+    }
+
+    fn get_health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|x| x.health.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+/// Whether the error comes from the provider (transport, throttling, unhealthy node),
+/// rather than from the request itself
+fn is_provider_error(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+        // node unhealthy, node behind, block not available
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            matches!(*code, -32005 | -32004 | -32007 | -32014 | -32016 | 429)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the provider answered 429, either as http status or as json rpc error code
+fn is_throttled(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Reqwest(err) => {
+            err.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+        }
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => *code == 429,
+        _ => false,
+    }
+}
+
+pub(crate) fn provider_host(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed.host_str().unwrap_or(url).to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test_client_pool {
+    use super::*;
+    use crate::metrics::init_metrics;
+    use anchor_client::solana_client::client_error::Result as ClientResult;
+    use anchor_client::solana_client::rpc_client::RpcClientConfig;
+    use anchor_client::solana_client::rpc_request::{RpcRequest, RpcResponseErrorData};
+    use anchor_client::solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+    use serde_json::{json, Value};
+    use solana_account_decoder::UiAccount;
+    use std::collections::HashMap;
+
+    #[derive(Clone, Copy)]
+    enum StubAnswer {
+        Slot(u64),
+        Fail,
+        Throttle,
+    }
+
+    /// Provider answering every request with the same slot or error, and serving its accounts
+    #[derive(Clone)]
+    struct StubProvider {
+        url: String,
+        answer: Arc<Mutex<StubAnswer>>,
+        accounts: Arc<HashMap<Pubkey, Account>>,
+        /// Requests received, getVersion aside
+        calls: Arc<AtomicUsize>,
+        /// Keys of every getMultipleAccounts request, in order of arrival
+        chunks: Arc<Mutex<Vec<Vec<Pubkey>>>>,
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl StubProvider {
+        fn new(url: &str, answer: StubAnswer) -> Self {
+            StubProvider {
+                url: url.to_string(),
+                answer: Arc::new(Mutex::new(answer)),
+                accounts: Arc::new(HashMap::new()),
+                calls: Arc::new(AtomicUsize::new(0)),
+                chunks: Arc::new(Mutex::new(vec![])),
+                in_flight: Arc::new(AtomicUsize::new(0)),
+                max_in_flight: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn set_answer(&self, answer: StubAnswer) {
+            *self.answer.lock().unwrap() = answer;
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        fn client(&self) -> (String, RpcClient) {
+            let config = RpcClientConfig::with_commitment(CommitmentConfig::confirmed());
+            (
+                self.url.clone(),
+                RpcClient::new_sender(self.clone(), config),
+            )
+        }
+
+        /// The first chunks answer last, so the pool has to restore the order of the keys
+        async fn get_multiple_accounts(&self, params: Value) -> ClientResult<Value> {
+            let pubkeys: Vec<Pubkey> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_str().unwrap().parse().unwrap())
+                .collect();
+            let chunk_index = {
+                let mut chunks = self.chunks.lock().unwrap();
+                chunks.push(pubkeys.clone());
+                chunks.len() as u64
+            };
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            sleep(Duration::from_millis(50 / chunk_index)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let value: Vec<Option<UiAccount>> = pubkeys
+                .iter()
+                .map(|pubkey| {
+                    self.accounts.get(pubkey).map(|account| {
+                        UiAccount::encode(pubkey, account, UiAccountEncoding::Base64, None, None)
+                    })
+                })
+                .collect();
+            Ok(json!({"context": {"slot": 1}, "value": value}))
+        }
+    }
+
+    #[async_trait]
+    impl RpcSender for StubProvider {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            if request == RpcRequest::GetVersion {
+                return Ok(json!({"solana-core": "1.16.0", "feature-set": 0}));
+            }
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let answer = *self.answer.lock().unwrap();
+            let slot = match answer {
+                StubAnswer::Slot(slot) => slot,
+                StubAnswer::Fail => {
+                    return Err(RpcError::RpcRequestError(String::from("connection reset")).into())
+                }
+                StubAnswer::Throttle => {
+                    return Err(RpcError::RpcResponseError {
+                        code: 429,
+                        message: String::from("Too many requests"),
+                        data: RpcResponseErrorData::Empty,
+                    }
+                    .into())
+                }
+            };
+            match request {
+                RpcRequest::GetSlot => Ok(json!(slot)),
+                RpcRequest::GetMultipleAccounts => self.get_multiple_accounts(params).await,
+                _ => Err(RpcError::ForUser(format!("{} not served", request)).into()),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            self.url.clone()
+        }
+    }
+
+    fn new_pool(providers: &[&StubProvider]) -> RpcPool {
+        let rate_limit = RateLimitConfig {
+            credits_per_second: 1_000_000.0,
+            ..RateLimitConfig::default()
+        };
+        RpcPool::with_clients(
+            providers.iter().map(|x| x.client()).collect(),
+            String::from("wss://stub"),
+            CommitmentConfig::confirmed(),
+            rate_limit,
+            init_metrics(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_next_provider() {
+        let first = StubProvider::new("first", StubAnswer::Fail);
+        let second = StubProvider::new("second", StubAnswer::Slot(100));
+        let pool = new_pool(&[&first, &second]);
+
+        assert_eq!(pool.get_slot().await.unwrap(), 100);
+        assert_eq!(first.calls(), 1);
+        assert_eq!(second.calls(), 1);
+        let health = pool.get_health();
+        assert_eq!(health[0].consecutive_errors, 1);
+        assert!(health[0].score < health[1].score);
+
+        // every provider failing returns the last error
+        second.set_answer(StubAnswer::Fail);
+        assert!(pool.get_slot().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_throttled_provider_skipped_until_backoff_expires() {
+        let first = StubProvider::new("first", StubAnswer::Throttle);
+        let second = StubProvider::new("second", StubAnswer::Slot(100));
+        let pool = new_pool(&[&first, &second]);
+
+        assert_eq!(pool.get_slot().await.unwrap(), 100);
+        assert_eq!(pool.get_health()[0].consecutive_throttles, 1);
+        assert!(pool.endpoints[0].throttle_remaining().unwrap() <= MIN_THROTTLE_BACKOFF);
+
+        // backed off: the next calls go to the second provider only
+        first.set_answer(StubAnswer::Slot(101));
+        for _ in 0..3 {
+            assert_eq!(pool.get_slot().await.unwrap(), 100);
+        }
+        assert_eq!(first.calls(), 1);
+        assert_eq!(second.calls(), 4);
+
+        // once the backoff is over the first provider is used again
+        *pool.endpoints[0].throttled_until.lock().unwrap() = Some(Instant::now());
+        assert_eq!(pool.get_slot().await.unwrap(), 101);
+        assert_eq!(first.calls(), 2);
+        assert_eq!(pool.get_health()[0].consecutive_throttles, 0);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_provider_demoted() {
+        let first = StubProvider::new("first", StubAnswer::Slot(1000 - MAX_SLOT_LAG - 1));
+        let second = StubProvider::new("second", StubAnswer::Slot(1000));
+        let pool = new_pool(&[&first, &second]);
+
+        pool.process_monitor_health().await;
+        let health = pool.get_health();
+        assert_eq!(health[0].slot_lag, MAX_SLOT_LAG + 1);
+        assert!(!health[0].is_healthy);
+        assert!(health[1].is_healthy);
+        assert_eq!(pool.ordered_endpoints(false), vec![1, 0]);
+        assert_eq!(pool.ordered_endpoints(true), vec![1, 0]);
+        assert_eq!(
+            pool.metrics
+                .get("keeper_rpc_endpoint_healthy", &[("provider", "first")]),
+            Some(0.0)
+        );
+        assert_eq!(pool.get_slot().await.unwrap(), 1000);
+
+        // caught up: used again
+        first.set_answer(StubAnswer::Slot(1000));
+        pool.process_monitor_health().await;
+        assert!(pool.get_health()[0].is_healthy);
+        assert_eq!(pool.ordered_endpoints(false).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_multiple_accounts_chunked_in_order() {
+        let pubkeys: Vec<Pubkey> = (0..250).map(|_| Pubkey::new_unique()).collect();
+        // the lamports tell the position of the key, every tenth account does not exist
+        let accounts = pubkeys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 10 != 9)
+            .map(|(i, pubkey)| {
+                let account = Account {
+                    lamports: i as u64,
+                    ..Account::default()
+                };
+                (*pubkey, account)
+            })
+            .collect();
+        let provider = StubProvider {
+            accounts: Arc::new(accounts),
+            ..StubProvider::new("stub", StubAnswer::Slot(100))
+        };
+        let pool = new_pool(&[&provider]);
+
+        let result = pool.get_multiple_accounts(&pubkeys).await.unwrap();
+        assert_eq!(result.len(), pubkeys.len());
+        for (i, account) in result.iter().enumerate() {
+            match account {
+                Some(account) => assert_eq!(account.lamports, i as u64),
+                None => assert_eq!(i % 10, 9),
+            }
+        }
+
+        let chunks = provider.chunks.lock().unwrap().clone();
+        let mut chunk_sizes: Vec<usize> = chunks.iter().map(|x| x.len()).collect();
+        chunk_sizes.sort_unstable();
+        assert_eq!(
+            chunk_sizes,
+            vec![50, MAX_MULTIPLE_ACCOUNTS, MAX_MULTIPLE_ACCOUNTS]
+        );
+        // the chunks are requested concurrently, not one after the other
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+}
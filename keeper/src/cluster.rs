@@ -0,0 +1,94 @@
+//! Cluster profiles, bundling the defaults of each solana cluster
+
+use crate::account_source::AccountSource;
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ClusterProfile {
+    Mainnet,
+    Devnet,
+    Localnet,
+}
+
+impl ClusterProfile {
+    /// Profile used without --cluster, selected by the devnet feature
+    pub fn default_profile() -> Self {
+        if cfg!(feature = "devnet") {
+            ClusterProfile::Devnet
+        } else {
+            ClusterProfile::Mainnet
+        }
+    }
+
+    pub fn provider(&self) -> &'static str {
+        match self {
+            ClusterProfile::Mainnet => "https://api.mainnet-beta.solana.com",
+            ClusterProfile::Devnet => "https://api.devnet.solana.com",
+            ClusterProfile::Localnet => "http://127.0.0.1:8899",
+        }
+    }
+
+    pub fn wss_provider(&self) -> &'static str {
+        match self {
+            ClusterProfile::Mainnet => "wss://api.mainnet-beta.solana.com",
+            ClusterProfile::Devnet => "wss://api.devnet.solana.com",
+            ClusterProfile::Localnet => "ws://127.0.0.1:8900",
+        }
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        match self {
+            ClusterProfile::Mainnet | ClusterProfile::Devnet => CommitmentConfig::confirmed(),
+            ClusterProfile::Localnet => CommitmentConfig::processed(),
+        }
+    }
+
+    /// Bases of the gauge factories known to be deployed on the cluster
+    pub fn bases(&self) -> Vec<String> {
+        let bases: &[&str] = match self {
+            ClusterProfile::Mainnet => &[],
+            ClusterProfile::Devnet => &["ba1AznDonanrFY2Ek6jaiMmkccMeU43A5TXU2jB8f4N"],
+            ClusterProfile::Localnet => &[],
+        };
+        bases.iter().map(|x| x.to_string()).collect()
+    }
+}
+
+/// Programs the keeper talks to, they must be deployed on the cluster
+pub fn required_programs() -> Vec<(&'static str, Pubkey)> {
+    vec![
+        ("gauge", gauge::id()),
+        ("quarry", quarry::id()),
+        ("moc-amm", moc_amm::id()),
+    ]
+}
+
+/// Check the required programs are deployed and executable, so a keeper pointed at the
+/// wrong cluster fails at startup instead of crawling nothing
+pub async fn check_programs_deployed(chain: &dyn AccountSource) -> Result<()> {
+    let programs = required_programs();
+    let program_ids: Vec<Pubkey> = programs.iter().map(|x| x.1).collect();
+    let accounts = chain.get_multiple_accounts(&program_ids).await?;
+
+    for ((name, program_id), account) in programs.iter().zip(accounts.iter()) {
+        match account {
+            Some(account) if account.executable => {}
+            Some(_) => {
+                return Err(anyhow::Error::msg(format!(
+                    "{} program {} is not executable",
+                    name, program_id
+                )))
+            }
+            None => {
+                return Err(anyhow::Error::msg(format!(
+                    "{} program {} is not deployed",
+                    name, program_id
+                )))
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! One-shot subcommands, run against the same database and RPC providers as `serve`
+
+use crate::admin::{RangeRequest, MAX_RECRAWL_RANGE};
+use crate::core::{find_bribe_addr, Core};
+use crate::database::*;
+use crate::state::GaugeState;
+use crate::verifier::{compare_bribe, compare_epoch_gauge, FieldComparison};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::str::FromStr;
+
+/// Latest stored epoch gauges printed by `inspect gauge`
+const INSPECT_EPOCH_GAUGES: i64 = 10;
+
+/// Remote address recorded in the audit log for actions run from the command line
+const CLI_REMOTE_ADDR: &str = "cli";
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InspectKind {
+    /// A gauge address, printed with its latest epoch gauges
+    Gauge,
+    /// An epoch gauge address
+    Epoch,
+    /// A bribe index of the default gauge factory
+    Bribe,
+}
+
+/// Re-crawl the epoch gauges of every gauge for the voting epochs, in chunks the admin route accepts
+pub async fn backfill(core: &Core, from: i64, to: i64) -> Result<()> {
+    if from < 0 || from > to {
+        return Err(anyhow::Error::msg("invalid range"));
+    }
+    let mut chunk_from = from;
+    while chunk_from <= to {
+        let request = RangeRequest {
+            from: chunk_from,
+            to: (chunk_from + MAX_RECRAWL_RANGE - 1).min(to),
+        };
+        let result = core.admin_recrawl_epochs(&request).await;
+        core.record_admin_action(
+            "backfill",
+            &serde_json::to_string(&request)?,
+            CLI_REMOTE_ADDR,
+            &result,
+        )
+        .await?;
+        println!(
+            "gauge factory {} epochs {} to {}: {}",
+            core.get_gauge_factory_key(),
+            request.from,
+            request.to,
+            result?
+        );
+        chunk_from = request.to + 1;
+    }
+    Ok(())
+}
+
+/// Run a single crank round, taking the leader lock first unless it is a dry run
+pub async fn crank_once(core: &Core, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        core.process_leader_election().await?;
+        if !core.leader.is_leader() {
+            return Err(anyhow::Error::msg(format!(
+                "another replica is the leader of gauge factory {}",
+                core.get_gauge_factory_key()
+            )));
+        }
+    }
+    core.crank(dry_run).await
+}
+
+pub async fn inspect(core: &Core, kind: InspectKind, id: &str) -> Result<()> {
+    match kind {
+        InspectKind::Gauge => inspect_gauge(core, &Pubkey::from_str(id)?).await,
+        InspectKind::Epoch => inspect_epoch_gauge(core, id).await,
+        InspectKind::Bribe => inspect_bribe(core, u32::from_str(id)?).await,
+    }
+}
+
+async fn inspect_gauge(core: &Core, gauge: &Pubkey) -> Result<()> {
+    // gauges are not stored, only cached by serve, so only the chain side is printed
+    let account = fetch_account::<gauge::Gauge>(core, gauge).await?;
+    match account {
+        Some(account) => println!(
+            "gauge {}\n{}",
+            gauge,
+            serde_json::to_string_pretty(&GaugeState::new(gauge, &account))?
+        ),
+        None => println!("gauge {} is missing on chain", gauge),
+    }
+
+    let epoch_gauges = get_epoch_gauges_of_gauge(
+        &core.pg_pool,
+        &core.get_gauge_factory_key(),
+        &gauge.to_string(),
+        INSPECT_EPOCH_GAUGES,
+    )
+    .await?;
+    if epoch_gauges.is_empty() {
+        println!("no epoch gauge stored");
+    }
+    for row in epoch_gauges.iter() {
+        print_epoch_gauge(core, &row.address, Some(row)).await?;
+    }
+    Ok(())
+}
+
+async fn inspect_epoch_gauge(core: &Core, address: &str) -> Result<()> {
+    let row = get_epoch_gauge(&core.pg_pool, address).await?;
+    print_epoch_gauge(core, address, row.as_ref()).await
+}
+
+async fn print_epoch_gauge(core: &Core, address: &str, row: Option<&EpochGauge>) -> Result<()> {
+    let chain = fetch_account::<gauge::EpochGauge>(core, &Pubkey::from_str(address)?).await?;
+    println!("epoch gauge {}", address);
+    match (row, chain) {
+        (Some(row), Some(chain)) => print_comparison(&compare_epoch_gauge(row, &chain)),
+        (Some(_), None) => println!("stored, but missing on chain"),
+        (None, Some(chain)) => println!(
+            "missing in the database, on chain: gauge {} voting_epoch {} total_power {} token_a_fee {} token_b_fee {}",
+            chain.gauge, chain.voting_epoch, chain.total_power, chain.token_a_fee, chain.token_b_fee
+        ),
+        (None, None) => println!("missing in the database and on chain"),
+    }
+    Ok(())
+}
+
+async fn inspect_bribe(core: &Core, bribe_index: u32) -> Result<()> {
+    let address = find_bribe_addr(&core.get_gauge_factory_addr(), bribe_index);
+    let row = get_bribe_by_index(
+        &core.pg_pool,
+        &core.get_gauge_factory_key(),
+        bribe_index.into(),
+    )
+    .await?;
+    let chain = fetch_account::<gauge::Bribe>(core, &address).await?;
+    println!("bribe {} at {}", bribe_index, address);
+    match (row, chain) {
+        (Some(row), Some(chain)) => print_comparison(&compare_bribe(&row, &chain)),
+        (Some(_), None) => println!("stored, but missing on chain"),
+        (None, Some(chain)) => println!(
+            "missing in the database, on chain: gauge {} token_mint {} reward_each_epoch {} briber {} epochs {} to {}",
+            chain.gauge,
+            chain.token_mint,
+            chain.reward_each_epoch,
+            chain.briber,
+            chain.bribe_rewards_epoch_start,
+            chain.bribe_rewards_epoch_end
+        ),
+        (None, None) => println!("missing in the database and on chain"),
+    }
+    Ok(())
+}
+
+async fn fetch_account<T: anchor_lang::AccountDeserialize>(
+    core: &Core,
+    pubkey: &Pubkey,
+) -> Result<Option<T>> {
+    let mut accounts = core.chain.get_anchor_accounts::<T>(&[*pubkey]).await?;
+    match accounts.pop() {
+        Some(account) => account,
+        None => Ok(None),
+    }
+}
+
+/// Print the stored and chain values side by side, marking the fields which differ
+fn print_comparison(fields: &[FieldComparison]) {
+    println!("  {:<28} {:<46} {:<46}", "field", "db", "chain");
+    for field in fields.iter() {
+        println!(
+            "{} {:<28} {:<46} {:<46}",
+            if field.matches { " " } else { "!" },
+            field.field,
+            field.db_value,
+            field.chain_value
+        );
+    }
+}
@@ -1,22 +1,26 @@
 // use gauge::GaugeFactory;
+use crate::account_source::AccountSource;
+use crate::admin::TaskControl;
 use crate::anchor_adapter::AClock;
 use crate::database::*;
+use crate::leader::LeaderElection;
+use crate::metrics::Metrics;
+use crate::signer::CrankSigner;
 use crate::state::{
-    BribeInfo, DaoState, EpochGaugeInfoWrapper, EpochInfos, GaugeFactoryState, GaugeInfo,
-    GaugeState, PoolInfo, QuarryInfo,
+    baseline_gauge_status, diff_gauge_events, diff_gauge_keys, is_partial_scan, BribeInfo,
+    CoverageInfo, DaoState, EpochCoverageInfo, EpochGaugeInfoWrapper, EpochInfos,
+    FactoryRolloverCost, GaugeEventInfo, GaugeEventType, GaugeFactoryState, GaugeInfo, GaugeKeys,
+    GaugeState, HealthInfo, PayerBalanceState, PoolInfo, QuarryInfo,
 };
 use crate::sync_gauge::*;
 use crate::unwrap_ok_or;
-use crate::utils::create_program;
-use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
-use anchor_client::solana_client::rpc_filter::RpcFilterType;
-use anchor_client::solana_sdk::signature::{read_keypair_file, Signable};
-use anchor_client::solana_sdk::signer::keypair::Keypair;
-use anchor_client::Program;
+use crate::verifier::VerifyMode;
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anchor_client::solana_sdk::signature::Signable;
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::sysvar;
-use anchor_lang::AccountDeserialize;
 use anyhow::Result;
+use clap::ValueEnum;
 use sqlx::Pool;
 use sqlx::Postgres;
 use std::collections::HashMap;
@@ -25,79 +29,253 @@ use std::convert::TryInto;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Lower bound of the crank delay, so a failing trigger at the epoch boundary is not retried in a hot loop
+const MIN_CRANK_DELAY: Duration = Duration::from_secs(1);
+
+/// (gauge, voting epoch) pairs checked by a single backfill run
+const MAX_BACKFILL_PAIRS: usize = 500;
+/// Epoch gauges missing on chain are checked again for the latest epochs, they may be created late
+const BACKFILL_RECHECK_EPOCHS: i64 = 2;
+
+/// Known gauges are fetched again by a full scan at this interval, in incremental discovery
+const FULL_GAUGE_SCAN_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Gauge events returned by a single request
+const MAX_GAUGE_EVENTS: i64 = 1000;
+
+/// Offset of gauge_factory in a Gauge account, its first field after the anchor discriminator
+const GAUGE_FACTORY_OFFSET: usize = 8;
+
 pub struct Core {
     pub pg_pool: Pool<Postgres>,
     pub base: String,
-    pub provider: String,
-    pub keypair_url: String,
+    /// The default factory also serves the routes which are not namespaced by factory
+    pub is_default_factory: bool,
+    /// On-chain accounts, from the RPC providers or from memory in tests
+    pub chain: Arc<dyn AccountSource>,
+    /// Crank payer, only loaded when the keeper cranks
+    pub signer: Option<Arc<dyn CrankSigner>>,
     pub state: Arc<Mutex<DaoState>>,
+    /// States of every factory cranked by the signer, this one included, they share the payer
+    pub payer_states: Vec<Arc<Mutex<DaoState>>>,
     pub epochs: Arc<Mutex<EpochInfos>>,
+    pub leader: LeaderElection,
+    pub metrics: Arc<Metrics>,
+    pub gauge_discovery: GaugeDiscovery,
+    pub verify_mode: VerifyMode,
+    /// Overwrite the stored rows which differ from the chain
+    pub verify_repair: bool,
+    pub tasks: TaskControl,
+}
+
+/// How process_monitor_gauge finds the gauges of the factory, the first scan is always full
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GaugeDiscovery {
+    /// Fetch every gauge of the factory with getProgramAccounts
+    Full,
+    /// Scan only the keys, and fetch the gauges by key
+    Incremental,
+}
+
+pub use dao_common::pda::{find_bribe_addr, find_epoch_gauge_addr};
+
+pub fn find_gauge_factory_addr(base: &str) -> Pubkey {
+    let base_pk = Pubkey::from_str(base).unwrap();
+    dao_common::pda::find_gauge_factory_addr(&base_pk)
+}
+
+pub fn current_unix_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// (gauge, voting epoch) pairs of the next backfill run, newest epochs first.
+/// The pairs never checked come before the rechecks, so the rechecks cannot starve the backfill
+pub fn select_backfill_pairs(
+    config: &CrawlConfig,
+    current_voting_epoch: i64,
+    gauges: &[String],
+    checked: &HashMap<(String, i64), bool>,
+) -> Result<Vec<(Pubkey, u32)>> {
+    let mut pairs: Vec<(Pubkey, u32)> = vec![];
+    let mut rechecks: Vec<(Pubkey, u32)> = vec![];
+    for voting_epoch in ((config.voting_epoch_down + 1)..=config.voting_epoch_up).rev() {
+        let recheck = voting_epoch > current_voting_epoch - BACKFILL_RECHECK_EPOCHS;
+        for gauge in gauges.iter() {
+            let pair = (Pubkey::from_str(gauge)?, u32::try_from(voting_epoch)?);
+            match checked.get(&(gauge.clone(), voting_epoch)) {
+                Some(false) if recheck => rechecks.push(pair),
+                Some(_) => {}
+                None => pairs.push(pair),
+            }
+        }
+    }
+    pairs.extend(rechecks);
+    pairs.truncate(MAX_BACKFILL_PAIRS);
+    Ok(pairs)
 }
 
 impl Core {
     pub fn get_gauge_factory_addr(&self) -> Pubkey {
-        let base_pk = Pubkey::from_str(&self.base).unwrap();
-        let (gauge_factory, _bump) = Pubkey::find_program_address(
-            &[b"GaugeFactory".as_ref(), base_pk.as_ref()],
-            &gauge::id(),
-        );
-        gauge_factory
+        find_gauge_factory_addr(&self.base)
     }
 
-    pub async fn init(&self) -> Result<()> {
-        let program: Program<Arc<Keypair>> = create_program(
-            self.provider.to_string(),
-            self.provider.to_string(),
-            gauge::ID,
-            Arc::new(Keypair::new()),
-        )?;
-        let gauge_factory = self.get_gauge_factory_addr();
-        let gauge_factory_state: gauge::GaugeFactory = program.account(gauge_factory).await?;
-        let gauges: Vec<(Pubkey, gauge::Gauge)> = program
-            .accounts::<gauge::Gauge>(vec![RpcFilterType::DataSize(
-                (8 + std::mem::size_of::<gauge::Gauge>()) as u64,
-            )])
-            .await?;
+    /// Gauge factory address as stored in the database and used in metrics labels
+    pub fn get_gauge_factory_key(&self) -> String {
+        self.get_gauge_factory_addr().to_string()
+    }
 
-        // filter gauge
-        let gauges = gauges
-            .into_iter()
-            .filter(|&x| x.1.gauge_factory == gauge_factory)
-            .collect::<Vec<(Pubkey, gauge::Gauge)>>();
+    /// Filters matching the gauges of the factory, so the RPC node does the filtering
+    fn gauge_filters(&self) -> Vec<RpcFilterType> {
+        vec![
+            RpcFilterType::DataSize((8 + std::mem::size_of::<gauge::Gauge>()) as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                GAUGE_FACTORY_OFFSET,
+                self.get_gauge_factory_addr().as_ref(),
+            )),
+        ]
+    }
 
-        // get all quarries
-        let quarry_pks = gauges
-            .clone()
-            .into_iter()
-            .map(|x| x.1.quarry)
-            .collect::<Vec<Pubkey>>();
+    /// Fetch the gauges of the factory with a full scan
+    async fn fetch_gauges(&self) -> Result<Vec<(Pubkey, gauge::Gauge)>> {
+        self.chain
+            .get_anchor_program_accounts::<gauge::Gauge>(&gauge::id(), self.gauge_filters())
+            .await
+    }
 
-        let quary_accounts = program.rpc().get_multiple_accounts(&quarry_pks)?;
+    /// Fetch the gauges of the factory, scanning only the keys to find the gauges created
+    /// or gone since the last scan. Only the new gauges are fetched by key, the known ones keep
+    /// their last scanned data until the next full scan, every FULL_GAUGE_SCAN_INTERVAL.
+    /// Fails when a new gauge can not be fetched, rather than returning a smaller set
+    async fn fetch_gauges_incremental(&self) -> Result<Vec<(Pubkey, gauge::Gauge)>> {
+        let (scanned, is_full_scan_due) = {
+            let state = self.state.lock().unwrap();
+            let is_full_scan_due = match state.last_full_gauge_scan {
+                Some(scanned_at) => scanned_at.elapsed() >= FULL_GAUGE_SCAN_INTERVAL,
+                None => true,
+            };
+            (state.scanned_gauges.clone(), is_full_scan_due)
+        };
+        if is_full_scan_due {
+            let gauges = self.fetch_gauges().await?;
+            self.state.lock().unwrap().last_full_gauge_scan = Some(Instant::now());
+            return Ok(gauges);
+        }
 
-        let quarries: Vec<(Pubkey, quarry::Quarry)> = quary_accounts
+        let gauge_pks = self
+            .chain
+            .get_program_account_keys(&gauge::id(), self.gauge_filters())
+            .await?;
+        let new_pks: Vec<Pubkey> = gauge_pks
+            .iter()
+            .filter(|x| !scanned.contains_key(x))
+            .cloned()
+            .collect();
+        let mut new_gauges = HashMap::new();
+        if !new_pks.is_empty() {
+            let gauge_accounts = self
+                .chain
+                .get_anchor_accounts::<gauge::Gauge>(&new_pks)
+                .await?;
+            for (gauge_pk, gauge) in new_pks.into_iter().zip(gauge_accounts.into_iter()) {
+                match gauge {
+                    Ok(Some(gauge)) => {
+                        println!("discovered gauge {}", gauge_pk);
+                        new_gauges.insert(gauge_pk, gauge);
+                    }
+                    Ok(None) => {
+                        return Err(anyhow::Error::msg(format!(
+                            "discovered gauge {} not found",
+                            gauge_pk
+                        )))
+                    }
+                    Err(err) => {
+                        return Err(anyhow::Error::msg(format!(
+                            "cannot decode discovered gauge {}: {}",
+                            gauge_pk, err
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(gauge_pks
             .into_iter()
-            .enumerate()
-            .map(|(i, x)| {
-                (
-                    quarry_pks[i],
-                    quarry::Quarry::try_deserialize(&mut x.unwrap().data.as_ref()).unwrap(),
-                )
+            .filter_map(|gauge_pk| {
+                let gauge = new_gauges
+                    .remove(&gauge_pk)
+                    .or_else(|| scanned.get(&gauge_pk).cloned())?;
+                Some((gauge_pk, gauge))
             })
-            .collect::<Vec<(Pubkey, quarry::Quarry)>>();
+            .collect())
+    }
+
+    /// Fetch the quarries of the gauges
+    async fn fetch_quarries(
+        &self,
+        gauges: &[(Pubkey, gauge::Gauge)],
+    ) -> Result<Vec<(Pubkey, quarry::Quarry)>> {
+        let quarry_pks = gauges.iter().map(|x| x.1.quarry).collect::<Vec<Pubkey>>();
+
+        let quarry_accounts = self
+            .chain
+            .get_anchor_accounts::<quarry::Quarry>(&quarry_pks)
+            .await?;
+
+        let mut quarries: Vec<(Pubkey, quarry::Quarry)> = vec![];
+        for (quarry_pk, quarry) in quarry_pks.iter().zip(quarry_accounts.into_iter()) {
+            match quarry {
+                Ok(Some(quarry)) => quarries.push((*quarry_pk, quarry)),
+                Ok(None) => println!("quarry {} not found", quarry_pk),
+                Err(err) => println!("quarry {} err {}", quarry_pk, err),
+            }
+        }
+        Ok(quarries)
+    }
+
+    /// Fetch the gauges of the factory, and their quarries
+    pub async fn fetch_gauges_and_quarries(
+        &self,
+        discovery: GaugeDiscovery,
+    ) -> Result<(Vec<(Pubkey, gauge::Gauge)>, Vec<(Pubkey, quarry::Quarry)>)> {
+        let gauges = match discovery {
+            GaugeDiscovery::Full => self.fetch_gauges().await?,
+            GaugeDiscovery::Incremental => self.fetch_gauges_incremental().await?,
+        };
+        let quarries = self.fetch_quarries(&gauges).await?;
+        Ok((gauges, quarries))
+    }
+
+    pub async fn init(&self) -> Result<()> {
+        let gauge_factory = self.get_gauge_factory_addr();
+        let gauge_factory_state: gauge::GaugeFactory =
+            self.chain.get_anchor_account(&gauge_factory).await?;
+        let (gauges, quarries) = self.fetch_gauges_and_quarries(GaugeDiscovery::Full).await?;
 
         {
             let mut state = self.state.lock().unwrap();
-            state.save_gauges_and_quarries(&gauges, &quarries);
+            state.save_gauges_and_quarries(&gauges, &quarries, true);
             state.save_gauge_factory(&gauge_factory_state, self.base.clone(), &gauge_factory);
         };
+        self.refresh_rewarder(&gauge_factory_state.rewarder).await;
 
-        let crawl_epoch_up = get_voting_epoch_up(&self.pg_pool).await;
+        let gauge_factory_key = gauge_factory.to_string();
+        if self.is_default_factory {
+            claim_legacy_rows(&self.pg_pool, &gauge_factory_key).await?;
+        }
+
+        let crawl_epoch_up = get_voting_epoch_up(&self.pg_pool, &gauge_factory_key).await;
         match crawl_epoch_up {
             Ok(_value) => {}
             Err(err) => match err {
                 sqlx::Error::RowNotFound => {
                     init_crawl_config(
                         &self.pg_pool,
+                        &gauge_factory_key,
                         gauge_factory_state.current_voting_epoch.into(),
                     )
                     .await?
@@ -110,73 +288,209 @@ impl Core {
 
         Ok(())
     }
-    pub async fn process_monitor_gauge_factory(&self) {
-        let program: Program<Arc<Keypair>> = unwrap_ok_or!(
-            create_program(
-                self.provider.to_string(),
-                self.provider.to_string(),
-                gauge::ID,
-                Arc::new(Keypair::new()),
-            ),
-            "Cannot get program client"
+    pub async fn process_leader_election(&self) -> Result<()> {
+        let was_leader = self.leader.is_leader();
+        let is_leader = self.leader.renew(&self.pg_pool).await?;
+        self.metrics.set(
+            "keeper_is_leader",
+            "Whether this replica holds the leader lock",
+            &[("gauge_factory", &self.get_gauge_factory_key())],
+            if is_leader { 1.0 } else { 0.0 },
         );
+        if was_leader != is_leader {
+            println!(
+                "leadership changed for gauge factory {}: is_leader {}",
+                self.get_gauge_factory_addr(),
+                is_leader
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn process_monitor_gauge_factory(&self) {
         let gauge_factory = self.get_gauge_factory_addr();
         let gauge_factory_state: gauge::GaugeFactory = unwrap_ok_or!(
-            program.account(gauge_factory).await,
+            self.chain.get_anchor_account(&gauge_factory).await,
             "cannot get gauge state"
         );
 
-        let mut state = self.state.lock().unwrap();
-        state.save_gauge_factory(&gauge_factory_state, self.base.clone(), &gauge_factory);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.save_gauge_factory(&gauge_factory_state, self.base.clone(), &gauge_factory);
+        }
+        self.refresh_rewarder(&gauge_factory_state.rewarder).await;
     }
 
-    pub async fn process_monitor_gauge(&self) -> Result<()> {
-        let program: Program<Arc<Keypair>> = create_program(
-            self.provider.to_string(),
-            self.provider.to_string(),
-            gauge::ID,
-            Arc::new(Keypair::new()),
-        )?;
-        let gauge_factory = self.get_gauge_factory_addr();
+    /// Best effort, the rewarder only feeds the emission analytics, which keep the last rate
+    async fn refresh_rewarder(&self, rewarder: &Pubkey) {
+        match self
+            .chain
+            .get_anchor_account::<quarry::Rewarder>(rewarder)
+            .await
+        {
+            Ok(rewarder_state) => {
+                let mut state = self.state.lock().unwrap();
+                state.save_rewarder(&rewarder_state, rewarder);
+            }
+            Err(err) => println!("cannot get rewarder state {}", err),
+        }
+    }
 
-        let gauges: Vec<(Pubkey, gauge::Gauge)> = program
-            .accounts::<gauge::Gauge>(vec![RpcFilterType::DataSize(
-                (8 + std::mem::size_of::<gauge::Gauge>()) as u64,
-            )])
-            .await?;
+    pub async fn process_monitor_gauge(&self) -> Result<()> {
+        let (gauges, quarries) = self.fetch_gauges_and_quarries(self.gauge_discovery).await?;
 
-        // filter gauge
-        let gauges = gauges
-            .into_iter()
-            .filter(|&x| x.1.gauge_factory == gauge_factory)
-            .collect::<Vec<(Pubkey, gauge::Gauge)>>();
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            // a truncated answer of the RPC node must not empty the cache
+            let is_full_scan = !is_partial_scan(state.gauges.len(), gauges.len());
+            if !is_full_scan {
+                println!(
+                    "ALERT: gauge scan found {} of {} cached gauges, keeping the cached ones",
+                    gauges.len(),
+                    state.gauges.len()
+                );
+            }
+            state.save_gauges_and_quarries(&gauges, &quarries, is_full_scan)
+        };
+        for gauge_pk in removed.iter() {
+            println!("gauge {} is gone from the factory", gauge_pk);
+        }
 
-        // get all quarries
-        let quarry_pks = gauges
-            .clone()
-            .into_iter()
-            .map(|x| x.1.quarry)
-            .collect::<Vec<Pubkey>>();
+        // only the leader records the events, so replicas do not record them twice
+        if self.leader.is_leader() {
+            self.record_gauge_events(&gauges).await?;
+        }
+        Ok(())
+    }
 
-        let quary_accounts = program.rpc().get_multiple_accounts(&quarry_pks)?;
+    /// Record the status and key events between the recorded status and keys of every gauge
+    /// and the scan. They are compared with gauge_snapshot, which moves with the stored events, so
+    /// changes made while no keeper was running or across a restart or a leader change are recorded too.
+    /// The first scan of a factory is recorded as the baseline, without events
+    async fn record_gauge_events(&self, gauges: &[(Pubkey, gauge::Gauge)]) -> Result<()> {
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let snapshots = get_gauge_snapshots(&self.pg_pool, &gauge_factory_key).await?;
+        if snapshots.is_empty() {
+            if !gauges.is_empty() {
+                println!("gauge baseline of {} gauges", gauges.len());
+                let baseline: Vec<GaugeSnapshot> = gauges
+                    .iter()
+                    .map(|(pubkey, gauge)| {
+                        let keys = GaugeKeys::new(gauge);
+                        GaugeSnapshot {
+                            gauge_factory: gauge_factory_key.clone(),
+                            gauge: pubkey.to_string(),
+                            status: baseline_gauge_status(gauge).as_str().to_string(),
+                            quarry: Some(keys.quarry),
+                            token_a_fee_key: Some(keys.token_a_fee_key),
+                            token_b_fee_key: Some(keys.token_b_fee_key),
+                        }
+                    })
+                    .collect();
+                seed_gauge_snapshots(&self.pg_pool, &baseline).await?;
+            }
+            return Ok(());
+        }
 
-        let quarries: Vec<(Pubkey, quarry::Quarry)> = quary_accounts
-            .into_iter()
-            .enumerate()
-            .map(|(i, x)| {
-                (
-                    quarry_pks[i],
-                    quarry::Quarry::try_deserialize(&mut x.unwrap().data.as_ref()).unwrap(),
-                )
-            })
-            .collect::<Vec<(Pubkey, quarry::Quarry)>>();
+        let mut latest = HashMap::new();
+        let mut recorded = HashMap::new();
+        for snapshot in snapshots {
+            latest.insert(
+                snapshot.gauge.clone(),
+                GaugeEventType::from_str(&snapshot.status)?,
+            );
+            if let (Some(quarry), Some(token_a_fee_key), Some(token_b_fee_key)) = (
+                snapshot.quarry,
+                snapshot.token_a_fee_key,
+                snapshot.token_b_fee_key,
+            ) {
+                recorded.insert(
+                    snapshot.gauge,
+                    GaugeKeys {
+                        quarry,
+                        token_a_fee_key,
+                        token_b_fee_key,
+                    },
+                );
+            }
+        }
+        let mut changes = diff_gauge_events(&latest, gauges);
+        let known = latest
+            .values()
+            .filter(|x| **x != GaugeEventType::Removed)
+            .count();
+        if is_partial_scan(known, gauges.len()) {
+            println!(
+                "ALERT: gauge scan found {} of {} known gauges, not recording removals",
+                gauges.len(),
+                known
+            );
+            changes.retain(|x| x.event_type != GaugeEventType::Removed);
+        }
+        changes.extend(diff_gauge_keys(&recorded, gauges));
+
+        if !changes.is_empty() {
+            // events are stamped with the cluster clock
+            let clock: AClock = self.chain.get_anchor_account(&sysvar::clock::id()).await?;
+            let slot = i64::try_from(clock.slot)?;
+            for change in changes {
+                println!(
+                    "gauge {} {} at slot {}",
+                    change.gauge,
+                    change.event_type.as_str(),
+                    slot
+                );
+                if change.event_type.is_status() {
+                    insert_gauge_status_event(
+                        &self.pg_pool,
+                        &gauge_factory_key,
+                        &change.gauge,
+                        change.event_type.as_str(),
+                        slot,
+                        clock.unix_timestamp,
+                    )
+                    .await?;
+                } else {
+                    insert_gauge_key_event(
+                        &self.pg_pool,
+                        &gauge_factory_key,
+                        &change.gauge,
+                        change.event_type.as_str(),
+                        &change.old_value.unwrap_or_default(),
+                        &change.new_value.unwrap_or_default(),
+                        slot,
+                        clock.unix_timestamp,
+                    )
+                    .await?;
+                }
+            }
+        }
 
-        let mut state = self.state.lock().unwrap();
-        state.save_gauges_and_quarries(&gauges, &quarries);
+        // gauges seen for the first time, or recorded before their keys were, get their keys without events
+        for (pubkey, gauge) in gauges.iter() {
+            let pubkey = pubkey.to_string();
+            if recorded.contains_key(&pubkey) {
+                continue;
+            }
+            let keys = GaugeKeys::new(gauge);
+            save_gauge_snapshot_keys(
+                &self.pg_pool,
+                &gauge_factory_key,
+                &pubkey,
+                &keys.quarry,
+                &keys.token_a_fee_key,
+                &keys.token_b_fee_key,
+            )
+            .await?;
+        }
         Ok(())
     }
 
     pub async fn process_crawl_epoch_up(&self) -> Result<()> {
+        // only the leader moves crawl_config forward
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
         let current_voting_epoch = {
             let state: std::sync::MutexGuard<'_, DaoState> = self.state.lock().unwrap();
             if !state.is_gauge_factory_initialized() {
@@ -184,7 +498,8 @@ impl Core {
             }
             state.gauge_factory.current_voting_epoch
         };
-        let crawl_epoch_up = get_voting_epoch_up(&self.pg_pool).await?;
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let crawl_epoch_up = get_voting_epoch_up(&self.pg_pool, &gauge_factory_key).await?;
         let crawl_epoch_up: u32 = crawl_epoch_up.try_into()?;
 
         let should_craw_epoch = if current_voting_epoch > crawl_epoch_up {
@@ -205,42 +520,47 @@ impl Core {
 
         let epoch_pubkeys: Vec<Pubkey> = gauges
             .iter()
-            .map(|&gauge| {
-                let (epoch_gauge, _bump) = Pubkey::find_program_address(
-                    &[
-                        b"EpochGauge".as_ref(),
-                        gauge.as_ref(),
-                        should_craw_epoch.to_le_bytes().as_ref(),
-                    ],
-                    &gauge::id(),
-                );
-                epoch_gauge
-            })
+            .map(|gauge| find_epoch_gauge_addr(gauge, should_craw_epoch))
             .collect();
 
-        let rpc_client = RpcClient::new(self.provider.clone());
-
-        let epoch_gauges = rpc_client.get_multiple_accounts(&epoch_pubkeys).await?;
-
-        let epoch_gauges: Vec<gauge::EpochGauge> = epoch_gauges
+        let epoch_gauges: Vec<Option<gauge::EpochGauge>> = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&epoch_pubkeys)
+            .await?
             .into_iter()
-            .filter(|x| !x.is_none())
-            .map(|x| gauge::EpochGauge::try_deserialize(&mut x.unwrap().data.as_ref()).unwrap())
+            .collect::<Result<Vec<Option<gauge::EpochGauge>>>>()?;
+        let coverage: Vec<(Pubkey, i64, bool)> = gauges
+            .iter()
+            .zip(epoch_gauges.iter())
+            .map(|(gauge, x)| (*gauge, i64::from(should_craw_epoch), x.is_some()))
             .collect();
+        let epoch_gauges: Vec<gauge::EpochGauge> = epoch_gauges.into_iter().flatten().collect();
 
         save_epoch_gauges_up(
             &self.pg_pool,
+            &gauge_factory_key,
             &epoch_gauges,
             (should_craw_epoch + 1).into(),
             should_craw_epoch < current_voting_epoch,
         )
         .await?;
+        save_crawl_coverage(
+            &self.pg_pool,
+            &gauge_factory_key,
+            &coverage,
+            current_unix_timestamp(),
+        )
+        .await?;
 
         Ok(())
     }
 
     pub async fn process_crawl_epoch_down(&self) -> Result<()> {
-        let crawl_epoch_down = get_voting_epoch_down(&self.pg_pool).await?;
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let crawl_epoch_down = get_voting_epoch_down(&self.pg_pool, &gauge_factory_key).await?;
         if crawl_epoch_down < 0 {
             return Ok(());
         }
@@ -258,37 +578,167 @@ impl Core {
 
         let epoch_pubkeys: Vec<Pubkey> = gauges
             .iter()
-            .map(|&gauge| {
-                let (epoch_gauge, _bump) = Pubkey::find_program_address(
-                    &[
-                        b"EpochGauge".as_ref(),
-                        gauge.as_ref(),
-                        crawl_epoch_down.to_le_bytes().as_ref(),
-                    ],
-                    &gauge::id(),
-                );
-                epoch_gauge
-            })
+            .map(|gauge| find_epoch_gauge_addr(gauge, crawl_epoch_down))
             .collect();
 
-        let rpc_client = RpcClient::new(self.provider.clone());
+        let epoch_gauges: Vec<Option<gauge::EpochGauge>> = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&epoch_pubkeys)
+            .await?
+            .into_iter()
+            .collect::<Result<Vec<Option<gauge::EpochGauge>>>>()?;
+        let coverage: Vec<(Pubkey, i64, bool)> = gauges
+            .iter()
+            .zip(epoch_gauges.iter())
+            .map(|(gauge, x)| (*gauge, i64::from(crawl_epoch_down), x.is_some()))
+            .collect();
+        let epoch_gauges: Vec<gauge::EpochGauge> = epoch_gauges.into_iter().flatten().collect();
 
-        let epoch_gauges = rpc_client.get_multiple_accounts(&epoch_pubkeys).await?;
+        let crawl_epoch_down: i64 = crawl_epoch_down.into();
+        save_epoch_gauges_down(
+            &self.pg_pool,
+            &gauge_factory_key,
+            &epoch_gauges,
+            crawl_epoch_down - 1,
+        )
+        .await?;
+        save_crawl_coverage(
+            &self.pg_pool,
+            &gauge_factory_key,
+            &coverage,
+            current_unix_timestamp(),
+        )
+        .await?;
 
-        let epoch_gauges: Vec<gauge::EpochGauge> = epoch_gauges
+        Ok(())
+    }
+
+    /// Coverage rows between the crawl pointers, keyed by (gauge, voting epoch)
+    async fn load_crawl_coverage(
+        &self,
+    ) -> Result<(CrawlConfig, Vec<String>, HashMap<(String, i64), bool>)> {
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let config = get_crawl_config(&self.pg_pool, &gauge_factory_key).await?;
+        let gauges: Vec<String> = {
+            let state = self.state.lock().unwrap();
+            state.get_gauges().into_iter().map(|x| x.pubkey).collect()
+        };
+        let rows = get_crawl_coverage(
+            &self.pg_pool,
+            &gauge_factory_key,
+            config.voting_epoch_down + 1,
+            config.voting_epoch_up,
+        )
+        .await?;
+        let checked = rows
             .into_iter()
-            .filter(|x| !x.is_none())
-            .map(|x| gauge::EpochGauge::try_deserialize(&mut x.unwrap().data.as_ref()).unwrap())
+            .map(|x| ((x.gauge, x.voting_epoch), x.found))
             .collect();
+        Ok((config, gauges, checked))
+    }
 
-        let crawl_epoch_down: i64 = crawl_epoch_down.into();
-        save_epoch_gauges_down(&self.pg_pool, &epoch_gauges, crawl_epoch_down - 1).await?;
+    pub async fn get_crawl_coverage_info(&self) -> Result<CoverageInfo> {
+        let (config, gauges, checked) = self.load_crawl_coverage().await?;
+        let mut info = CoverageInfo {
+            voting_epoch_down: config.voting_epoch_down,
+            voting_epoch_up: config.voting_epoch_up,
+            num_gauges: gauges.len() as u64,
+            ..CoverageInfo::default()
+        };
+        for voting_epoch in (config.voting_epoch_down + 1)..=config.voting_epoch_up {
+            let mut epoch_info = EpochCoverageInfo {
+                voting_epoch,
+                ..EpochCoverageInfo::default()
+            };
+            for gauge in gauges.iter() {
+                match checked.get(&(gauge.clone(), voting_epoch)) {
+                    Some(true) => epoch_info.num_found += 1,
+                    Some(false) => epoch_info.num_not_on_chain += 1,
+                    None => epoch_info.unchecked_gauges.push(gauge.clone()),
+                }
+            }
+            info.num_found += epoch_info.num_found;
+            info.num_not_on_chain += epoch_info.num_not_on_chain;
+            info.num_unchecked += epoch_info.unchecked_gauges.len() as u64;
+            info.epochs.push(epoch_info);
+        }
+        Ok(info)
+    }
+
+    /// Fetch the (gauge, voting epoch) pairs the crawl pointers went past without checking,
+    /// like gauges added after their epoch was crawled, latest epochs first
+    pub async fn process_backfill_epoch_gauges(&self) -> Result<()> {
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
+        let current_voting_epoch: i64 = {
+            let state = self.state.lock().unwrap();
+            if !state.is_gauge_factory_initialized() {
+                return Ok(());
+            }
+            state.gauge_factory.current_voting_epoch.into()
+        };
+        let (config, gauges, checked) = self.load_crawl_coverage().await?;
+
+        let pairs = select_backfill_pairs(&config, current_voting_epoch, &gauges, &checked)?;
+        if pairs.is_empty() {
+            return Ok(());
+        }
+
+        let epoch_pubkeys: Vec<Pubkey> = pairs
+            .iter()
+            .map(|(gauge, voting_epoch)| find_epoch_gauge_addr(gauge, *voting_epoch))
+            .collect();
+        let accounts = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&epoch_pubkeys)
+            .await?;
+
+        let mut epoch_gauges = vec![];
+        let mut coverage = vec![];
+        for ((gauge, voting_epoch), account) in pairs.iter().zip(accounts.into_iter()) {
+            let account = match account {
+                Ok(account) => account,
+                Err(err) => {
+                    // not recorded as checked, so it is retried once the decoding is fixed
+                    println!(
+                        "cannot decode epoch gauge of gauge {} voting epoch {}: {}",
+                        gauge, voting_epoch, err
+                    );
+                    continue;
+                }
+            };
+            coverage.push((*gauge, i64::from(*voting_epoch), account.is_some()));
+            if let Some(epoch_gauge) = account {
+                epoch_gauges.push(epoch_gauge);
+            }
+        }
 
+        let gauge_factory_key = self.get_gauge_factory_key();
+        if !epoch_gauges.is_empty() {
+            println!(
+                "backfill {} epoch gauges of gauge factory {}",
+                epoch_gauges.len(),
+                gauge_factory_key
+            );
+        }
+        save_epoch_gauges(&self.pg_pool, &gauge_factory_key, &epoch_gauges).await?;
+        save_crawl_coverage(
+            &self.pg_pool,
+            &gauge_factory_key,
+            &coverage,
+            current_unix_timestamp(),
+        )
+        .await?;
         Ok(())
     }
 
     pub async fn process_crawl_bribe(&self) -> Result<()> {
-        let bribe_index = get_max_bribe_index(&self.pg_pool).await?;
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let bribe_index = get_max_bribe_index(&self.pg_pool, &gauge_factory_key).await?;
 
         let current_bribe_index = {
             let state = self.state.lock().unwrap();
@@ -300,30 +750,24 @@ impl Core {
         }
         let next_bribe_index: u32 = (bribe_index + 1).try_into()?;
 
-        let (bribe, _bump) = Pubkey::find_program_address(
-            &[
-                b"Bribe".as_ref(),
-                self.get_gauge_factory_addr().as_ref(),
-                next_bribe_index.to_le_bytes().as_ref(),
-            ],
-            &gauge::id(),
-        );
-
-        let program: Program<Arc<Keypair>> = create_program(
-            self.provider.to_string(),
-            self.provider.to_string(),
-            gauge::ID,
-            Arc::new(Keypair::new()),
-        )?;
+        let bribe = find_bribe_addr(&self.get_gauge_factory_addr(), next_bribe_index);
 
-        let bribe_state: gauge::Bribe = program.account(bribe).await?;
+        let bribe_state: gauge::Bribe = self.chain.get_anchor_account(&bribe).await?;
 
-        save_bribe(&self.pg_pool, bribe, &bribe_state).await?;
+        save_bribe(&self.pg_pool, &gauge_factory_key, bribe, &bribe_state).await?;
 
         Ok(())
     }
 
     pub async fn process_cache_latest_epoches(&self) -> Result<()> {
+        // apply the invalidations made on any replica
+        let generation =
+            get_epoch_cache_generation(&self.pg_pool, &self.get_gauge_factory_key()).await?;
+        {
+            let mut epochs = self.epochs.lock().unwrap();
+            epochs.invalidate(generation);
+        }
+
         // latest epoch
         let gauge_factory_state = self.get_gauge_factory();
         let current_voting_epoch: u64 = gauge_factory_state.current_voting_epoch.into();
@@ -337,32 +781,175 @@ impl Core {
         Ok(())
     }
 
+    pub async fn process_monitor_payer_balance(&self) -> Result<PayerBalanceState> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or(anyhow::Error::msg("crank signer is not loaded"))?;
+        let payer = signer.pubkey();
+
+        let balance = self.chain.get_balance(&payer).await?;
+        let epoch_gauge_rent = self
+            .chain
+            .get_minimum_balance_for_rent_exemption(8 + std::mem::size_of::<gauge::EpochGauge>())
+            .await?;
+
+        let factories: Vec<FactoryRolloverCost> = self
+            .payer_states
+            .iter()
+            .map(|x| x.lock().unwrap().rollover_cost(epoch_gauge_rent))
+            .collect();
+        let payer_balance = PayerBalanceState {
+            payer: payer.to_string(),
+            balance,
+            required_balance: factories
+                .iter()
+                .fold(0u64, |acc, x| acc.saturating_add(x.required_balance)),
+            epoch_gauge_rent,
+            num_gauges: factories.iter().map(|x| x.num_gauges).sum(),
+            factories,
+            updated_at: current_unix_timestamp(),
+        };
+
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let labels = [("gauge_factory", gauge_factory_key.as_str())];
+        let payer_key = payer_balance.payer.clone();
+        let required_balance = payer_balance
+            .factories
+            .iter()
+            .find(|x| x.gauge_factory == gauge_factory_key)
+            .map(|x| x.required_balance)
+            .unwrap_or(0);
+
+        self.metrics.set(
+            "keeper_payer_balance_lamports",
+            "Balance of the crank payer",
+            &labels,
+            payer_balance.balance as f64,
+        );
+        self.metrics.set(
+            "keeper_rollover_required_lamports",
+            "Estimated lamports the next epoch rollover of the factory needs",
+            &labels,
+            required_balance as f64,
+        );
+        self.metrics.set(
+            "keeper_payer_required_lamports",
+            "Estimated lamports the next epoch rollover of every factory cranked by the payer needs",
+            &[("payer", payer_key.as_str())],
+            payer_balance.required_balance as f64,
+        );
+        self.metrics.set(
+            "keeper_epoch_gauge_rent_lamports",
+            "Rent exempt balance of an epoch gauge account",
+            &labels,
+            payer_balance.epoch_gauge_rent as f64,
+        );
+        self.metrics.set(
+            "keeper_crank_paused",
+            "Whether cranking is paused because the payer balance is too low",
+            &labels,
+            if payer_balance.is_sufficient() {
+                0.0
+            } else {
+                1.0
+            },
+        );
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.payer_balance = payer_balance.clone();
+        }
+        Ok(payer_balance)
+    }
+
     pub async fn process_sync_gauge(&self) -> Result<()> {
-        //payer
-        // let payer = read_keypair_file(self.keypair_url.clone()).expect("Requires a keypair file");
-        let program: Program<Arc<Keypair>> = create_program(
-            self.provider.to_string(),
-            self.provider.to_string(),
-            gauge::ID,
-            Arc::new(read_keypair_file(self.keypair_url.clone()).expect("Requires a keypair file")),
-        )?;
-
-        // let current_node_time =
-        let clock: AClock = program.account(sysvar::clock::id()).await?;
+        self.crank(false).await
+    }
+
+    /// Run a crank round: trigger the next epoch when it is due, sync the gauges of the rewards epoch
+    /// and create the epoch gauges of the voting epoch. A dry run simulates or prints the transactions instead.
+    pub async fn crank(&self, dry_run: bool) -> Result<()> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or(anyhow::Error::msg("crank signer is not loaded"))?
+            .as_ref();
+
+        // every replica reports its payer balance, even when it is not the leader.
+        // Best effort, a failed check must not stop the crank
+        let payer_balance = match self.process_monitor_payer_balance().await {
+            Ok(payer_balance) => Some(payer_balance),
+            Err(err) => {
+                println!("cannot check payer balance {}", err);
+                None
+            }
+        };
+
+        // replicas which are not the leader must not crank, they would race the leader
+        if !dry_run && !self.leader.is_leader() {
+            return Ok(());
+        }
+
+        if let Some(payer_balance) = payer_balance.filter(|x| !x.is_sufficient()) {
+            println!(
+                "ALERT: crank paused, payer {} balance {} lamports is below the {} lamports required to roll over {} gauges of {} factories",
+                payer_balance.payer,
+                payer_balance.balance,
+                payer_balance.required_balance,
+                payer_balance.num_gauges,
+                payer_balance.factories.len()
+            );
+            return Ok(());
+        }
+        // use the cluster clock and a fresh gauge factory, the cached one can be a minute stale
+        let clock: AClock = self.chain.get_anchor_account(&sysvar::clock::id()).await?;
         let current_node_time = u64::try_from(clock.unix_timestamp)?;
+        let gauge_factory = self.get_gauge_factory_addr();
+        let gauge_factory_state: gauge::GaugeFactory =
+            self.chain.get_anchor_account(&gauge_factory).await?;
         let should_trigger_next_epoch = {
-            let state = self.state.lock().unwrap();
+            let mut state = self.state.lock().unwrap();
+            state.save_gauge_factory(&gauge_factory_state, self.base.clone(), &gauge_factory);
             state.should_trigger_next_epoch(current_node_time)
         };
 
-        let gauge_factory = self.get_gauge_factory_addr();
         // trigger next epoch
-        if should_trigger_next_epoch {
-            trigger_next_epoch(&program, &self.keypair_url, gauge_factory).await?;
-        }
+        let gauge_factory_state = if should_trigger_next_epoch && dry_run {
+            println!(
+                "dry run: would trigger next epoch of gauge factory {}",
+                gauge_factory
+            );
+            gauge_factory_state
+        } else if should_trigger_next_epoch {
+            let signature = trigger_next_epoch(self.chain.as_ref(), signer, gauge_factory).await?;
+            let gauge_factory_state: gauge::GaugeFactory =
+                self.chain.get_anchor_account(&gauge_factory).await?;
+            {
+                let mut state = self.state.lock().unwrap();
+                state.save_gauge_factory(&gauge_factory_state, self.base.clone(), &gauge_factory);
+            }
+            // the epoch started with the transaction, its end is set one duration later
+            let started_at = gauge_factory_state
+                .next_epoch_starts_at
+                .saturating_sub(gauge_factory_state.epoch_duration_seconds.into());
+            if let Err(err) = save_epoch_transition(
+                &self.pg_pool,
+                &self.get_gauge_factory_key(),
+                gauge_factory_state.current_voting_epoch.into(),
+                &signature.to_string(),
+                i64::try_from(started_at)?,
+            )
+            .await
+            {
+                println!("cannot record epoch transition {}", err);
+            }
+            gauge_factory_state
+        } else {
+            gauge_factory_state
+        };
 
         // check whether old gauge are sync
-        let gauge_factory_state: gauge::GaugeFactory = program.account(gauge_factory).await?;
         let gauges = {
             let state = self.state.lock().unwrap();
             let gauges = state.get_gauges();
@@ -373,72 +960,122 @@ impl Core {
                 .collect();
             gauge_pubkeys
         };
-        for gauge_pk in gauges.iter() {
-            let (epoch_gauge, _bump) = Pubkey::find_program_address(
-                &[
-                    b"EpochGauge".as_ref(),
-                    gauge_pk.as_ref(),
-                    gauge_factory_state.rewards_epoch()?.to_le_bytes().as_ref(),
-                ],
-                &gauge::id(),
-            );
-            match program.rpc().get_account(&epoch_gauge) {
-                Ok(account) => {
-                    let epoch_gauge_state =
-                        gauge::EpochGauge::try_deserialize(&mut account.data.as_ref())?;
-                    let gauge_state: gauge::Gauge = program.account(*gauge_pk).await?;
-                    let quarry_state: quarry::Quarry = program.account(gauge_state.quarry).await?;
+        let rewards_epoch = gauge_factory_state.rewards_epoch()?;
+        let epoch_pubkeys: Vec<Pubkey> = gauges
+            .iter()
+            .map(|gauge_pk| find_epoch_gauge_addr(gauge_pk, rewards_epoch))
+            .collect();
+        let epoch_gauges = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&epoch_pubkeys)
+            .await?;
+        for (gauge_pk, epoch_gauge_state) in gauges.iter().zip(epoch_gauges.into_iter()) {
+            match epoch_gauge_state? {
+                Some(epoch_gauge_state) => {
+                    let gauge_state: gauge::Gauge = self.chain.get_anchor_account(gauge_pk).await?;
+                    let quarry_state: quarry::Quarry =
+                        self.chain.get_anchor_account(&gauge_state.quarry).await?;
                     if quarry_state.rewards_share != epoch_gauge_state.total_power {
                         println!(
                             "sync gauge {} epoch {} quarry_rewards_share {} epoch_total_power {}",
                             gauge_pk,
-                            gauge_factory_state.rewards_epoch()?,
+                            rewards_epoch,
                             quarry_state.rewards_share,
                             epoch_gauge_state.total_power
                         );
                         sync_gauge(
-                            &program,
-                            &self.keypair_url,
+                            self.chain.as_ref(),
+                            signer,
                             gauge_factory,
                             *gauge_pk,
-                            gauge_factory_state.rewards_epoch()?,
-                            false,
+                            rewards_epoch,
+                            dry_run,
                         )
                         .await?;
                     }
                 }
-                Err(err) => {
-                    // println!("{}", err);
-                }
+                None => {}
             }
         }
 
         // check whether to new epoch gauge is created
-        for gauge_pk in gauges.iter() {
-            let (epoch_gauge, _bump) = Pubkey::find_program_address(
-                &[
-                    b"EpochGauge".as_ref(),
-                    gauge_pk.as_ref(),
-                    gauge_factory_state
-                        .current_voting_epoch
-                        .to_le_bytes()
-                        .as_ref(),
-                ],
-                &gauge::id(),
-            );
-            match program.rpc().get_account(&epoch_gauge) {
-                Ok(_account) => {}
-                Err(err) => {
+        let epoch_pubkeys: Vec<Pubkey> = gauges
+            .iter()
+            .map(|gauge_pk| {
+                find_epoch_gauge_addr(gauge_pk, gauge_factory_state.current_voting_epoch)
+            })
+            .collect();
+        let epoch_gauges = self.chain.get_multiple_accounts(&epoch_pubkeys).await?;
+        for (gauge_pk, epoch_gauge) in gauges.iter().zip(epoch_gauges.into_iter()) {
+            match epoch_gauge {
+                Some(_account) => {}
+                None if dry_run => {
+                    println!("dry run: would create epoch gauge {}", gauge_pk);
+                }
+                None => {
                     println!("create epoch gauge {}", gauge_pk);
-                    create_epoch_gauge(&program, &self.keypair_url, gauge_factory, *gauge_pk)
+                    create_epoch_gauge(self.chain.as_ref(), signer, gauge_factory, *gauge_pk)
                         .await?;
                 }
             }
         }
 
+        if should_trigger_next_epoch && !dry_run {
+            // crawl the epoch which just ended and serve the new one right away
+            self.process_crawl_epoch_up().await?;
+            self.process_cache_latest_epoches().await?;
+        }
+
         Ok(())
     }
 
+    /// How long the crank can sleep: until the next epoch boundary by the cluster clock,
+    /// but never longer than max_delay so gauges keep being synced
+    pub async fn next_crank_delay(&self, max_delay: Duration) -> Result<Duration> {
+        let clock: AClock = self.chain.get_anchor_account(&sysvar::clock::id()).await?;
+        let current_node_time = u64::try_from(clock.unix_timestamp)?;
+        let seconds_until_next_epoch = {
+            let state = self.state.lock().unwrap();
+            state.seconds_until_next_epoch(current_node_time)
+        };
+        // the cluster clock only moves with new blocks, don't spin while waiting for it
+        Ok(Duration::from_secs(seconds_until_next_epoch)
+            .max(MIN_CRANK_DELAY)
+            .min(max_delay))
+    }
+
+    pub fn get_health(&self) -> HealthInfo {
+        let should_crank = self.signer.is_some();
+        let payer_balance = if should_crank {
+            let state = self.state.lock().unwrap();
+            Some(state.payer_balance.clone())
+        } else {
+            None
+        };
+        let crank_paused = payer_balance
+            .as_ref()
+            .map(|x| x.updated_at > 0 && !x.is_sufficient())
+            .unwrap_or(false);
+
+        HealthInfo {
+            status: if crank_paused {
+                String::from("low_payer_balance")
+            } else {
+                String::from("ok")
+            },
+            is_leader: self.leader.is_leader(),
+            rpc_endpoints: self.chain.get_health(),
+            should_crank,
+            crank_paused,
+            payer_balance,
+            paused_tasks: self.tasks.get_paused(),
+        }
+    }
+
+    pub fn get_metrics(&self) -> String {
+        self.metrics.render()
+    }
+
     pub fn get_gauge_factory(&self) -> GaugeFactoryState {
         let state: std::sync::MutexGuard<'_, DaoState> = self.state.lock().unwrap();
         return state.gauge_factory.clone();
@@ -448,6 +1085,39 @@ impl Core {
         let state: std::sync::MutexGuard<'_, DaoState> = self.state.lock().unwrap();
         return state.get_gauges();
     }
+
+    /// Gauge events since a timestamp and after an event id, oldest first
+    pub async fn get_gauge_events(
+        &self,
+        event_type: Option<GaugeEventType>,
+        gauge: Option<String>,
+        since: i64,
+        after_id: i64,
+    ) -> Result<Vec<GaugeEventInfo>> {
+        let events = get_gauge_events(
+            &self.pg_pool,
+            &self.get_gauge_factory_key(),
+            event_type.map(|x| x.as_str().to_string()),
+            gauge,
+            since,
+            after_id,
+            MAX_GAUGE_EVENTS,
+        )
+        .await?;
+        let mut infos = vec![];
+        for event in events {
+            infos.push(GaugeEventInfo {
+                id: event.id,
+                gauge: event.gauge,
+                event_type: GaugeEventType::from_str(&event.event_type)?,
+                old_value: event.old_value,
+                new_value: event.new_value,
+                slot: event.slot,
+                created_at: event.created_at,
+            });
+        }
+        Ok(infos)
+    }
     pub async fn get_epoch_info(&self, epoch: u64) -> Result<Vec<GaugeInfo>> {
         // get from cache first
         let epoch_info = {
@@ -543,13 +1213,15 @@ impl Core {
     }
     pub async fn get_epoch_info_internal(&self, epoch: u64) -> Result<Vec<GaugeInfo>> {
         let epoch: i64 = epoch.try_into()?;
-        let epoch_gauges = get_epoch_gauges(&self.pg_pool, epoch).await?;
-        let bribes = get_bribes(&self.pg_pool, epoch).await?;
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let epoch_gauges = get_epoch_gauges(&self.pg_pool, &gauge_factory_key, epoch).await?;
+        let bribes = get_bribes(&self.pg_pool, &gauge_factory_key, epoch).await?;
         let gauges = self.get_gauges();
 
         let mut gauge_infos = vec![];
         for epoch_gauge in epoch_gauges.iter() {
-            let gauge = DaoState::get_gauge(&gauges, epoch_gauge.gauge.clone())?;
+            // gauges gone from the factory keep their stored epoch data, without pool fields
+            let gauge = DaoState::get_gauge(&gauges, epoch_gauge.gauge.clone()).unwrap_or_default();
 
             let bribes: Vec<BribeInfo> = bribes
                 .clone()
@@ -568,8 +1240,8 @@ impl Core {
 
             gauge_infos.push(GaugeInfo {
                 gauge_pk: epoch_gauge.gauge.clone(),
-                pool_pk: gauge.amm_pool.to_string(),
-                quarry_pk: gauge.quarry.to_string(),
+                pool_pk: gauge.amm_pool,
+                quarry_pk: gauge.quarry,
                 voting_power: epoch_gauge.total_power.parse::<u64>()?,
                 token_a_mint: gauge.token_a_mint,
                 token_b_mint: gauge.token_b_mint,
@@ -582,3 +1254,249 @@ impl Core {
         Ok(gauge_infos)
     }
 }
+
+#[cfg(test)]
+mod test_core {
+    use super::*;
+    use crate::account_source::MemoryAccountSource;
+    use crate::metrics::init_metrics;
+    use crate::signer::KeypairSigner;
+    use crate::state::{init_epoch_infos, init_state};
+    use anchor_client::solana_sdk::signer::keypair::Keypair;
+    use anchor_client::solana_sdk::sysvar::clock::Clock;
+    use sqlx::postgres::PgPoolOptions;
+
+    const BASE: &str = "ba1AznDonanrFY2Ek6jaiMmkccMeU43A5TXU2jB8f4N";
+
+    fn new_core(chain: Arc<MemoryAccountSource>, signer: Option<Arc<dyn CrankSigner>>) -> Core {
+        let state = init_state();
+        Core {
+            // never connected, the tests only run logic which does not touch the database
+            pg_pool: PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/keeper")
+                .unwrap(),
+            base: BASE.to_string(),
+            is_default_factory: true,
+            chain,
+            signer,
+            state: state.clone(),
+            payer_states: vec![state],
+            epochs: init_epoch_infos(),
+            leader: LeaderElection::new(&find_gauge_factory_addr(BASE), Duration::from_secs(30)),
+            metrics: init_metrics(),
+            gauge_discovery: GaugeDiscovery::Incremental,
+            verify_mode: VerifyMode::Off,
+            verify_repair: false,
+            tasks: TaskControl::default(),
+        }
+    }
+
+    fn seed_gauge(chain: &MemoryAccountSource, gauge_factory: Pubkey) -> (Pubkey, Pubkey) {
+        let gauge_pk = Pubkey::new_unique();
+        let quarry_pk = Pubkey::new_unique();
+        chain
+            .set_anchor_account(
+                gauge_pk,
+                &gauge::Gauge {
+                    gauge_factory,
+                    quarry: quarry_pk,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        chain
+            .set_anchor_account(quarry_pk, &quarry::Quarry::default())
+            .unwrap();
+        (gauge_pk, quarry_pk)
+    }
+
+    fn new_signer() -> Arc<dyn CrankSigner> {
+        Arc::new(KeypairSigner::from_secret(&Keypair::new().to_base58_string()).unwrap())
+    }
+
+    #[test]
+    fn test_select_backfill_pairs() {
+        let config = CrawlConfig {
+            gauge_factory: Pubkey::new_unique().to_string(),
+            voting_epoch_up: 10,
+            voting_epoch_down: 0,
+        };
+        let gauges: Vec<String> = (0..MAX_BACKFILL_PAIRS)
+            .map(|_| Pubkey::new_unique().to_string())
+            .collect();
+        // every gauge is missing on chain for the latest epochs, to be checked again
+        let mut checked = HashMap::new();
+        for gauge in gauges.iter() {
+            checked.insert((gauge.clone(), 10), false);
+            checked.insert((gauge.clone(), 9), false);
+            checked.insert((gauge.clone(), 8), true);
+        }
+        let new_gauge = Pubkey::new_unique();
+        let mut all_gauges = gauges.clone();
+        all_gauges.push(new_gauge.to_string());
+
+        let pairs = select_backfill_pairs(&config, 10, &all_gauges, &checked).unwrap();
+        assert_eq!(pairs.len(), MAX_BACKFILL_PAIRS);
+        assert_eq!(pairs[0], (new_gauge, 10));
+        // the never checked pairs fill the batch ahead of the rechecks
+        assert!(pairs.iter().all(|x| x.1 <= 7 || x.0 == new_gauge));
+
+        // once everything is checked, the rechecks of the latest epochs remain
+        for gauge in all_gauges.iter() {
+            for voting_epoch in 1..=10 {
+                checked.entry((gauge.clone(), voting_epoch)).or_insert(true);
+            }
+        }
+        let pairs = select_backfill_pairs(&config, 10, &all_gauges, &checked).unwrap();
+        assert_eq!(pairs.len(), MAX_BACKFILL_PAIRS);
+        assert!(pairs.iter().all(|x| x.1 == 10));
+    }
+
+    #[tokio::test]
+    async fn test_gauge_discovery_filtered_by_factory() {
+        let chain = Arc::new(MemoryAccountSource::new());
+        let core = new_core(chain.clone(), None);
+        let gauge_factory = core.get_gauge_factory_addr();
+        seed_gauge(&chain, gauge_factory);
+        seed_gauge(&chain, gauge_factory);
+        seed_gauge(&chain, Pubkey::new_unique());
+
+        for discovery in [GaugeDiscovery::Full, GaugeDiscovery::Incremental] {
+            let (gauges, quarries) = core.fetch_gauges_and_quarries(discovery).await.unwrap();
+            assert_eq!(gauges.len(), 2);
+            assert_eq!(quarries.len(), 2);
+            assert!(gauges.iter().all(|x| x.1.gauge_factory == gauge_factory));
+        }
+
+        // a gauge created later is picked up by the next incremental scan
+        core.process_monitor_gauge().await.unwrap();
+        let (gauge_pk, quarry_pk) = seed_gauge(&chain, gauge_factory);
+        core.process_monitor_gauge().await.unwrap();
+        assert_eq!(core.get_gauges().len(), 3);
+
+        // known gauges are not fetched again until the next full scan
+        let disabled = gauge::Gauge {
+            gauge_factory,
+            quarry: quarry_pk,
+            is_disabled: true,
+            ..Default::default()
+        };
+        chain.set_anchor_account(gauge_pk, &disabled).unwrap();
+        core.process_monitor_gauge().await.unwrap();
+        let is_disabled = |core: &Core| core.state.lock().unwrap().gauges[&gauge_pk].is_disabled;
+        assert!(!is_disabled(&core));
+        core.state.lock().unwrap().last_full_gauge_scan = None;
+        core.process_monitor_gauge().await.unwrap();
+        assert!(is_disabled(&core));
+
+        // and a closed gauge is dropped with its quarry
+        chain.remove_account(&gauge_pk);
+        core.process_monitor_gauge().await.unwrap();
+        assert_eq!(core.get_gauges().len(), 2);
+        assert_eq!(core.state.lock().unwrap().quarries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_crank_dry_run_only_simulates() {
+        let chain = Arc::new(MemoryAccountSource::new());
+        let signer = new_signer();
+        let core = new_core(chain.clone(), Some(signer.clone()));
+        let gauge_factory = core.get_gauge_factory_addr();
+        let gauge_factory_state = gauge::GaugeFactory {
+            current_voting_epoch: 3,
+            next_epoch_starts_at: 100,
+            ..Default::default()
+        };
+        chain
+            .set_anchor_account(gauge_factory, &gauge_factory_state)
+            .unwrap();
+        chain
+            .set_clock(&Clock {
+                unix_timestamp: 200,
+                ..Clock::default()
+            })
+            .unwrap();
+        chain.set_balance(signer.pubkey(), 1_000_000_000_000);
+
+        // the quarry share differs from the rewards epoch power, so the gauge needs a sync
+        let (gauge_pk, _quarry_pk) = seed_gauge(&chain, gauge_factory);
+        let rewards_epoch = gauge_factory_state.rewards_epoch().unwrap();
+        chain
+            .set_anchor_account(
+                find_epoch_gauge_addr(&gauge_pk, rewards_epoch),
+                &gauge::EpochGauge {
+                    gauge: gauge_pk,
+                    voting_epoch: rewards_epoch,
+                    total_power: 10,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        core.process_monitor_gauge().await.unwrap();
+
+        core.crank(true).await.unwrap();
+        assert_eq!(chain.simulated_transactions().len(), 1);
+        // neither the trigger nor the missing epoch gauge creation is sent
+        assert!(chain.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_crank_skipped_when_not_leader() {
+        let chain = Arc::new(MemoryAccountSource::new());
+        let signer = new_signer();
+        let core = new_core(chain.clone(), Some(signer.clone()));
+        chain
+            .set_anchor_account(
+                core.get_gauge_factory_addr(),
+                &gauge::GaugeFactory {
+                    current_voting_epoch: 3,
+                    next_epoch_starts_at: 100,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        chain
+            .set_clock(&Clock {
+                unix_timestamp: 200,
+                ..Clock::default()
+            })
+            .unwrap();
+        chain.set_balance(signer.pubkey(), 1_000_000_000_000);
+
+        core.crank(false).await.unwrap();
+        assert!(chain.sent_transactions().is_empty());
+        assert!(chain.simulated_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_next_crank_delay() {
+        let chain = Arc::new(MemoryAccountSource::new());
+        let core = new_core(chain.clone(), None);
+        core.state
+            .lock()
+            .unwrap()
+            .gauge_factory
+            .next_epoch_starts_at = 1000;
+        let max_delay = Duration::from_secs(60);
+
+        let cases = [
+            // farther out than max_delay
+            (900, max_delay),
+            // before the boundary
+            (970, Duration::from_secs(30)),
+            // at and after the boundary, until the epoch is triggered
+            (1000, MIN_CRANK_DELAY),
+            (1100, MIN_CRANK_DELAY),
+        ];
+        for (unix_timestamp, expected) in cases {
+            chain
+                .set_clock(&Clock {
+                    unix_timestamp,
+                    ..Clock::default()
+                })
+                .unwrap();
+            let delay = core.next_crank_delay(max_delay).await.unwrap();
+            assert_eq!(delay, expected, "at {}", unix_timestamp);
+        }
+    }
+}
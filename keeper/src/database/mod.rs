@@ -2,50 +2,148 @@ use std::convert::TryInto;
 
 // use anyhow::Result;
 use anchor_lang::prelude::Pubkey;
+use dao_common::pda::find_epoch_gauge_addr;
+use sqlx::PgConnection;
 use sqlx::Pool;
 use sqlx::Postgres;
 use sqlx::QueryBuilder;
 
 #[derive(Debug)]
 pub struct CrawlConfig {
+    pub gauge_factory: String,
     pub voting_epoch_up: i64,
     pub voting_epoch_down: i64,
 }
 
-pub async fn get_voting_epoch_up(pg_pool: &Pool<Postgres>) -> Result<i64, sqlx::Error> {
-    let config: CrawlConfig = sqlx::query_as!(CrawlConfig, r#"SELECT * FROM crawl_config"#)
-        .fetch_one(pg_pool)
-        .await?;
+pub async fn get_crawl_config(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> Result<CrawlConfig, sqlx::Error> {
+    let config: CrawlConfig = sqlx::query_as!(
+        CrawlConfig,
+        r#"SELECT gauge_factory, voting_epoch_up, voting_epoch_down FROM crawl_config WHERE gauge_factory = $1"#,
+        gauge_factory
+    )
+    .fetch_one(pg_pool)
+    .await?;
+    Ok(config)
+}
 
+pub async fn get_voting_epoch_up(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> Result<i64, sqlx::Error> {
+    let config = get_crawl_config(pg_pool, gauge_factory).await?;
     Ok(config.voting_epoch_up)
 }
 
+/// Rows written before the keeper served several gauge factories have no factory,
+/// they belong to the default one
+pub async fn claim_legacy_rows(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    sqlx::query!(
+        "UPDATE crawl_config SET gauge_factory = $1 WHERE gauge_factory = ''",
+        gauge_factory
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE epoch_gauge SET gauge_factory = $1 WHERE gauge_factory = ''",
+        gauge_factory
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE bribe SET gauge_factory = $1 WHERE gauge_factory = ''",
+        gauge_factory
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE crawl_coverage SET gauge_factory = $1 WHERE gauge_factory = ''",
+        gauge_factory
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
 pub async fn init_crawl_config(
     pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
     current_voting_epoch: i64,
 ) -> Result<(), sqlx::Error> {
+    let mut tx = pg_pool.begin().await?;
+
+    // replicas may boot at the same time, only the first one creates the row
+    sqlx::query!("LOCK TABLE crawl_config IN EXCLUSIVE MODE")
+        .execute(&mut tx)
+        .await?;
     sqlx::query!(
         r#"
-            INSERT INTO crawl_config (voting_epoch_up, voting_epoch_down) VALUES ($1, $2)
+            INSERT INTO crawl_config (gauge_factory, voting_epoch_up, voting_epoch_down)
+            SELECT $1, $2, $3
+            WHERE NOT EXISTS (SELECT 1 FROM crawl_config WHERE gauge_factory = $1)
         "#,
+        gauge_factory,
         current_voting_epoch,
         current_voting_epoch - 1,
     )
-    .execute(pg_pool)
+    .execute(&mut tx)
     .await?;
+
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn get_voting_epoch_down(pg_pool: &Pool<Postgres>) -> Result<i64, sqlx::Error> {
-    let config: CrawlConfig = sqlx::query_as!(CrawlConfig, r#"SELECT * FROM crawl_config"#)
-        .fetch_one(pg_pool)
+/// Try to take the session level advisory lock, it is held until the connection is closed
+pub async fn try_advisory_lock(conn: &mut PgConnection, key: i64) -> Result<bool, sqlx::Error> {
+    let locked = sqlx::query_scalar!(r#"SELECT pg_try_advisory_lock($1) as "locked!""#, key)
+        .fetch_one(conn)
         .await?;
+    Ok(locked)
+}
+
+pub async fn ping_connection(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT 1 as one").fetch_one(conn).await?;
+    Ok(())
+}
 
+pub async fn update_crawl_config(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    voting_epoch_up: i64,
+    voting_epoch_down: i64,
+) -> anyhow::Result<()> {
+    let result = sqlx::query!(
+        "UPDATE crawl_config SET voting_epoch_up = $1, voting_epoch_down = $2 WHERE gauge_factory = $3",
+        voting_epoch_up,
+        voting_epoch_down,
+        gauge_factory,
+    )
+    .execute(pg_pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::msg("crawl config not found"));
+    }
+    Ok(())
+}
+
+pub async fn get_voting_epoch_down(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> Result<i64, sqlx::Error> {
+    let config = get_crawl_config(pg_pool, gauge_factory).await?;
     Ok(config.voting_epoch_down)
 }
 
 pub async fn save_epoch_gauges_up(
     pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
     epoch_gauges: &Vec<gauge::EpochGauge>,
     voting_epoch_up: i64,
     should_save_voting_epoch: bool,
@@ -54,38 +152,15 @@ pub async fn save_epoch_gauges_up(
 
     if should_save_voting_epoch {
         sqlx::query!(
-            "UPDATE crawl_config SET voting_epoch_up = $1",
+            "UPDATE crawl_config SET voting_epoch_up = $1 WHERE gauge_factory = $2",
             voting_epoch_up,
+            gauge_factory,
         )
         .execute(&mut tx)
         .await?;
     }
     for epoch_gauge in epoch_gauges.iter() {
-        let (pubkey, _bump) = Pubkey::find_program_address(
-            &[
-                b"EpochGauge".as_ref(),
-                epoch_gauge.gauge.as_ref(),
-                epoch_gauge.voting_epoch.to_le_bytes().as_ref(),
-            ],
-            &gauge::id(),
-        );
-        sqlx::query!(
-            r#"
-                insert into epoch_gauge(address, gauge, voting_epoch, total_power, token_a_fee, token_b_fee) values($1, $2, $3, $4, $5, $6)
-                ON CONFLICT (address) 
-                DO
-                    UPDATE SET total_power = $7
-            "#,
-            pubkey.to_string(),
-            epoch_gauge.gauge.to_string(),
-            epoch_gauge.voting_epoch as i64, // TODO change type
-            epoch_gauge.total_power as i64,// TODO change type
-            epoch_gauge.token_a_fee as i64,// TODO change type
-            epoch_gauge.token_b_fee as i64,// TODO change type
-            epoch_gauge.total_power as i64,// TODO change type
-        )
-        .execute(&mut tx)
-        .await?;
+        insert_epoch_gauge(&mut tx, gauge_factory, epoch_gauge).await?;
     }
 
     // insertions become visible to other connections only after this point
@@ -96,51 +171,126 @@ pub async fn save_epoch_gauges_up(
 
 pub async fn save_epoch_gauges_down(
     pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
     epoch_gauges: &Vec<gauge::EpochGauge>,
     voting_epoch_down: i64,
 ) -> anyhow::Result<()> {
     let mut tx = pg_pool.begin().await?;
 
     sqlx::query!(
-        "UPDATE crawl_config SET voting_epoch_down = $1",
+        "UPDATE crawl_config SET voting_epoch_down = $1 WHERE gauge_factory = $2",
         voting_epoch_down,
+        gauge_factory,
     )
     .execute(&mut tx)
     .await?;
     for epoch_gauge in epoch_gauges.iter() {
-        let (pubkey, _bump) = Pubkey::find_program_address(
-            &[
-                b"EpochGauge".as_ref(),
-                epoch_gauge.gauge.as_ref(),
-                epoch_gauge.voting_epoch.to_le_bytes().as_ref(),
-            ],
-            &gauge::id(),
-        );
+        insert_epoch_gauge(&mut tx, gauge_factory, epoch_gauge).await?;
+    }
+
+    // insertions become visible to other connections only after this point
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn insert_epoch_gauge(
+    conn: &mut PgConnection,
+    gauge_factory: &str,
+    epoch_gauge: &gauge::EpochGauge,
+) -> Result<(), sqlx::Error> {
+    let pubkey = find_epoch_gauge_addr(&epoch_gauge.gauge, epoch_gauge.voting_epoch);
+    sqlx::query!(
+        r#"
+            insert into epoch_gauge(address, gauge, voting_epoch, total_power, token_a_fee, token_b_fee, gauge_factory) values($1, $2, $3, $4, $5, $6, $8)
+            ON CONFLICT (address) 
+            DO
+                UPDATE SET total_power = $7, token_a_fee = $5, token_b_fee = $6
+        "#,
+        pubkey.to_string(),
+        epoch_gauge.gauge.to_string(),
+        epoch_gauge.voting_epoch as i64, // TODO change type
+        epoch_gauge.total_power as i64,// TODO change type
+        epoch_gauge.token_a_fee as i64,// TODO change type
+        epoch_gauge.token_b_fee as i64,// TODO change type
+        epoch_gauge.total_power as i64,// TODO change type
+        gauge_factory,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Save epoch gauges found by the backfill, without moving the crawl pointers
+pub async fn save_epoch_gauges(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    epoch_gauges: &Vec<gauge::EpochGauge>,
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    for epoch_gauge in epoch_gauges.iter() {
+        insert_epoch_gauge(&mut tx, gauge_factory, epoch_gauge).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct CrawlCoverage {
+    pub gauge_factory: String,
+    pub gauge: String,
+    pub voting_epoch: i64,
+    pub found: bool,
+    pub checked_at: i64,
+}
+
+/// Record the (gauge, voting epoch, found) pairs checked on chain
+pub async fn save_crawl_coverage(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    coverage: &[(Pubkey, i64, bool)],
+    checked_at: i64,
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    for (gauge, voting_epoch, found) in coverage.iter() {
         sqlx::query!(
             r#"
-                insert into epoch_gauge(address, gauge, voting_epoch, total_power, token_a_fee, token_b_fee) values($1, $2, $3, $4, $5, $6)
-                ON CONFLICT (address) 
+                INSERT INTO crawl_coverage (gauge_factory, gauge, voting_epoch, found, checked_at) VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (gauge_factory, gauge, voting_epoch)
                 DO
-                    UPDATE SET total_power = $7
+                    UPDATE SET found = $4, checked_at = $5
             "#,
-            pubkey.to_string(),
-            epoch_gauge.gauge.to_string(),
-            epoch_gauge.voting_epoch as i64, // TODO change type
-            epoch_gauge.total_power as i64,// TODO change type
-            epoch_gauge.token_a_fee as i64,// TODO change type
-            epoch_gauge.token_b_fee as i64,// TODO change type
-            epoch_gauge.total_power as i64,// TODO change type
+            gauge_factory,
+            gauge.to_string(),
+            voting_epoch,
+            found,
+            checked_at,
         )
         .execute(&mut tx)
         .await?;
     }
-
-    // insertions become visible to other connections only after this point
     tx.commit().await?;
-
     Ok(())
 }
 
+pub async fn get_crawl_coverage(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    from_epoch: i64,
+    to_epoch: i64,
+) -> anyhow::Result<Vec<CrawlCoverage>> {
+    let coverage: Vec<CrawlCoverage> = sqlx::query_as!(
+        CrawlCoverage,
+        r#"SELECT gauge_factory, gauge, voting_epoch, found, checked_at FROM crawl_coverage WHERE gauge_factory = $1 AND voting_epoch >= $2 AND voting_epoch <= $3"#,
+        gauge_factory,
+        from_epoch,
+        to_epoch,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(coverage)
+}
+
 #[derive(Debug, Clone)]
 pub struct Bribe {
     pub address: String,
@@ -152,21 +302,21 @@ pub struct Bribe {
     pub bribe_rewards_epoch_start: i64,
     pub bribe_rewards_epoch_end: i64,
     pub bribe_index: i64,
+    pub gauge_factory: String,
 }
 
-pub async fn get_max_bribe_index(pg_pool: &Pool<Postgres>) -> anyhow::Result<i64> {
-    match sqlx::query!(r#"SELECT bribe_index, MAX(bribe_index) FROM bribe GROUP BY bribe_index"#)
-        .fetch_optional(pg_pool)
-        .await
+pub async fn get_max_bribe_index(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<i64> {
+    match sqlx::query!(
+        r#"SELECT MAX(bribe_index) as max_bribe_index FROM bribe WHERE gauge_factory = $1"#,
+        gauge_factory
+    )
+    .fetch_one(pg_pool)
+    .await
     {
-        Ok(value) => {
-            if value.is_none() {
-                return Ok(-1);
-            }
-            value
-                .and_then(|res| Some(res.bribe_index))
-                .ok_or(anyhow::Error::msg("cannot get max bribe index"))
-        }
+        Ok(value) => Ok(value.max_bribe_index.unwrap_or(-1)),
         Err(err) => match err {
             sqlx::Error::RowNotFound => Ok(-1),
             err => {
@@ -179,6 +329,7 @@ pub async fn get_max_bribe_index(pg_pool: &Pool<Postgres>) -> anyhow::Result<i64
 
 pub async fn save_bribe(
     pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
     pubkey: Pubkey,
     bribe: &gauge::Bribe,
 ) -> anyhow::Result<()> {
@@ -188,7 +339,10 @@ pub async fn save_bribe(
     let reward_each_epoch = bribe.reward_each_epoch.to_string();
     sqlx::query!(
         r#"
-            INSERT INTO bribe (address, gauge, token_mint, reward_each_epoch, briber, token_account_vault, bribe_rewards_epoch_start, bribe_rewards_epoch_end, bribe_index) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)            
+            INSERT INTO bribe (address, gauge, token_mint, reward_each_epoch, briber, token_account_vault, bribe_rewards_epoch_start, bribe_rewards_epoch_end, bribe_index, gauge_factory) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (address)
+            DO
+                UPDATE SET gauge = $2, token_mint = $3, reward_each_epoch = $4, briber = $5, token_account_vault = $6, bribe_rewards_epoch_start = $7, bribe_rewards_epoch_end = $8, bribe_index = $9
         "#,
         pubkey.to_string(),
         bribe.gauge.to_string(),
@@ -199,13 +353,14 @@ pub async fn save_bribe(
         bribe_rewards_epoch_start,
         bribe_rewards_epoch_end,
         bribe_index,
+        gauge_factory,
     )
     .execute(pg_pool)
     .await?;
     Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EpochGauge {
     pub address: String,
     pub gauge: String,
@@ -213,15 +368,18 @@ pub struct EpochGauge {
     pub token_a_fee: String,
     pub token_b_fee: String,
     pub voting_epoch: i64,
+    pub gauge_factory: String,
 }
 
 pub async fn get_epoch_gauges(
     pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
     epoch: i64,
 ) -> anyhow::Result<Vec<EpochGauge>> {
     let epoch_gauges: Vec<EpochGauge> = sqlx::query_as!(
         EpochGauge,
-        r#"SELECT * FROM epoch_gauge WHERE voting_epoch = $1"#,
+        r#"SELECT * FROM epoch_gauge WHERE gauge_factory = $1 AND voting_epoch = $2"#,
+        gauge_factory,
         epoch
     )
     .fetch_all(pg_pool)
@@ -229,10 +387,15 @@ pub async fn get_epoch_gauges(
     Ok(epoch_gauges)
 }
 
-pub async fn get_bribes(pg_pool: &Pool<Postgres>, epoch: i64) -> anyhow::Result<Vec<Bribe>> {
+pub async fn get_bribes(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    epoch: i64,
+) -> anyhow::Result<Vec<Bribe>> {
     let bribes: Vec<Bribe> = sqlx::query_as!(
         Bribe,
-        r#"SELECT * FROM bribe WHERE bribe_rewards_epoch_start <= $1 and bribe_rewards_epoch_end >= $2"#,
+        r#"SELECT * FROM bribe WHERE gauge_factory = $1 AND bribe_rewards_epoch_start <= $2 and bribe_rewards_epoch_end >= $3"#,
+        gauge_factory,
         epoch,
         epoch,
     )
@@ -240,3 +403,690 @@ pub async fn get_bribes(pg_pool: &Pool<Postgres>, epoch: i64) -> anyhow::Result<
     .await?;
     Ok(bribes)
 }
+
+/// Stored epoch gauges of ended epochs, the current one still changes. A random sample of
+/// `limit` rows, or all of them without a limit
+pub async fn get_ended_epoch_gauges(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    current_voting_epoch: i64,
+    limit: Option<i64>,
+) -> anyhow::Result<Vec<EpochGauge>> {
+    let epoch_gauges: Vec<EpochGauge> = sqlx::query_as!(
+        EpochGauge,
+        r#"SELECT * FROM epoch_gauge WHERE gauge_factory = $1 AND voting_epoch < $2 ORDER BY random() LIMIT $3"#,
+        gauge_factory,
+        current_voting_epoch,
+        limit,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(epoch_gauges)
+}
+
+/// Stored bribes, a random sample of `limit` rows, or all of them without a limit
+pub async fn get_sampled_bribes(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    limit: Option<i64>,
+) -> anyhow::Result<Vec<Bribe>> {
+    let bribes: Vec<Bribe> = sqlx::query_as!(
+        Bribe,
+        r#"SELECT * FROM bribe WHERE gauge_factory = $1 ORDER BY random() LIMIT $2"#,
+        gauge_factory,
+        limit,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(bribes)
+}
+
+pub async fn save_consistency_issue(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    kind: &str,
+    address: &str,
+    field: &str,
+    db_value: &str,
+    chain_value: &str,
+    detected_at: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO consistency_issue (gauge_factory, kind, address, field, db_value, chain_value, detected_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (address, field) WHERE resolved_at IS NULL
+            DO
+                UPDATE SET db_value = $5, chain_value = $6
+        "#,
+        gauge_factory,
+        kind,
+        address,
+        field,
+        db_value,
+        chain_value,
+        detected_at,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+/// Close the open issues of a row, once it matches the chain again or was repaired
+pub async fn resolve_consistency_issues(
+    pg_pool: &Pool<Postgres>,
+    address: &str,
+    resolved_at: i64,
+    repaired: bool,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE consistency_issue SET resolved_at = $2, repaired = $3 WHERE address = $1 AND resolved_at IS NULL",
+        address,
+        resolved_at,
+        repaired,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn repair_epoch_gauge(
+    pg_pool: &Pool<Postgres>,
+    address: &str,
+    epoch_gauge: &gauge::EpochGauge,
+) -> anyhow::Result<()> {
+    let voting_epoch: i64 = epoch_gauge.voting_epoch.into();
+    // amounts are stored as text, like the verifier compares them
+    sqlx::query!(
+        r#"UPDATE epoch_gauge SET gauge = $2, voting_epoch = $3, total_power = $4, token_a_fee = $5, token_b_fee = $6 WHERE address = $1"#,
+        address,
+        epoch_gauge.gauge.to_string(),
+        voting_epoch,
+        epoch_gauge.total_power.to_string(),
+        epoch_gauge.token_a_fee.to_string(),
+        epoch_gauge.token_b_fee.to_string(),
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn repair_bribe(
+    pg_pool: &Pool<Postgres>,
+    address: &str,
+    bribe: &gauge::Bribe,
+) -> anyhow::Result<()> {
+    let bribe_rewards_epoch_start: i64 = bribe.bribe_rewards_epoch_start.into();
+    let bribe_rewards_epoch_end: i64 = bribe.bribe_rewards_epoch_end.into();
+    let bribe_index: i64 = bribe.bribe_index.into();
+    sqlx::query!(
+        r#"
+            UPDATE bribe SET gauge = $2, token_mint = $3, reward_each_epoch = $4, briber = $5, token_account_vault = $6, bribe_rewards_epoch_start = $7, bribe_rewards_epoch_end = $8, bribe_index = $9 WHERE address = $1
+        "#,
+        address,
+        bribe.gauge.to_string(),
+        bribe.token_mint.to_string(),
+        bribe.reward_each_epoch.to_string(),
+        bribe.briber.to_string(),
+        bribe.token_account_vault.to_string(),
+        bribe_rewards_epoch_start,
+        bribe_rewards_epoch_end,
+        bribe_index,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn insert_admin_audit_log(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    action: &str,
+    params: &str,
+    remote_addr: &str,
+    outcome: &str,
+    created_at: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO admin_audit_log (gauge_factory, action, params, remote_addr, outcome, created_at) VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        gauge_factory,
+        action,
+        params,
+        remote_addr,
+        outcome,
+        created_at,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn save_paused_task(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    task: &str,
+    paused_at: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO paused_task (gauge_factory, task, paused_at) VALUES ($1, $2, $3)
+            ON CONFLICT (gauge_factory, task)
+            DO NOTHING
+        "#,
+        gauge_factory,
+        task,
+        paused_at,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_paused_task(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    task: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"DELETE FROM paused_task WHERE gauge_factory = $1 AND task = $2"#,
+        gauge_factory,
+        task,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_paused_tasks(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<Vec<String>> {
+    let tasks: Vec<String> = sqlx::query_scalar!(
+        r#"SELECT task FROM paused_task WHERE gauge_factory = $1 ORDER BY task"#,
+        gauge_factory,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(tasks)
+}
+
+/// Bump the epoch cache generation of the factory, returns the new one
+pub async fn invalidate_epoch_cache(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    invalidated_at: i64,
+) -> anyhow::Result<i64> {
+    let generation = sqlx::query_scalar!(
+        r#"
+            INSERT INTO epoch_cache_invalidation (gauge_factory, generation, invalidated_at) VALUES ($1, 1, $2)
+            ON CONFLICT (gauge_factory)
+            DO
+                UPDATE SET generation = epoch_cache_invalidation.generation + 1, invalidated_at = $2
+            RETURNING generation
+        "#,
+        gauge_factory,
+        invalidated_at,
+    )
+    .fetch_one(pg_pool)
+    .await?;
+    Ok(generation)
+}
+
+/// Epoch cache generation of the factory, 0 until the first invalidation
+pub async fn get_epoch_cache_generation(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<i64> {
+    let generation = sqlx::query_scalar!(
+        r#"SELECT generation FROM epoch_cache_invalidation WHERE gauge_factory = $1"#,
+        gauge_factory,
+    )
+    .fetch_optional(pg_pool)
+    .await?;
+    Ok(generation.unwrap_or(0))
+}
+
+/// Latest stored epoch gauges of a gauge
+pub async fn get_epoch_gauges_of_gauge(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    gauge: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<EpochGauge>> {
+    let epoch_gauges: Vec<EpochGauge> = sqlx::query_as!(
+        EpochGauge,
+        r#"SELECT * FROM epoch_gauge WHERE gauge_factory = $1 AND gauge = $2 ORDER BY voting_epoch DESC LIMIT $3"#,
+        gauge_factory,
+        gauge,
+        limit,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(epoch_gauges)
+}
+
+pub async fn get_epoch_gauge(
+    pg_pool: &Pool<Postgres>,
+    address: &str,
+) -> anyhow::Result<Option<EpochGauge>> {
+    let epoch_gauge: Option<EpochGauge> = sqlx::query_as!(
+        EpochGauge,
+        r#"SELECT * FROM epoch_gauge WHERE address = $1"#,
+        address,
+    )
+    .fetch_optional(pg_pool)
+    .await?;
+    Ok(epoch_gauge)
+}
+
+pub async fn get_bribe_by_index(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    bribe_index: i64,
+) -> anyhow::Result<Option<Bribe>> {
+    let bribe: Option<Bribe> = sqlx::query_as!(
+        Bribe,
+        r#"SELECT * FROM bribe WHERE gauge_factory = $1 AND bribe_index = $2"#,
+        gauge_factory,
+        bribe_index,
+    )
+    .fetch_optional(pg_pool)
+    .await?;
+    Ok(bribe)
+}
+
+#[derive(Debug)]
+pub struct GaugeEvent {
+    pub id: i64,
+    pub gauge_factory: String,
+    pub gauge: String,
+    pub event_type: String,
+    pub created_at: i64,
+    pub slot: i64,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct GaugeSnapshot {
+    pub gauge_factory: String,
+    pub gauge: String,
+    pub status: String,
+    pub quarry: Option<String>,
+    pub token_a_fee_key: Option<String>,
+    pub token_b_fee_key: Option<String>,
+}
+
+/// Last recorded status and keys of every gauge of the factory
+pub async fn get_gauge_snapshots(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<Vec<GaugeSnapshot>> {
+    let snapshots: Vec<GaugeSnapshot> = sqlx::query_as!(
+        GaugeSnapshot,
+        "SELECT * FROM gauge_snapshot WHERE gauge_factory = $1",
+        gauge_factory,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(snapshots)
+}
+
+/// Record the status and keys of gauges without events, the first scan of a factory is the baseline
+pub async fn seed_gauge_snapshots(
+    pg_pool: &Pool<Postgres>,
+    snapshots: &[GaugeSnapshot],
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    for snapshot in snapshots.iter() {
+        sqlx::query!(
+            r#"
+                INSERT INTO gauge_snapshot (gauge_factory, gauge, status, quarry, token_a_fee_key, token_b_fee_key) VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT DO NOTHING
+            "#,
+            snapshot.gauge_factory,
+            snapshot.gauge,
+            snapshot.status,
+            snapshot.quarry,
+            snapshot.token_a_fee_key,
+            snapshot.token_b_fee_key,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Record the keys of a gauge without event, for gauges whose keys were never recorded
+pub async fn save_gauge_snapshot_keys(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    gauge: &str,
+    quarry: &str,
+    token_a_fee_key: &str,
+    token_b_fee_key: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            UPDATE gauge_snapshot SET quarry = $3, token_a_fee_key = $4, token_b_fee_key = $5
+            WHERE gauge_factory = $1 AND gauge = $2
+        "#,
+        gauge_factory,
+        gauge,
+        quarry,
+        token_a_fee_key,
+        token_b_fee_key,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+/// Record a status event and move the recorded status of the gauge with it
+pub async fn insert_gauge_status_event(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    gauge: &str,
+    event_type: &str,
+    slot: i64,
+    created_at: i64,
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    sqlx::query!(
+        r#"
+            INSERT INTO gauge_event (gauge_factory, gauge, event_type, slot, created_at) VALUES ($1, $2, $3, $4, $5)
+        "#,
+        gauge_factory,
+        gauge,
+        event_type,
+        slot,
+        created_at,
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        r#"
+            INSERT INTO gauge_snapshot (gauge_factory, gauge, status) VALUES ($1, $2, $3)
+            ON CONFLICT (gauge_factory, gauge) DO UPDATE SET status = $3
+        "#,
+        gauge_factory,
+        gauge,
+        event_type,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Record a key change event and move the recorded key of the gauge with it, so a change
+/// is compared with the recorded key until its event is stored
+pub async fn insert_gauge_key_event(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    gauge: &str,
+    event_type: &str,
+    old_value: &str,
+    new_value: &str,
+    slot: i64,
+    created_at: i64,
+) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    sqlx::query!(
+        r#"
+            INSERT INTO gauge_event (gauge_factory, gauge, event_type, old_value, new_value, slot, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        gauge_factory,
+        gauge,
+        event_type,
+        old_value,
+        new_value,
+        slot,
+        created_at,
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        r#"
+            UPDATE gauge_snapshot SET
+                quarry = CASE WHEN $3 = 'quarry_changed' THEN $4 ELSE quarry END,
+                token_a_fee_key = CASE WHEN $3 = 'token_a_fee_key_changed' THEN $4 ELSE token_a_fee_key END,
+                token_b_fee_key = CASE WHEN $3 = 'token_b_fee_key_changed' THEN $4 ELSE token_b_fee_key END
+            WHERE gauge_factory = $1 AND gauge = $2
+        "#,
+        gauge_factory,
+        gauge,
+        event_type,
+        new_value,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Events of the factory since a timestamp and after an event id, oldest first, optionally of a single type or gauge
+pub async fn get_gauge_events(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    event_type: Option<String>,
+    gauge: Option<String>,
+    since: i64,
+    after_id: i64,
+    limit: i64,
+) -> anyhow::Result<Vec<GaugeEvent>> {
+    let events: Vec<GaugeEvent> = sqlx::query_as!(
+        GaugeEvent,
+        r#"
+            SELECT * FROM gauge_event
+            WHERE gauge_factory = $1 AND ($2::VARCHAR IS NULL OR event_type = $2) AND ($3::VARCHAR IS NULL OR gauge = $3) AND created_at >= $4 AND id > $5
+            ORDER BY id LIMIT $6
+        "#,
+        gauge_factory,
+        event_type,
+        gauge,
+        since,
+        after_id,
+        limit,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(events)
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub address: String,
+    pub coingecko_id: String,
+    pub decimals: i32,
+    pub symbol: String,
+    pub name: String,
+}
+
+pub async fn get_tokens(pg_pool: &Pool<Postgres>) -> anyhow::Result<Vec<Token>> {
+    let tokens: Vec<Token> = sqlx::query_as!(Token, r#"SELECT * FROM token"#)
+        .fetch_all(pg_pool)
+        .await?;
+    Ok(tokens)
+}
+
+pub async fn get_token(pg_pool: &Pool<Postgres>, address: &str) -> anyhow::Result<Option<Token>> {
+    let token: Option<Token> =
+        sqlx::query_as!(Token, r#"SELECT * FROM token WHERE address = $1"#, address,)
+            .fetch_optional(pg_pool)
+            .await?;
+    Ok(token)
+}
+
+/// Insert or refresh a discovered token, the coingecko id is set by hand and kept.
+/// The symbol and name are kept when the mint has no metadata
+pub async fn upsert_token(
+    pg_pool: &Pool<Postgres>,
+    address: &str,
+    decimals: i32,
+    symbol: Option<String>,
+    name: Option<String>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO token (address, coingecko_id, decimals, symbol, name) VALUES ($1, '', $2, COALESCE($3, ''), COALESCE($4, ''))
+            ON CONFLICT (address)
+            DO
+                UPDATE SET decimals = $2, symbol = COALESCE($3, token.symbol), name = COALESCE($4, token.name)
+        "#,
+        address,
+        decimals,
+        symbol,
+        name,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+/// Mints of the bribes of the factory
+pub async fn get_bribe_token_mints(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<Vec<String>> {
+    let mints: Vec<String> = sqlx::query_scalar!(
+        r#"SELECT DISTINCT token_mint FROM bribe WHERE gauge_factory = $1"#,
+        gauge_factory,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(mints)
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenPrice {
+    pub gauge_factory: String,
+    pub voting_epoch: i64,
+    pub token_mint: String,
+    pub price_usd: f64,
+    pub updated_at: i64,
+}
+
+pub async fn save_token_price(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    voting_epoch: i64,
+    token_mint: &str,
+    price_usd: f64,
+    updated_at: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO token_price (gauge_factory, voting_epoch, token_mint, price_usd, updated_at) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (gauge_factory, voting_epoch, token_mint)
+            DO
+                UPDATE SET price_usd = $4, updated_at = $5
+        "#,
+        gauge_factory,
+        voting_epoch,
+        token_mint,
+        price_usd,
+        updated_at,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+/// Price snapshots of the epochs
+pub async fn get_token_prices(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    from_epoch: i64,
+    to_epoch: i64,
+) -> anyhow::Result<Vec<TokenPrice>> {
+    let prices: Vec<TokenPrice> = sqlx::query_as!(
+        TokenPrice,
+        r#"SELECT * FROM token_price WHERE gauge_factory = $1 AND voting_epoch >= $2 AND voting_epoch <= $3"#,
+        gauge_factory,
+        from_epoch,
+        to_epoch,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(prices)
+}
+
+/// Latest price snapshot of every token
+pub async fn get_latest_token_prices(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<Vec<TokenPrice>> {
+    let prices: Vec<TokenPrice> = sqlx::query_as!(
+        TokenPrice,
+        r#"SELECT DISTINCT ON (token_mint) * FROM token_price WHERE gauge_factory = $1 ORDER BY token_mint, voting_epoch DESC"#,
+        gauge_factory,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(prices)
+}
+
+pub async fn get_bribes_of_gauge(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    gauge: &str,
+) -> anyhow::Result<Vec<Bribe>> {
+    let bribes: Vec<Bribe> = sqlx::query_as!(
+        Bribe,
+        r#"SELECT * FROM bribe WHERE gauge_factory = $1 AND gauge = $2"#,
+        gauge_factory,
+        gauge,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(bribes)
+}
+
+#[derive(Debug, Clone)]
+pub struct EpochTransition {
+    pub gauge_factory: String,
+    pub voting_epoch: i64,
+    pub signature: String,
+    pub started_at: i64,
+}
+
+pub async fn save_epoch_transition(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+    voting_epoch: i64,
+    signature: &str,
+    started_at: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO epoch_transition (gauge_factory, voting_epoch, signature, started_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (gauge_factory, voting_epoch)
+            DO
+                UPDATE SET signature = $3, started_at = $4
+        "#,
+        gauge_factory,
+        voting_epoch,
+        signature,
+        started_at,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_epoch_transitions(
+    pg_pool: &Pool<Postgres>,
+    gauge_factory: &str,
+) -> anyhow::Result<Vec<EpochTransition>> {
+    let transitions: Vec<EpochTransition> = sqlx::query_as!(
+        EpochTransition,
+        r#"SELECT * FROM epoch_transition WHERE gauge_factory = $1 ORDER BY voting_epoch"#,
+        gauge_factory,
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(transitions)
+}
@@ -0,0 +1,121 @@
+//! Leader election between keeper replicas, backed by postgres advisory locks
+
+use crate::database::{ping_connection, try_advisory_lock};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use sqlx::pool::PoolConnection;
+use sqlx::Pool;
+use sqlx::Postgres;
+use std::convert::TryInto;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+/// Shortest lease, it is renewed every third of it so shorter ones would renew in a hot loop
+pub const MIN_LEASE_SECONDS: u64 = 3;
+
+/// Parse --leader-lease-seconds, refusing leases too short to be renewed in time
+pub fn parse_lease_seconds(arg: &str) -> Result<u64, String> {
+    let seconds = arg
+        .parse::<u64>()
+        .map_err(|err| format!("invalid lease {}: {}", arg, err))?;
+    if seconds < MIN_LEASE_SECONDS {
+        return Err(format!(
+            "lease of {} seconds is too short, at least {} seconds are required",
+            seconds, MIN_LEASE_SECONDS
+        ));
+    }
+    Ok(seconds)
+}
+
+/// Only the replica holding the advisory lock is allowed to crank and to write crawl_config.
+/// The lock lives as long as the dedicated connection holding it, so a crashed or partitioned
+/// leader releases it and another replica takes over on its next renewal tick.
+pub struct LeaderElection {
+    lock_key: i64,
+    lease_duration: Duration,
+    /// Connection holding the advisory lock, only set while this replica is the leader
+    conn: tokio::sync::Mutex<Option<PoolConnection<Postgres>>>,
+    /// Leadership is only trusted until the lease expires, it must be renewed before that
+    lease_expires_at: Mutex<Option<Instant>>,
+}
+
+impl LeaderElection {
+    pub fn new(gauge_factory: &Pubkey, lease_duration: Duration) -> Self {
+        LeaderElection {
+            lock_key: lock_key_for(gauge_factory),
+            lease_duration,
+            conn: tokio::sync::Mutex::new(None),
+            lease_expires_at: Mutex::new(None),
+        }
+    }
+
+    pub fn lock_key(&self) -> i64 {
+        self.lock_key
+    }
+
+    /// Interval the election task should run at, so the lease is renewed well before it expires
+    pub fn renew_interval(&self) -> Duration {
+        self.lease_duration / 3
+    }
+
+    pub fn is_leader(&self) -> bool {
+        match *self.lease_expires_at.lock().unwrap() {
+            Some(expires_at) => Instant::now() < expires_at,
+            None => false,
+        }
+    }
+
+    /// Renew the lease if we are the leader, otherwise try to acquire the lock.
+    /// Returns whether this replica is the leader after the call.
+    pub async fn renew(&self, pg_pool: &Pool<Postgres>) -> Result<bool> {
+        let mut conn = self.conn.lock().await;
+
+        if let Some(held) = conn.as_mut() {
+            let renewed = timeout(self.lease_duration / 2, ping_connection(held)).await;
+            match renewed {
+                Ok(Ok(())) => {
+                    self.extend_lease();
+                    return Ok(true);
+                }
+                _ => {
+                    println!(
+                        "leader lease for lock {} lost, stepping down",
+                        self.lock_key
+                    );
+                    self.clear_lease();
+                    // detach so the connection is closed instead of going back to the pool,
+                    // postgres releases the advisory lock with the session
+                    if let Some(held) = conn.take() {
+                        drop(held.detach());
+                    }
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut candidate = pg_pool.acquire().await?;
+        if try_advisory_lock(&mut candidate, self.lock_key).await? {
+            println!("acquired leader lock {}", self.lock_key);
+            *conn = Some(candidate);
+            self.extend_lease();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn extend_lease(&self) {
+        *self.lease_expires_at.lock().unwrap() = Some(Instant::now() + self.lease_duration);
+    }
+
+    fn clear_lease(&self) {
+        *self.lease_expires_at.lock().unwrap() = None;
+    }
+}
+
+/// Advisory locks are keyed by a bigint, derive it from the gauge factory address
+/// so replicas serving the same factory compete for the same lock
+pub fn lock_key_for(gauge_factory: &Pubkey) -> i64 {
+    i64::from_le_bytes(gauge_factory.to_bytes()[..8].try_into().unwrap())
+}
@@ -1,18 +1,42 @@
-// pub mod client_pool;
+pub mod account_source;
+pub mod admin;
+pub mod analytics;
 pub mod anchor_adapter;
+pub mod client_pool;
+pub mod cluster;
+pub mod commands;
 pub mod core;
 pub mod database;
+pub mod leader;
+pub mod metrics;
+pub mod price;
+pub mod rate_limit;
+pub mod registry;
 pub mod router;
+pub mod signer;
 pub mod state;
 pub mod sync_gauge;
+pub mod timeline;
+pub mod token_metadata;
 pub mod utils;
+pub mod verifier;
 #[macro_use]
 pub mod macros;
 
-use crate::core::Core;
+use crate::admin::TaskControl;
+use crate::client_pool::{provider_host, RpcPool};
+use crate::cluster::{check_programs_deployed, ClusterProfile};
+use crate::commands::InspectKind;
+use crate::core::{find_gauge_factory_addr, Core, GaugeDiscovery};
+use crate::leader::{parse_lease_seconds, LeaderElection};
+use crate::metrics::init_metrics;
+use crate::rate_limit::{current_task, parse_key_value, RateLimitConfig, RPC_TASK};
+use crate::registry::FactoryRegistry;
+use crate::signer::{load_signer, validate_signer, CrankSigner};
 use crate::state::init_state;
 use crate::utils::create_pg_pool;
-use clap::Parser;
+use crate::verifier::VerifyMode;
+use clap::{Parser, Subcommand};
 use hyper::Server;
 use log::info;
 use router::router;
@@ -21,12 +45,26 @@ use sqlx::migrate::Migrator;
 use state::init_epoch_infos;
 use std::result::Result::Ok;
 use std::sync::Arc;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
+use tokio::time::sleep;
 use tokio::time::Duration;
 const MONITOR_GAUGE_FACTORY: u64 = 60 * 1; // 1 minutes
 
 const MONITOR_GAUGE: u64 = 60 * 1; // 1 minutes
 
+const MONITOR_RPC_HEALTH: u64 = 10; // 10 seconds
+
+const BACKFILL_EPOCH_GAUGES: u64 = 60 * 1; // 1 minutes
+
+const VERIFY_CONSISTENCY: u64 = 60 * 10; // 10 minutes
+
+const SNAPSHOT_TOKEN_PRICES: u64 = 60 * 10; // 10 minutes
+
+const DISCOVER_TOKENS: u64 = 60 * 60; // 1 hour
+
+const CRANK_INTERVAL: u64 = 10; // 10 seconds, shortened to hit the epoch boundary
+
 static MIGRATOR: Migrator = sqlx::migrate!();
 
 #[derive(Parser, Debug)]
@@ -46,191 +84,642 @@ pub struct PostgresArgs {
 }
 
 #[derive(Parser, Debug)]
-pub struct Args {
-    /// Base address for gauge factory
+pub struct ClusterArgs {
+    /// Cluster profile, providing the defaults below. Defaults to devnet with the devnet feature, mainnet otherwise
+    #[clap(long, value_enum)]
+    cluster: Option<ClusterProfile>,
+    /// Base address for gauge factory, repeat it to serve several factories. The first one is the default factory.
+    /// Defaults to the factories known on the cluster
     #[clap(long)]
-    base: String,
-    /// Socket address the keeper to bind to. For example: 0.0.0.0:5566
+    base: Vec<String>,
+    /// Solana RPC provider. For example: https://api.mainnet-beta.solana.com. Defaults to the cluster one.
+    /// Repeat it to spread reads over several providers and fail over between them
     #[clap(long)]
-    socket_address: String,
-    #[clap(flatten)]
-    postgres_args: PostgresArgs,
-    /// Solana RPC provider. For example: https://api.mainnet-beta.solana.com
+    provider: Vec<String>,
+    /// Solana websocket provider. For example: wss://api.mainnet-beta.solana.com. Defaults to the cluster one
     #[clap(long)]
-    provider: String,
+    wss_provider: Option<String>,
+    /// RPC credits per second of the provider plan, shared by all requests
+    #[clap(long, default_value_t = 100.0)]
+    rpc_credits_per_second: f64,
+    /// Credit cost of an RPC method, as METHOD=CREDITS. For example: getProgramAccounts=10. Other methods cost 1
+    #[clap(long, value_parser = parse_key_value)]
+    rpc_method_cost: Vec<(String, f64)>,
+    /// Requests per second allowed for an RPC method, as METHOD=RATE. For example: getProgramAccounts=1
+    #[clap(long, value_parser = parse_key_value)]
+    rpc_method_rate: Vec<(String, f64)>,
+    /// RPC credits per minute a background task may consume, as TASK=CREDITS. For example: process_monitor_gauge=600
+    #[clap(long, value_parser = parse_key_value)]
+    rpc_task_budget: Vec<(String, f64)>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SignerArgs {
     /// Keypair, used to do permissionless actions like trigger next epoch
     #[clap(long, default_value_t = String::from(shellexpand::tilde("~/.config/solana/id.json")))]
     keypair_url: String,
+    /// Crank signer, overrides keypair_url. One of file:<path>, base58:<secret>, env:<VAR> or remote:<url>
+    #[clap(long)]
+    signer: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ServeArgs {
+    #[clap(flatten)]
+    cluster_args: ClusterArgs,
+    /// Socket address the keeper to bind to. For example: 0.0.0.0:5566
+    #[clap(long)]
+    socket_address: String,
+    #[clap(flatten)]
+    postgres_args: PostgresArgs,
+    #[clap(flatten)]
+    signer_args: SignerArgs,
     /// should trigger
     #[clap(long, default_value_t = 0)]
     should_crank: u64,
+    /// Lease of the leader lock in seconds, at least 3. Only the leader replica cranks and crawls
+    #[clap(long, default_value_t = 30, value_parser = parse_lease_seconds)]
+    leader_lease_seconds: u64,
+    /// How gauges are discovered every minute: full fetches every gauge with getProgramAccounts,
+    /// incremental scans only the keys and fetches the gauges by key
+    #[clap(long, value_enum, default_value_t = GaugeDiscovery::Incremental)]
+    gauge_discovery: GaugeDiscovery,
+    /// Compare the stored epoch gauges and bribes with the chain every 10 minutes, a random sample or all of them
+    #[clap(long, value_enum, default_value_t = VerifyMode::Sample)]
+    verify_mode: VerifyMode,
+    /// Overwrite the stored rows which differ from the chain with the chain values
+    #[clap(long)]
+    verify_repair: bool,
+    /// Bearer token of the admin routes, they are disabled without it
+    #[clap(long, env = "KEEPER_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Serve the API and run the crawlers, and the crank with --should-crank 1
+    Serve(ServeArgs),
+    /// Run the database migrations and exit
+    Migrate {
+        #[clap(flatten)]
+        postgres_args: PostgresArgs,
+    },
+    /// Crawl the epoch gauges of a range of voting epochs and exit
+    Backfill {
+        #[clap(flatten)]
+        cluster_args: ClusterArgs,
+        #[clap(flatten)]
+        postgres_args: PostgresArgs,
+        /// First voting epoch to crawl
+        #[clap(long)]
+        from: i64,
+        /// Last voting epoch to crawl, inclusive
+        #[clap(long)]
+        to: i64,
+    },
+    /// Run a single crank round and exit
+    CrankOnce {
+        #[clap(flatten)]
+        cluster_args: ClusterArgs,
+        #[clap(flatten)]
+        postgres_args: PostgresArgs,
+        #[clap(flatten)]
+        signer_args: SignerArgs,
+        /// Simulate the transactions without sending them, and do not take the leader lock
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Print the on-chain and stored state of an account of the default factory side by side
+    Inspect {
+        #[clap(flatten)]
+        cluster_args: ClusterArgs,
+        #[clap(flatten)]
+        postgres_args: PostgresArgs,
+        #[clap(value_enum)]
+        kind: InspectKind,
+        /// Gauge address, epoch gauge address or bribe index
+        id: String,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
 }
 
-#[tokio::main(worker_threads = 20)] // TODO figure out why it is blocking in linux
+/// Options of the cores which only matter when serving
+struct CoreOptions {
+    leader_lease_seconds: u64,
+    gauge_discovery: GaugeDiscovery,
+    verify_mode: VerifyMode,
+    verify_repair: bool,
+}
+
+impl Default for CoreOptions {
+    fn default() -> Self {
+        CoreOptions {
+            leader_lease_seconds: 30,
+            gauge_discovery: GaugeDiscovery::Incremental,
+            verify_mode: VerifyMode::Off,
+            verify_repair: false,
+        }
+    }
+}
+
+#[tokio::main]
 async fn main() {
-    let Args {
-        base,
-        socket_address,
+    match Args::parse().command {
+        Command::Serve(args) => serve(args).await,
+        Command::Migrate { postgres_args } => {
+            let pg_pool = create_pg_pool(postgres_args).await.unwrap();
+            MIGRATOR.run(&pg_pool).await.unwrap();
+            println!("migrations applied");
+        }
+        Command::Backfill {
+            cluster_args,
+            postgres_args,
+            from,
+            to,
+        } => {
+            let (_, cores) =
+                build_cores(cluster_args, postgres_args, None, CoreOptions::default()).await;
+            for core in cores {
+                commands::backfill(&core, from, to)
+                    .await
+                    .expect("Backfill failed");
+            }
+        }
+        Command::CrankOnce {
+            cluster_args,
+            postgres_args,
+            signer_args,
+            dry_run,
+        } => {
+            let signer = load_crank_signer(signer_args).await;
+            let (_, cores) = build_cores(
+                cluster_args,
+                postgres_args,
+                Some(signer),
+                CoreOptions::default(),
+            )
+            .await;
+            for core in cores {
+                commands::crank_once(&core, dry_run)
+                    .await
+                    .expect("Crank failed");
+            }
+        }
+        Command::Inspect {
+            cluster_args,
+            postgres_args,
+            kind,
+            id,
+        } => {
+            let (_, cores) =
+                build_cores(cluster_args, postgres_args, None, CoreOptions::default()).await;
+            commands::inspect(&cores[0], kind, &id)
+                .await
+                .expect("Inspect failed");
+        }
+    }
+}
+
+async fn serve(args: ServeArgs) {
+    let ServeArgs {
+        cluster_args,
+        socket_address: _,
         postgres_args,
-        provider,
-        keypair_url,
+        signer_args,
         should_crank,
-    } = Args::parse();
+        leader_lease_seconds,
+        gauge_discovery,
+        verify_mode,
+        verify_repair,
+        admin_token,
+    } = args;
 
-    let pg_pool = create_pg_pool(postgres_args).await.unwrap();
-    MIGRATOR.run(&pg_pool).await.unwrap();
+    // load the crank signer once, and refuse to start with a broken one
+    let signer = if should_crank == 1 {
+        Some(load_crank_signer(signer_args).await)
+    } else {
+        None
+    };
+
+    let (rpc_pool, cores) = build_cores(
+        cluster_args,
+        postgres_args,
+        signer,
+        CoreOptions {
+            leader_lease_seconds,
+            gauge_discovery,
+            verify_mode,
+            verify_repair,
+        },
+    )
+    .await;
+
+    let registry = Arc::new(FactoryRegistry::new(cores));
+    let mut handles = vec![];
+
+    {
+        // score rpc providers by slot lag, shared by all factories
+        let rpc_pool = rpc_pool.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_monitor_rpc_health", async move {
+            let mut interval = interval(Duration::from_secs(MONITOR_RPC_HEALTH));
+            loop {
+                interval.tick().await;
+                info!("process_monitor_rpc_health");
+                rpc_pool.process_monitor_health().await;
+            }
+        }));
+        handles.push(handle);
+    }
 
-    let core = Core {
-        pg_pool,
+    for core in registry.get_cores() {
+        handles.extend(spawn_tasks(core, should_crank == 1));
+    }
+
+    let router = router(registry, admin_token);
+
+    let service = RouterService::new(router).unwrap();
+
+    let addr = ([0, 0, 0, 0], 8080).into();
+
+    let server = Server::bind(&addr).serve(service);
+
+    server.await.unwrap();
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
+
+async fn load_crank_signer(args: SignerArgs) -> Arc<dyn CrankSigner> {
+    let spec = args.signer.unwrap_or(args.keypair_url);
+    let signer = load_signer(&spec).await.expect("Cannot load crank signer");
+    validate_signer(signer.as_ref())
+        .await
+        .expect("Crank signer failed validation");
+    println!("crank signer {}", signer.pubkey());
+    signer
+}
+
+/// Connect to the cluster and the database, run the migrations and initialize a core per gauge factory
+async fn build_cores(
+    cluster_args: ClusterArgs,
+    postgres_args: PostgresArgs,
+    signer: Option<Arc<dyn CrankSigner>>,
+    options: CoreOptions,
+) -> (Arc<RpcPool>, Vec<Arc<Core>>) {
+    let ClusterArgs {
+        cluster,
         base,
         provider,
-        state: init_state(),
-        epochs: init_epoch_infos(),
-        keypair_url,
+        wss_provider,
+        rpc_credits_per_second,
+        rpc_method_cost,
+        rpc_method_rate,
+        rpc_task_budget,
+    } = cluster_args;
+
+    let cluster = cluster.unwrap_or_else(ClusterProfile::default_profile);
+    let provider = if provider.is_empty() {
+        vec![cluster.provider().to_string()]
+    } else {
+        provider
+    };
+    let wss_provider = wss_provider.unwrap_or_else(|| cluster.wss_provider().to_string());
+    let base = if base.is_empty() {
+        cluster.bases()
+    } else {
+        base
     };
+    if base.is_empty() {
+        panic!(
+            "No gauge factory known on {:?}, --base is required",
+            cluster
+        );
+    }
+
+    let metrics = init_metrics();
+    let rate_limit = RateLimitConfig::new(
+        rpc_credits_per_second,
+        rpc_method_cost,
+        rpc_method_rate,
+        rpc_task_budget,
+    );
+    let rpc_pool = Arc::new(RpcPool::new(
+        &provider,
+        wss_provider,
+        cluster.commitment(),
+        rate_limit,
+        metrics.clone(),
+    ));
+    println!(
+        "websocket provider {}",
+        provider_host(rpc_pool.wss_provider())
+    );
+
+    check_programs_deployed(rpc_pool.as_ref())
+        .await
+        .expect("Programs are not deployed on the cluster");
+
+    let pg_pool = create_pg_pool(postgres_args).await.unwrap();
+    MIGRATOR.run(&pg_pool).await.unwrap();
 
-    // init some state
-    core.init().await.unwrap();
+    // the signer cranks every factory, so each one checks the payer against all rollovers
+    let states: Vec<_> = base.iter().map(|_| init_state()).collect();
+    let mut cores = vec![];
+    for (i, base) in base.into_iter().enumerate() {
+        let leader = LeaderElection::new(
+            &find_gauge_factory_addr(&base),
+            Duration::from_secs(options.leader_lease_seconds),
+        );
+
+        let core = Core {
+            pg_pool: pg_pool.clone(),
+            base,
+            is_default_factory: i == 0,
+            leader,
+            metrics: metrics.clone(),
+            chain: rpc_pool.clone(),
+            state: states[i].clone(),
+            payer_states: states.clone(),
+            epochs: init_epoch_infos(),
+            signer: signer.clone(),
+            gauge_discovery: options.gauge_discovery,
+            verify_mode: options.verify_mode,
+            verify_repair: options.verify_repair,
+            tasks: TaskControl::default(),
+        };
+
+        // init some state
+        core.init().await.unwrap();
+        println!(
+            "serving gauge factory {} with base {}",
+            core.get_gauge_factory_addr(),
+            core.base
+        );
+
+        cores.push(Arc::new(core));
+    }
+    (rpc_pool, cores)
+}
 
-    let core: Arc<Core> = Arc::new(core);
+/// Spawn the background tasks of a gauge factory
+fn spawn_tasks(core: Arc<Core>, should_crank: bool) -> Vec<JoinHandle<()>> {
     let mut handles = vec![];
 
+    {
+        // leader election, so only one replica cranks and writes crawl_config
+        let core = core.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_leader_election", async move {
+            let mut interval = interval(core.leader.renew_interval());
+            loop {
+                interval.tick().await;
+                info!("process_leader_election");
+                match core.process_leader_election().await {
+                    Ok(_) => {}
+                    Err(err) => println!("process_leader_election err {}", err),
+                }
+            }
+        }));
+        handles.push(handle);
+    }
+
     {
         // cache gauge factory
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_monitor_gauge_factory", async move {
             let duration = MONITOR_GAUGE_FACTORY;
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_monitor gauge factory");
                 core.process_monitor_gauge_factory().await;
             }
-        });
+        }));
         handles.push(handle);
     }
 
     {
         // cache gauge
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_monitor_gauge", async move {
             let duration = MONITOR_GAUGE;
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_monitor gauge");
                 match core.process_monitor_gauge().await {
                     Ok(_) => {}
                     Err(err) => println!("process_monitor_gauge err {}", err),
                 }
             }
-        });
+        }));
         handles.push(handle);
     }
 
     {
         // crawl epoch up
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_crawl_epoch_up", async move {
             let duration = 10 * 1; // 1 min
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_crawl_epoch_up");
                 match core.process_crawl_epoch_up().await {
                     Ok(_) => {}
                     Err(err) => println!("process_crawl_epoch_up err {}", err),
                 }
             }
-        });
+        }));
         handles.push(handle);
     }
 
     {
         // crawl epoch down
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_crawl_epoch_down", async move {
             let duration = 10 * 1; // 1 min
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_crawl_epoch_down");
                 match core.process_crawl_epoch_down().await {
                     Ok(_) => {}
                     Err(err) => println!("process_crawl_epoch_down err {}", err),
                 }
             }
-        });
+        }));
         handles.push(handle);
     }
 
     {
         // crawl bribe
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_crawl_bribe", async move {
             let duration = 10 * 1; // 1 min
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_crawl_bribe");
                 match core.process_crawl_bribe().await {
                     Ok(_) => {}
                     Err(err) => println!("process_crawl_bribe err {}", err),
                 }
             }
-        });
+        }));
         handles.push(handle);
     }
 
     {
         // crawl bribe
         let core = core.clone();
-        let handle = tokio::spawn(async move {
+        let handle = tokio::spawn(RPC_TASK.scope("process_cache_latest_epoches", async move {
             let duration = 30 * 1; // 1 min
             let mut interval = interval(Duration::from_secs(duration));
             loop {
                 interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
                 info!("process_cache_latest_epoches");
                 match core.process_cache_latest_epoches().await {
                     Ok(_) => {}
                     Err(err) => println!("process_cache_latest_epoches err {}", err),
                 }
             }
-        });
+        }));
+        handles.push(handle);
+    }
+
+    {
+        // backfill the (gauge, epoch) pairs the crawl pointers went past
+        let core = core.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_backfill_epoch_gauges", async move {
+            let mut interval = interval(Duration::from_secs(BACKFILL_EPOCH_GAUGES));
+            loop {
+                interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
+                info!("process_backfill_epoch_gauges");
+                match core.process_backfill_epoch_gauges().await {
+                    Ok(_) => {}
+                    Err(err) => println!("process_backfill_epoch_gauges err {}", err),
+                }
+            }
+        }));
+        handles.push(handle);
+    }
+
+    {
+        // compare the stored rows with the chain
+        let core = core.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_verify_consistency", async move {
+            let mut interval = interval(Duration::from_secs(VERIFY_CONSISTENCY));
+            loop {
+                interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
+                info!("process_verify_consistency");
+                match core.process_verify_consistency().await {
+                    Ok(_) => {}
+                    Err(err) => println!("process_verify_consistency err {}", err),
+                }
+            }
+        }));
+        handles.push(handle);
+    }
+
+    {
+        // usd prices of the tokens for the current voting epoch
+        let core = core.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_snapshot_token_prices", async move {
+            let mut interval = interval(Duration::from_secs(SNAPSHOT_TOKEN_PRICES));
+            loop {
+                interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
+                info!("process_snapshot_token_prices");
+                match core.process_snapshot_token_prices().await {
+                    Ok(_) => {}
+                    Err(err) => println!("process_snapshot_token_prices err {}", err),
+                }
+            }
+        }));
+        handles.push(handle);
+    }
+
+    {
+        // decimals, symbol and name of the mints of the gauges and bribes
+        let core = core.clone();
+        let handle = tokio::spawn(RPC_TASK.scope("process_discover_tokens", async move {
+            let mut interval = interval(Duration::from_secs(DISCOVER_TOKENS));
+            loop {
+                interval.tick().await;
+                if core.is_task_paused(current_task()).await {
+                    continue;
+                }
+                info!("process_discover_tokens");
+                match core.process_discover_tokens().await {
+                    Ok(_) => {}
+                    Err(err) => println!("process_discover_tokens err {}", err),
+                }
+            }
+        }));
         handles.push(handle);
     }
 
-    if should_crank == 1 {
+    if should_crank {
         {
-            // sync gauge
+            // sync gauge, scheduled on the epoch boundary so the rollover happens right after it
             let core: Arc<Core> = core.clone();
-            let handle = tokio::spawn(async move {
-                let duration = 10 * 1; // 1 min
-                let mut interval = interval(Duration::from_secs(duration));
+            let handle = tokio::spawn(RPC_TASK.scope("process_sync_gauge", async move {
+                let max_delay = Duration::from_secs(CRANK_INTERVAL);
                 loop {
-                    interval.tick().await;
+                    if core.is_task_paused(current_task()).await {
+                        sleep(max_delay).await;
+                        continue;
+                    }
                     info!("process_sync_gauge");
                     match core.process_sync_gauge().await {
                         Ok(_) => {}
                         Err(err) => println!("process_sync_gauge err {}", err),
                     }
+                    let delay = match core.next_crank_delay(max_delay).await {
+                        Ok(delay) => delay,
+                        Err(err) => {
+                            println!("next_crank_delay err {}", err);
+                            max_delay
+                        }
+                    };
+                    sleep(delay).await;
                 }
-            });
+            }));
             handles.push(handle);
         }
     }
 
-    let router = router(core);
-
-    let service = RouterService::new(router).unwrap();
-
-    let addr = ([0, 0, 0, 0], 8080).into();
-
-    let server = Server::bind(&addr).serve(service);
-
-    server.await.unwrap();
-
-    for handle in handles {
-        handle.await.unwrap();
-    }
+    handles
 }
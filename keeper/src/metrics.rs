@@ -0,0 +1,100 @@
+//! Metrics exposed in prometheus text format on /metrics
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Gauge => "gauge",
+            MetricKind::Counter => "counter",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MetricValue {
+    kind: MetricKind,
+    help: &'static str,
+    value: f64,
+}
+
+pub struct Metrics {
+    /// keyed by metric name and rendered labels, so series of the same metric are rendered together
+    values: Mutex<BTreeMap<(String, String), MetricValue>>,
+}
+
+pub fn init_metrics() -> Arc<Metrics> {
+    Arc::new(Metrics {
+        values: Mutex::new(BTreeMap::new()),
+    })
+}
+
+impl Metrics {
+    /// Set a gauge to the given value
+    pub fn set(&self, name: &str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+        let mut values = self.values.lock().unwrap();
+        values.insert(
+            (name.to_string(), render_labels(labels)),
+            MetricValue {
+                kind: MetricKind::Gauge,
+                help,
+                value,
+            },
+        );
+    }
+
+    /// Increase a counter by the given amount
+    pub fn inc(&self, name: &str, help: &'static str, labels: &[(&str, &str)], by: f64) {
+        let mut values = self.values.lock().unwrap();
+        let metric = values
+            .entry((name.to_string(), render_labels(labels)))
+            .or_insert(MetricValue {
+                kind: MetricKind::Counter,
+                help,
+                value: 0.0,
+            });
+        metric.value += by;
+    }
+
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        let values = self.values.lock().unwrap();
+        values
+            .get(&(name.to_string(), render_labels(labels)))
+            .map(|x| x.value)
+    }
+
+    pub fn render(&self) -> String {
+        let values = self.values.lock().unwrap();
+        let mut output = String::new();
+        let mut last_name: Option<&str> = None;
+        for ((name, labels), metric) in values.iter() {
+            if last_name != Some(name.as_str()) {
+                let _ = writeln!(output, "# HELP {} {}", name, metric.help);
+                let _ = writeln!(output, "# TYPE {} {}", name, metric.kind.as_str());
+                last_name = Some(name.as_str());
+            }
+            let _ = writeln!(output, "{}{} {}", name, labels, metric.value);
+        }
+        output
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('"', "\\\"")))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
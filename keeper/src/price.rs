@@ -0,0 +1,118 @@
+//! USD prices of the tokens of the token table, from CoinGecko by their coingecko_id
+
+use crate::core::{current_unix_timestamp, Core};
+use crate::database::*;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const COINGECKO_PRICE_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+/// A hung price request fails the snapshot, the next run tries again
+const PRICE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Prices of a CoinGecko answer, `{"solana": {"usd": 20.1}, ...}`.
+/// Ids which were not requested or have no USD price are left out
+pub fn parse_usd_prices(
+    coingecko_ids: &[String],
+    response: HashMap<String, HashMap<String, f64>>,
+) -> HashMap<String, f64> {
+    response
+        .into_iter()
+        .filter(|(id, _)| coingecko_ids.contains(id))
+        .filter_map(|(id, prices)| prices.get("usd").map(|x| (id, *x)))
+        .collect()
+}
+
+/// USD price of every id CoinGecko knows, unknown ids are left out
+pub async fn fetch_usd_prices(coingecko_ids: &[String]) -> Result<HashMap<String, f64>> {
+    if coingecko_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let response: HashMap<String, HashMap<String, f64>> = reqwest::Client::builder()
+        .timeout(PRICE_REQUEST_TIMEOUT)
+        .build()?
+        .get(COINGECKO_PRICE_URL)
+        .query(&[
+            ("ids", coingecko_ids.join(",")),
+            ("vs_currencies", String::from("usd")),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(parse_usd_prices(coingecko_ids, response))
+}
+
+impl Core {
+    /// Record the token prices of the current voting epoch, overwritten until the epoch ends,
+    /// so every epoch keeps the prices of its voting period
+    pub async fn process_snapshot_token_prices(&self) -> Result<()> {
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
+        let current_voting_epoch = {
+            let state = self.state.lock().unwrap();
+            if !state.is_gauge_factory_initialized() {
+                return Ok(());
+            }
+            state.gauge_factory.current_voting_epoch
+        };
+
+        let tokens: Vec<Token> = get_tokens(&self.pg_pool)
+            .await?
+            .into_iter()
+            .filter(|x| !x.coingecko_id.is_empty())
+            .collect();
+        let coingecko_ids: Vec<String> = tokens.iter().map(|x| x.coingecko_id.clone()).collect();
+        let prices = fetch_usd_prices(&coingecko_ids).await?;
+
+        let gauge_factory_key = self.get_gauge_factory_key();
+        let updated_at = current_unix_timestamp();
+        for token in tokens.iter() {
+            let price_usd = match prices.get(&token.coingecko_id) {
+                Some(value) => *value,
+                None => {
+                    println!(
+                        "no price for token {} ({})",
+                        token.address, token.coingecko_id
+                    );
+                    continue;
+                }
+            };
+            save_token_price(
+                &self.pg_pool,
+                &gauge_factory_key,
+                current_voting_epoch.into(),
+                &token.address,
+                price_usd,
+                updated_at,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_price {
+    use super::*;
+
+    #[test]
+    fn test_parse_usd_prices() {
+        let coingecko_ids = vec![
+            String::from("solana"),
+            String::from("usd-coin"),
+            String::from("unknown-token"),
+        ];
+        let response = serde_json::from_str(
+            r#"{"solana": {"usd": 20.1}, "usd-coin": {"eur": 0.9}, "bitcoin": {"usd": 30000.0}}"#,
+        )
+        .unwrap();
+        let prices = parse_usd_prices(&coingecko_ids, response);
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices.get("solana"), Some(&20.1));
+
+        assert!(parse_usd_prices(&coingecko_ids, HashMap::new()).is_empty());
+    }
+}
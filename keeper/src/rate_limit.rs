@@ -0,0 +1,249 @@
+//! Rate limiting and budgeting of RPC requests, so paid providers do not throttle the keeper
+
+use crate::metrics::Metrics;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+tokio::task_local! {
+    /// Name of the background task issuing RPC requests, used for budgets and metrics
+    pub static RPC_TASK: &'static str;
+}
+
+/// Task name of requests issued outside of a background task, like HTTP handlers and startup
+const UNKNOWN_TASK: &str = "other";
+
+/// Credit cost of methods which are heavier than a plain request
+const DEFAULT_METHOD_COSTS: &[(&str, f64)] = &[("getProgramAccounts", 10.0)];
+
+pub fn current_task() -> &'static str {
+    RPC_TASK.try_with(|task| *task).unwrap_or(UNKNOWN_TASK)
+}
+
+/// Parse a `KEY=VALUE` argument
+pub fn parse_key_value(arg: &str) -> Result<(String, f64), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {}", arg))?;
+    let value = value
+        .parse::<f64>()
+        .map_err(|err| format!("invalid value in {}: {}", arg, err))?;
+    Ok((key.to_string(), value))
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, refill_per_second: f64) -> Self {
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_second,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.updated_at = now;
+    }
+
+    /// Time to wait before the cost can be taken. A cost above the capacity is let through
+    /// once the bucket is full, the bucket then goes negative and delays the next requests.
+    pub fn wait_time(&mut self, cost: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let needed = cost.min(self.capacity);
+        if self.tokens >= needed {
+            return Duration::from_secs(0);
+        }
+        if self.refill_per_second <= 0.0 {
+            return Duration::from_secs(1);
+        }
+        Duration::from_secs_f64((needed - self.tokens) / self.refill_per_second)
+    }
+
+    pub fn take(&mut self, cost: f64) {
+        self.tokens -= cost;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Credits per second of the RPC plan, shared by all methods
+    pub credits_per_second: f64,
+    /// Credit cost of a method, 1 when not set
+    pub method_costs: HashMap<String, f64>,
+    /// Requests per second allowed for a method, unlimited when not set
+    pub method_rates: HashMap<String, f64>,
+    /// Credits per minute a background task may consume, unlimited when not set
+    pub task_budgets: HashMap<String, f64>,
+}
+
+impl RateLimitConfig {
+    pub fn new(
+        credits_per_second: f64,
+        method_costs: Vec<(String, f64)>,
+        method_rates: Vec<(String, f64)>,
+        task_budgets: Vec<(String, f64)>,
+    ) -> Self {
+        let mut costs: HashMap<String, f64> = DEFAULT_METHOD_COSTS
+            .iter()
+            .map(|(method, cost)| (method.to_string(), *cost))
+            .collect();
+        costs.extend(method_costs);
+        RateLimitConfig {
+            credits_per_second,
+            method_costs: costs,
+            method_rates: method_rates.into_iter().collect(),
+            task_budgets: task_budgets.into_iter().collect(),
+        }
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    credits: Mutex<TokenBucket>,
+    methods: Mutex<HashMap<String, TokenBucket>>,
+    tasks: Mutex<HashMap<String, TokenBucket>>,
+    metrics: Arc<Metrics>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, metrics: Arc<Metrics>) -> Self {
+        let credits = TokenBucket::new(config.credits_per_second, config.credits_per_second);
+        let methods = config
+            .method_rates
+            .iter()
+            .map(|(method, rate)| (method.clone(), TokenBucket::new(*rate, *rate)))
+            .collect();
+        let tasks = config
+            .task_budgets
+            .iter()
+            .map(|(task, budget)| (task.clone(), TokenBucket::new(*budget, *budget / 60.0)))
+            .collect();
+        RateLimiter {
+            config,
+            credits: Mutex::new(credits),
+            methods: Mutex::new(methods),
+            tasks: Mutex::new(tasks),
+            metrics,
+        }
+    }
+
+    pub fn cost(&self, method: &str) -> f64 {
+        *self.config.method_costs.get(method).unwrap_or(&1.0)
+    }
+
+    /// Take the credits of a request from the plan, method and task buckets,
+    /// or tell how long to wait until all of them have enough
+    fn try_acquire(&self, method: &str, task: &str, cost: f64) -> Duration {
+        let now = Instant::now();
+        let mut credits = self.credits.lock().unwrap();
+        let mut methods = self.methods.lock().unwrap();
+        let mut tasks = self.tasks.lock().unwrap();
+
+        let mut wait = credits.wait_time(cost, now);
+        if let Some(bucket) = methods.get_mut(method) {
+            wait = wait.max(bucket.wait_time(1.0, now));
+        }
+        if let Some(bucket) = tasks.get_mut(task) {
+            wait = wait.max(bucket.wait_time(cost, now));
+        }
+
+        if wait == Duration::from_secs(0) {
+            credits.take(cost);
+            if let Some(bucket) = methods.get_mut(method) {
+                bucket.take(1.0);
+            }
+            if let Some(bucket) = tasks.get_mut(task) {
+                bucket.take(cost);
+            }
+        }
+        wait
+    }
+
+    /// Wait until the request fits in the plan, method and task budgets
+    pub async fn acquire(&self, method: &str) {
+        let task = current_task();
+        let cost = self.cost(method);
+        let started_at = Instant::now();
+        loop {
+            let wait = self.try_acquire(method, task, cost);
+            if wait == Duration::from_secs(0) {
+                break;
+            }
+            sleep(wait).await;
+        }
+
+        let labels = [("method", method), ("task", task)];
+        self.metrics.inc(
+            "keeper_rpc_credits_total",
+            "RPC credits consumed, by method and task",
+            &labels,
+            cost,
+        );
+        let waited = started_at.elapsed().as_secs_f64();
+        if waited > 0.0 {
+            self.metrics.inc(
+                "keeper_rpc_rate_limited_seconds_total",
+                "Time spent waiting for the RPC rate limiter, by method and task",
+                &labels,
+                waited,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_rate_limit {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(10.0, 5.0);
+        bucket.updated_at = now;
+
+        assert_eq!(bucket.wait_time(4.0, now), Duration::from_secs(0));
+        bucket.take(4.0);
+        assert_eq!(bucket.wait_time(6.0, now), Duration::from_secs(0));
+        bucket.take(6.0);
+        // empty, 5 credits per second
+        assert_eq!(bucket.wait_time(5.0, now), Duration::from_secs(1));
+        assert_eq!(
+            bucket.wait_time(5.0, now + Duration::from_secs(1)),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn test_cost_above_capacity_waits_for_full_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 1.0);
+        bucket.updated_at = now;
+
+        assert_eq!(bucket.wait_time(10.0, now), Duration::from_secs(0));
+        bucket.take(10.0);
+        // 8 credits in debt, then 2 more to fill the bucket
+        assert_eq!(bucket.wait_time(10.0, now), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("getProgramAccounts=10"),
+            Ok((String::from("getProgramAccounts"), 10.0))
+        );
+        assert!(parse_key_value("getProgramAccounts").is_err());
+        assert!(parse_key_value("getProgramAccounts=ten").is_err());
+    }
+}
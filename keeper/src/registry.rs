@@ -0,0 +1,39 @@
+//! Gauge factories served by this keeper
+
+use crate::core::Core;
+use crate::state::GaugeFactoryState;
+use std::sync::Arc;
+
+pub struct FactoryRegistry {
+    /// One core per gauge factory, the first one is the default factory
+    cores: Vec<Arc<Core>>,
+}
+
+impl FactoryRegistry {
+    pub fn new(cores: Vec<Arc<Core>>) -> Self {
+        assert!(!cores.is_empty(), "Requires at least one gauge factory");
+        FactoryRegistry { cores }
+    }
+
+    pub fn default_core(&self) -> Arc<Core> {
+        self.cores[0].clone()
+    }
+
+    pub fn get_core(&self, gauge_factory: &str) -> Option<Arc<Core>> {
+        self.cores
+            .iter()
+            .find(|core| core.get_gauge_factory_key() == gauge_factory)
+            .cloned()
+    }
+
+    pub fn get_cores(&self) -> Vec<Arc<Core>> {
+        self.cores.clone()
+    }
+
+    pub fn get_gauge_factories(&self) -> Vec<GaugeFactoryState> {
+        self.cores
+            .iter()
+            .map(|core| core.get_gauge_factory())
+            .collect()
+    }
+}
@@ -1,28 +1,104 @@
+use crate::admin::{CrawlConfigRequest, RangeRequest};
 use crate::core::Core;
+use crate::registry::FactoryRegistry;
+use crate::state::GaugeEventType;
 
 use hyper::{Body, Request, Response, StatusCode};
 use log::debug;
 use routerify::prelude::*;
-use routerify::{Middleware, RequestInfo, Router};
+use routerify::{Middleware, RequestInfo, Router, RouterBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::convert::Infallible;
+use std::str::FromStr;
 use std::sync::Arc;
 
-pub fn router(core: Arc<Core>) -> Router<Body, Infallible> {
-    Router::builder()
-        .data(core)
+/// Bearer token of the admin routes, they are disabled without it
+struct AdminToken(Option<String>);
+
+pub fn router(
+    registry: Arc<FactoryRegistry>,
+    admin_token: Option<String>,
+) -> Router<Body, Infallible> {
+    let builder = Router::builder()
+        .data(registry)
+        .data(AdminToken(admin_token))
         .middleware(Middleware::pre(logger))
         .get("/version", get_version)
-        .get("/gauge_factory", get_gauge_factory)
-        .get("/gauges", get_gauges)
-        .get("/epoch/:epoch", get_epoch)
-        .get("/latest_epoches", get_latest_epoches)
-        .get("/pools", get_all_pools)
-        .get("/quarries", get_all_quarries)
+        .get("/metrics", get_metrics)
+        .get("/factories", get_factories);
+
+    // routes without a factory are aliases for the default factory
+    let builder = factory_routes(builder, "");
+    let builder = factory_routes(builder, "/factory/:address");
+
+    builder
         .err_handler_with_info(error_handler)
         .build()
         .unwrap()
 }
 
+fn factory_routes(
+    builder: RouterBuilder<Body, Infallible>,
+    prefix: &str,
+) -> RouterBuilder<Body, Infallible> {
+    builder
+        .get(format!("{}/health", prefix), get_health)
+        .get(format!("{}/gauge_factory", prefix), get_gauge_factory)
+        .get(format!("{}/gauges", prefix), get_gauges)
+        .get(format!("{}/epoch/:epoch", prefix), get_epoch)
+        .get(
+            format!("{}/epoch/:epoch/analytics", prefix),
+            get_epoch_vote_analytics,
+        )
+        .get(format!("{}/latest_epoches", prefix), get_latest_epoches)
+        .get(format!("{}/epochs/timeline", prefix), get_epoch_timeline)
+        .get(
+            format!("{}/emissions/forecast", prefix),
+            get_emission_forecast,
+        )
+        .get(format!("{}/pools", prefix), get_all_pools)
+        .get(format!("{}/quarries", prefix), get_all_quarries)
+        .get(format!("{}/events", prefix), get_gauge_events)
+        .get(format!("{}/tokens", prefix), get_tokens)
+        .get(format!("{}/tokens/:mint", prefix), get_token)
+        .get(
+            format!("{}/gauge/:gauge/efficiency", prefix),
+            get_gauge_efficiency,
+        )
+        .get(
+            format!("{}/efficiency/leaderboard", prefix),
+            get_efficiency_leaderboard,
+        )
+        .get(format!("{}/admin/coverage", prefix), get_coverage)
+        .post(format!("{}/admin/crawl_config", prefix), reset_crawl_config)
+        .post(format!("{}/admin/recrawl/epochs", prefix), recrawl_epochs)
+        .post(format!("{}/admin/recrawl/bribes", prefix), recrawl_bribes)
+        .post(
+            format!("{}/admin/cache/invalidate", prefix),
+            invalidate_epoch_cache,
+        )
+        .get(format!("{}/admin/tasks", prefix), get_tasks)
+        .post(format!("{}/admin/tasks/:task/pause", prefix), pause_task)
+        .post(format!("{}/admin/tasks/:task/resume", prefix), resume_task)
+}
+
+/// Core of the factory in the route, routes without a factory are served by the default one
+fn get_core(req: &Request<Body>) -> Option<Arc<Core>> {
+    let registry = req.data::<Arc<FactoryRegistry>>().unwrap();
+    match req.param("address") {
+        Some(address) => registry.get_core(address),
+        None => Some(registry.default_core()),
+    }
+}
+
+fn unknown_factory_response() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from("Unknown gauge factory"))
+        .unwrap()
+}
+
 fn get_response_builder() -> hyper::http::response::Builder {
     Response::builder()
         .header("Access-Control-Allow-Origin", "*")
@@ -41,8 +117,43 @@ async fn get_version(_req: Request<Body>) -> Result<Response<Body>, Infallible>
     }
 }
 
+async fn get_health(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    match serde_json::to_string(&core.get_health()) {
+        Ok(res) => {
+            let builder = get_response_builder();
+            Ok(builder.body(Body::from(res)).unwrap())
+        }
+        Err(_) => Ok(Response::new(Body::from("Cannot encode health"))),
+    }
+}
+
+async fn get_metrics(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    // metrics are shared by all factories and labelled by factory
+    let core = req.data::<Arc<FactoryRegistry>>().unwrap().default_core();
+    let builder = get_response_builder().header("Content-Type", "text/plain; version=0.0.4");
+    Ok(builder.body(Body::from(core.get_metrics())).unwrap())
+}
+
+async fn get_factories(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let registry = req.data::<Arc<FactoryRegistry>>().unwrap();
+    match serde_json::to_string(&registry.get_gauge_factories()) {
+        Ok(res) => {
+            let builder = get_response_builder();
+            Ok(builder.body(Body::from(res)).unwrap())
+        }
+        Err(_) => Ok(Response::new(Body::from("Cannot encode gauge factories"))),
+    }
+}
+
 async fn get_gauge_factory(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
     match serde_json::to_string(&core.get_gauge_factory()) {
         Ok(res) => {
             let builder = get_response_builder();
@@ -53,7 +164,10 @@ async fn get_gauge_factory(req: Request<Body>) -> Result<Response<Body>, Infalli
 }
 
 async fn get_gauges(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
     match serde_json::to_string(&core.get_gauges()) {
         Ok(res) => {
             let builder = get_response_builder();
@@ -64,7 +178,10 @@ async fn get_gauges(req: Request<Body>) -> Result<Response<Body>, Infallible> {
 }
 
 async fn get_epoch(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
 
     let epoch = match parse_epoch(&req) {
         Ok(value) => value,
@@ -85,8 +202,51 @@ async fn get_epoch(req: Request<Body>) -> Result<Response<Body>, Infallible> {
     }
 }
 
+async fn get_epoch_vote_analytics(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    let epoch = match parse_epoch(&req) {
+        Ok(value) => value,
+        Err(_) => return Ok(Response::new(Body::from("Cannot decode epoch"))),
+    };
+
+    match core.get_epoch_vote_analytics(epoch).await {
+        Ok(analytics) => Ok(json_response(&analytics)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get epoch analytics"))),
+    }
+}
+
+async fn get_epoch_timeline(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+
+    match core.get_epoch_timeline().await {
+        Ok(timeline) => Ok(json_response(&timeline)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get epoch timeline"))),
+    }
+}
+
+async fn get_emission_forecast(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+
+    match core.get_emission_forecast().await {
+        Ok(forecast) => Ok(json_response(&forecast)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get emission forecast"))),
+    }
+}
+
 async fn get_latest_epoches(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
 
     match core.get_latest_epoches().await {
         Ok(info) => match serde_json::to_string(&info) {
@@ -101,7 +261,10 @@ async fn get_latest_epoches(req: Request<Body>) -> Result<Response<Body>, Infall
 }
 
 async fn get_all_pools(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
 
     match core.get_all_pools().await {
         Ok(info) => match serde_json::to_string(&info) {
@@ -116,7 +279,10 @@ async fn get_all_pools(req: Request<Body>) -> Result<Response<Body>, Infallible>
 }
 
 async fn get_all_quarries(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let core = req.data::<Arc<Core>>().unwrap();
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
 
     match core.get_all_quarries().await {
         Ok(info) => match serde_json::to_string(&info) {
@@ -130,6 +296,360 @@ async fn get_all_quarries(req: Request<Body>) -> Result<Response<Body>, Infallib
     }
 }
 
+async fn get_gauge_events(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    let event_type = match query_param(&req, "type").map(|x| GaugeEventType::from_str(&x)) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(_)) => return Ok(Response::new(Body::from("Cannot decode type"))),
+        None => None,
+    };
+    let gauge = query_param(&req, "gauge");
+    let since = match query_param(&req, "since").map(|x| x.parse::<i64>()) {
+        Some(Ok(value)) => value,
+        Some(Err(_)) => return Ok(Response::new(Body::from("Cannot decode since"))),
+        None => 0,
+    };
+    let after_id = match query_param(&req, "after_id").map(|x| x.parse::<i64>()) {
+        Some(Ok(value)) => value,
+        Some(Err(_)) => return Ok(Response::new(Body::from("Cannot decode after_id"))),
+        None => 0,
+    };
+
+    match core
+        .get_gauge_events(event_type, gauge, since, after_id)
+        .await
+    {
+        Ok(events) => Ok(json_response(&events)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get gauge events"))),
+    }
+}
+
+async fn get_tokens(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+
+    match core.get_tokens().await {
+        Ok(tokens) => Ok(json_response(&tokens)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get tokens"))),
+    }
+}
+
+async fn get_token(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    let mint = match req.param("mint") {
+        Some(value) => value.clone(),
+        None => return Ok(Response::new(Body::from("Cannot decode mint"))),
+    };
+
+    match core.get_token(&mint).await {
+        Ok(token) => Ok(json_response(&token)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get token"))),
+    }
+}
+
+async fn get_gauge_efficiency(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    let gauge = match req.param("gauge") {
+        Some(value) => value.clone(),
+        None => return Ok(Response::new(Body::from("Cannot decode gauge"))),
+    };
+
+    match core.get_gauge_efficiency_series(&gauge).await {
+        Ok(series) => Ok(json_response(&series)),
+        Err(_) => Ok(Response::new(Body::from("Cannot get gauge efficiency"))),
+    }
+}
+
+async fn get_efficiency_leaderboard(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let core = match get_core(&req) {
+        Some(core) => core,
+        None => return Ok(unknown_factory_response()),
+    };
+    let epoch = match query_param(&req, "epoch").map(|x| x.parse::<u64>()) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(_)) => return Ok(Response::new(Body::from("Cannot decode epoch"))),
+        None => None,
+    };
+
+    match core.get_efficiency_leaderboard(epoch).await {
+        Ok(leaderboard) => Ok(json_response(&leaderboard)),
+        Err(_) => Ok(Response::new(Body::from(
+            "Cannot get efficiency leaderboard",
+        ))),
+    }
+}
+
+/// Value of a query string parameter, the values of the routes are never percent-encoded
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|x| x.split_once('='))
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value.to_string())
+    })
+}
+
+/// Admin routes require `Authorization: Bearer <admin token>`, returns the response to send otherwise
+fn check_admin_token(req: &Request<Body>) -> Option<Response<Body>> {
+    let token = match &req.data::<AdminToken>().unwrap().0 {
+        Some(token) => token,
+        None => {
+            return Some(admin_response(
+                StatusCode::FORBIDDEN,
+                String::from("Admin routes are disabled"),
+            ))
+        }
+    };
+    let authorized = req
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "))
+        .map(|x| constant_time_eq(x.as_bytes(), token.as_bytes()))
+        .unwrap_or(false);
+    if authorized {
+        None
+    } else {
+        Some(admin_response(
+            StatusCode::UNAUTHORIZED,
+            String::from("Unauthorized"),
+        ))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn admin_response(status: StatusCode, message: String) -> Response<Body> {
+    get_response_builder()
+        .status(status)
+        .body(Body::from(message))
+        .unwrap()
+}
+
+fn json_response<T: Serialize>(value: &T) -> Response<Body> {
+    match serde_json::to_string(value) {
+        Ok(res) => get_response_builder().body(Body::from(res)).unwrap(),
+        Err(_) => Response::new(Body::from("Cannot encode response")),
+    }
+}
+
+/// Decode the JSON body, the raw body is returned too for the audit log
+async fn parse_body<T: DeserializeOwned>(req: Request<Body>) -> (String, anyhow::Result<T>) {
+    let bytes = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bytes) => bytes,
+        Err(err) => return (String::new(), Err(err.into())),
+    };
+    let body = String::from_utf8_lossy(&bytes).to_string();
+    (
+        body,
+        serde_json::from_slice(&bytes).map_err(|err| err.into()),
+    )
+}
+
+/// Reject an admin request whose body can not be decoded, the rejection is audited too
+async fn reject_admin_body(
+    core: &Core,
+    action: &str,
+    body: String,
+    remote_addr: &str,
+    what: &str,
+    err: anyhow::Error,
+) -> Response<Body> {
+    let result = Err(anyhow::Error::msg(format!(
+        "Cannot decode {}: {}",
+        what, err
+    )));
+    finish_admin_action(core, action, body, remote_addr, result).await
+}
+
+/// Authorize an admin request, returns its core and remote address
+fn admin_request(req: &Request<Body>) -> Result<(Arc<Core>, String), Response<Body>> {
+    if let Some(response) = check_admin_token(req) {
+        return Err(response);
+    }
+    match get_core(req) {
+        Some(core) => Ok((core, req.remote_addr().to_string())),
+        None => Err(unknown_factory_response()),
+    }
+}
+
+/// Record the action in the audit log and answer with its outcome
+async fn finish_admin_action(
+    core: &Core,
+    action: &str,
+    params: String,
+    remote_addr: &str,
+    result: anyhow::Result<String>,
+) -> Response<Body> {
+    if let Err(err) = core
+        .record_admin_action(action, &params, remote_addr, &result)
+        .await
+    {
+        println!("cannot record admin action {} err {}", action, err);
+    }
+    match result {
+        Ok(message) => admin_response(StatusCode::OK, message),
+        Err(err) => admin_response(StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+async fn reset_crawl_config(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let request: CrawlConfigRequest = match parse_body(req).await {
+        (_, Ok(value)) => value,
+        (body, Err(err)) => {
+            return Ok(reject_admin_body(
+                &core,
+                "reset_crawl_config",
+                body,
+                &remote_addr,
+                "crawl config",
+                err,
+            )
+            .await)
+        }
+    };
+    let params = serde_json::to_string(&request).unwrap_or_default();
+    let result = core.admin_reset_crawl_config(&request).await;
+    Ok(finish_admin_action(&core, "reset_crawl_config", params, &remote_addr, result).await)
+}
+
+async fn recrawl_epochs(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let request: RangeRequest = match parse_body(req).await {
+        (_, Ok(value)) => value,
+        (body, Err(err)) => {
+            return Ok(reject_admin_body(
+                &core,
+                "recrawl_epochs",
+                body,
+                &remote_addr,
+                "epoch range",
+                err,
+            )
+            .await)
+        }
+    };
+    let params = serde_json::to_string(&request).unwrap_or_default();
+    let result = core.admin_recrawl_epochs(&request).await;
+    Ok(finish_admin_action(&core, "recrawl_epochs", params, &remote_addr, result).await)
+}
+
+async fn recrawl_bribes(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let request: RangeRequest = match parse_body(req).await {
+        (_, Ok(value)) => value,
+        (body, Err(err)) => {
+            return Ok(reject_admin_body(
+                &core,
+                "recrawl_bribes",
+                body,
+                &remote_addr,
+                "bribe range",
+                err,
+            )
+            .await)
+        }
+    };
+    let params = serde_json::to_string(&request).unwrap_or_default();
+    let result = core.admin_recrawl_bribes(&request).await;
+    Ok(finish_admin_action(&core, "recrawl_bribes", params, &remote_addr, result).await)
+}
+
+async fn invalidate_epoch_cache(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let result = core.admin_invalidate_epoch_cache().await;
+    Ok(finish_admin_action(
+        &core,
+        "invalidate_epoch_cache",
+        String::new(),
+        &remote_addr,
+        result,
+    )
+    .await)
+}
+
+async fn get_tasks(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, _remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    match core.admin_get_tasks().await {
+        Ok(tasks) => Ok(json_response(&tasks)),
+        Err(err) => Ok(admin_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )),
+    }
+}
+
+async fn pause_task(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let task = req.param("task").cloned().unwrap_or_default();
+    let result = core.admin_pause_task(&task).await;
+    Ok(finish_admin_action(&core, "pause_task", task, &remote_addr, result).await)
+}
+
+async fn resume_task(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+    let task = req.param("task").cloned().unwrap_or_default();
+    let result = core.admin_resume_task(&task).await;
+    Ok(finish_admin_action(&core, "resume_task", task, &remote_addr, result).await)
+}
+
+async fn get_coverage(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (core, _remote_addr) = match admin_request(&req) {
+        Ok(value) => value,
+        Err(response) => return Ok(response),
+    };
+
+    match core.get_crawl_coverage_info().await {
+        Ok(info) => match serde_json::to_string(&info) {
+            Ok(res) => {
+                let builder = get_response_builder();
+                Ok(builder.body(Body::from(res)).unwrap())
+            }
+            Err(_) => Ok(Response::new(Body::from("Cannot encode coverage"))),
+        },
+        Err(_) => Ok(Response::new(Body::from("Cannot get coverage"))),
+    }
+}
+
 fn parse_epoch(req: &Request<Body>) -> anyhow::Result<u64> {
     let epoch = req
         .param("epoch")
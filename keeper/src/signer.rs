@@ -0,0 +1,278 @@
+//! Signers for the crank payer
+
+use anchor_client::solana_sdk::hash::Hash;
+use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::solana_sdk::signature::{read_keypair_file, Signature};
+use anchor_client::solana_sdk::signer::keypair::Keypair;
+use anchor_client::solana_sdk::signer::Signer;
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::prelude::Pubkey;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Message signed at boot to check the signer actually holds the key of its pubkey
+const PROBE_MESSAGE: &[u8] = b"dao-keeper signer probe";
+/// Timeout of a request to the remote signer, a hung signer fails the crank instead of stalling it
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signer of the crank payer, loaded once at startup
+#[async_trait]
+pub trait CrankSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature>;
+}
+
+/// Local keypair, read from a JSON keypair file or from a base58 secret
+pub struct KeypairSigner(Keypair);
+
+impl KeypairSigner {
+    pub fn from_file(path: &str) -> Result<Self> {
+        let keypair = read_keypair_file(path).map_err(|err| {
+            anyhow::Error::msg(format!("cannot read keypair file {}: {}", path, err))
+        })?;
+        Ok(KeypairSigner(keypair))
+    }
+
+    /// Secret is either base58 encoded or a JSON array of bytes, as in a keypair file
+    pub fn from_secret(secret: &str) -> Result<Self> {
+        let secret = secret.trim();
+        let bytes = if secret.starts_with('[') {
+            serde_json::from_str::<Vec<u8>>(secret).context("cannot decode secret bytes")?
+        } else {
+            bs58::decode(secret)
+                .into_vec()
+                .context("cannot decode base58 secret")?
+        };
+        let keypair = Keypair::from_bytes(&bytes)
+            .map_err(|err| anyhow::Error::msg(format!("invalid secret key: {}", err)))?;
+        Ok(KeypairSigner(keypair))
+    }
+}
+
+#[async_trait]
+impl CrankSigner for KeypairSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.0.pubkey()
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        Ok(self.0.sign_message(message))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemotePubkeyResponse {
+    pub pubkey: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSignRequest {
+    /// base58 encoded message
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSignResponse {
+    /// base58 encoded signature
+    pub signature: String,
+}
+
+/// Signer holding the key in another service.
+/// `GET {url}/pubkey` returns the payer pubkey, `POST {url}/sign` signs a message.
+pub struct RemoteSigner {
+    url: String,
+    pubkey: Pubkey,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    pub async fn connect(url: &str) -> Result<Self> {
+        Self::connect_with_timeout(url, REMOTE_SIGNER_TIMEOUT).await
+    }
+
+    async fn connect_with_timeout(url: &str, timeout: Duration) -> Result<Self> {
+        let url = url.trim_end_matches('/').to_string();
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let response: RemotePubkeyResponse = client
+            .get(format!("{}/pubkey", url))
+            .send()
+            .await
+            .with_context(|| format!("cannot reach remote signer {}", url))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("cannot decode remote signer pubkey")?;
+        let pubkey = Pubkey::from_str(&response.pubkey)?;
+        Ok(RemoteSigner {
+            url,
+            pubkey,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl CrankSigner for RemoteSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        let response: RemoteSignResponse = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&RemoteSignRequest {
+                message: bs58::encode(message).into_string(),
+            })
+            .send()
+            .await
+            .context("cannot reach remote signer")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("cannot decode remote signature")?;
+        Ok(Signature::from_str(&response.signature)?)
+    }
+}
+
+/// Load the signer from its spec:
+/// `file:<path>`, `base58:<secret>`, `env:<VAR>` or `remote:<url>`. A bare value is a keypair file path.
+pub async fn load_signer(spec: &str) -> Result<Arc<dyn CrankSigner>> {
+    let signer: Arc<dyn CrankSigner> = if let Some(path) = spec.strip_prefix("file:") {
+        Arc::new(KeypairSigner::from_file(path)?)
+    } else if let Some(secret) = spec.strip_prefix("base58:") {
+        Arc::new(KeypairSigner::from_secret(secret)?)
+    } else if let Some(var) = spec.strip_prefix("env:") {
+        let secret = std::env::var(var).with_context(|| format!("cannot read env {}", var))?;
+        Arc::new(KeypairSigner::from_secret(&secret)?)
+    } else if let Some(url) = spec.strip_prefix("remote:") {
+        Arc::new(RemoteSigner::connect(url).await?)
+    } else {
+        Arc::new(KeypairSigner::from_file(spec)?)
+    };
+    Ok(signer)
+}
+
+/// Sign a probe message and verify it against the signer pubkey
+pub async fn validate_signer(signer: &dyn CrankSigner) -> Result<()> {
+    let signature = signer.sign_message(PROBE_MESSAGE).await?;
+    if !signature.verify(signer.pubkey().as_ref(), PROBE_MESSAGE) {
+        return Err(anyhow::Error::msg(format!(
+            "signer returned an invalid signature for {}",
+            signer.pubkey()
+        )));
+    }
+    Ok(())
+}
+
+/// Build a transaction paid and signed by the crank signer
+pub async fn sign_transaction(
+    signer: &dyn CrankSigner,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+) -> Result<Transaction> {
+    let payer = signer.pubkey();
+    let mut tx = Transaction::new_with_payer(instructions, Some(&payer));
+    if tx.message.header.num_required_signatures != 1 {
+        return Err(anyhow::Error::msg(
+            "crank transactions must only be signed by the payer",
+        ));
+    }
+    tx.message.recent_blockhash = recent_blockhash;
+    tx.signatures[0] = signer.sign_message(&tx.message_data()).await?;
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod test_signer {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+    use std::convert::Infallible;
+
+    async fn stand_in(
+        keypair: Arc<Keypair>,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Infallible> {
+        let body = match (req.method(), req.uri().path()) {
+            (&Method::GET, "/pubkey") => serde_json::to_string(&RemotePubkeyResponse {
+                pubkey: keypair.pubkey().to_string(),
+            })
+            .unwrap(),
+            (&Method::POST, "/sign") => {
+                let bytes = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                let request: RemoteSignRequest = serde_json::from_slice(&bytes).unwrap();
+                let message = bs58::decode(request.message).into_vec().unwrap();
+                serde_json::to_string(&RemoteSignResponse {
+                    signature: keypair.sign_message(&message).to_string(),
+                })
+                .unwrap()
+            }
+            _ => {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NOT_FOUND;
+                return Ok(response);
+            }
+        };
+        Ok(Response::new(Body::from(body)))
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer() {
+        let keypair = Arc::new(Keypair::new());
+        let expected = keypair.pubkey();
+        let make_service = make_service_fn(move |_| {
+            let keypair = keypair.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| stand_in(keypair.clone(), req))) }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let signer = load_signer(&format!("remote:http://{}", addr))
+            .await
+            .unwrap();
+        assert_eq!(signer.pubkey(), expected);
+        validate_signer(signer.as_ref()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_timeout() {
+        // accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = vec![];
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let url = format!("http://{}", addr);
+        let result = RemoteSigner::connect_with_timeout(&url, Duration::from_millis(100)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keypair_signer_from_secret() {
+        let keypair = Keypair::new();
+        let base58 = load_signer(&format!("base58:{}", keypair.to_base58_string()))
+            .await
+            .unwrap();
+        assert_eq!(base58.pubkey(), keypair.pubkey());
+        validate_signer(base58.as_ref()).await.unwrap();
+
+        let json = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+        let from_json = KeypairSigner::from_secret(&json).unwrap();
+        assert_eq!(from_json.pubkey(), keypair.pubkey());
+    }
+
+    #[tokio::test]
+    async fn test_missing_keypair_file_fails() {
+        assert!(load_signer("file:/nonexistent/id.json").await.is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::client_pool::EndpointHealth;
 use anchor_client::solana_sdk::pubkey;
 // use gauge::GaugeFactory;
 use anchor_client::solana_sdk::signature::Signable;
@@ -5,10 +6,11 @@ use anchor_lang::prelude::Pubkey;
 use anchor_lang::solana_program::blake3::Hash;
 use anyhow::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
 
 #[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct GaugeFactoryState {
@@ -21,6 +23,18 @@ pub struct GaugeFactoryState {
     pub current_voting_epoch: u32,
     pub next_epoch_starts_at: u64,
     pub bribe_index: u32,
+    /// Epoch whose votes the quarries are synced with, from GaugeFactory::rewards_epoch.
+    /// None until the program has one, before the first rollover
+    pub rewards_epoch: Option<u32>,
+}
+
+/// Rewarder of the gauge factory, its rate is split between the quarries by rewards share
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct RewarderState {
+    pub pubkey: String,
+    pub annual_rewards_rate: u64,
+    /// Rewards shares of all the quarries of the rewarder
+    pub total_rewards_shares: u64,
 }
 
 #[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -40,22 +54,94 @@ pub struct GaugeState {
     pub amm_type: u64,
 }
 
+impl GaugeState {
+    pub fn new(pubkey: &Pubkey, gauge: &gauge::Gauge) -> Self {
+        GaugeState {
+            pubkey: pubkey.to_string(),
+            quarry: gauge.quarry.to_string(),
+            amm_pool: gauge.amm_pool.to_string(),
+            token_a_mint: gauge.token_a_mint.to_string(),
+            token_b_mint: gauge.token_b_mint.to_string(),
+            token_a_fee_key: gauge.token_a_fee_key.to_string(),
+            token_b_fee_key: gauge.token_b_fee_key.to_string(),
+            is_disabled: gauge.is_disabled,
+            cummulative_token_a_fee: gauge.cummulative_token_a_fee,
+            cummulative_token_b_fee: gauge.cummulative_token_b_fee,
+            cummulative_claimed_token_a_fee: gauge.cummulative_claimed_token_a_fee,
+            cummulative_claimed_token_b_fee: gauge.cummulative_claimed_token_b_fee,
+            amm_type: gauge.amm_type,
+        }
+    }
+}
+
+/// Fee paid for each signature of a crank transaction
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// Rollover cost of one of the factories cranked by the payer
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct FactoryRolloverCost {
+    pub gauge_factory: String,
+    pub num_gauges: u64,
+    pub required_balance: u64,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PayerBalanceState {
+    pub payer: String,
+    pub balance: u64,
+    /// Lamports the next epoch rollover of every factory cranked by the payer needs:
+    /// epoch gauge rents and transaction fees
+    pub required_balance: u64,
+    pub epoch_gauge_rent: u64,
+    /// Enabled gauges of every factory cranked by the payer
+    pub num_gauges: u64,
+    /// The totals by factory
+    pub factories: Vec<FactoryRolloverCost>,
+    pub updated_at: i64,
+}
+
+impl PayerBalanceState {
+    pub fn is_sufficient(&self) -> bool {
+        self.balance >= self.required_balance
+    }
+}
+
+/// Cost of rolling over to the next epoch: one trigger_next_epoch transaction,
+/// then for every gauge a create_epoch_gauge (paying the rent) and a sync_gauge transaction.
+/// Saturates at u64::MAX, no payer balance covers it
+pub fn estimate_rollover_cost(num_gauges: u64, epoch_gauge_rent: u64) -> u64 {
+    let cost_per_gauge = epoch_gauge_rent.saturating_add(2 * LAMPORTS_PER_SIGNATURE);
+    LAMPORTS_PER_SIGNATURE.saturating_add(num_gauges.saturating_mul(cost_per_gauge))
+}
+
 pub struct DaoState {
     pub gauge_factory: GaugeFactoryState,
+    pub rewarder: RewarderState,
     pub gauges: HashMap<Pubkey, GaugeState>,
     pub quarries: HashMap<Pubkey, quarry::Quarry>,
 
     pub pools: HashMap<Pubkey, PoolDynamicInfo>,
     pub quarry_infos: HashMap<Pubkey, QuarryDynamicInfo>,
+
+    pub payer_balance: PayerBalanceState,
+
+    /// Gauges as last scanned, the incremental discovery only fetches the new ones
+    pub scanned_gauges: HashMap<Pubkey, gauge::Gauge>,
+    /// Every known gauge is fetched again by a full scan, from time to time
+    pub last_full_gauge_scan: Option<Instant>,
 }
 
 pub fn init_state() -> Arc<Mutex<DaoState>> {
     let e = DaoState {
         gauge_factory: GaugeFactoryState::default(),
+        rewarder: RewarderState::default(),
         gauges: HashMap::new(),
         quarries: HashMap::new(),
         pools: HashMap::new(),
         quarry_infos: HashMap::new(),
+        payer_balance: PayerBalanceState::default(),
+        scanned_gauges: HashMap::new(),
+        last_full_gauge_scan: None,
     };
     Arc::new(Mutex::new(e))
 }
@@ -64,6 +150,20 @@ impl DaoState {
     pub fn should_trigger_next_epoch(&self, current_node_time: u64) -> bool {
         current_node_time >= self.gauge_factory.next_epoch_starts_at
     }
+    pub fn seconds_until_next_epoch(&self, current_node_time: u64) -> u64 {
+        self.gauge_factory
+            .next_epoch_starts_at
+            .saturating_sub(current_node_time)
+    }
+    /// Cost of the next rollover of the factory, for its enabled gauges
+    pub fn rollover_cost(&self, epoch_gauge_rent: u64) -> FactoryRolloverCost {
+        let num_gauges = self.gauges.values().filter(|x| !x.is_disabled).count() as u64;
+        FactoryRolloverCost {
+            gauge_factory: self.gauge_factory.pubkey.clone(),
+            num_gauges,
+            required_balance: estimate_rollover_cost(num_gauges, epoch_gauge_rent),
+        }
+    }
     pub fn is_gauge_factory_initialized(&self) -> bool {
         self.gauge_factory.pubkey != String::default()
     }
@@ -82,31 +182,59 @@ impl DaoState {
         self.gauge_factory.current_voting_epoch = gauge_factory.current_voting_epoch;
         self.gauge_factory.next_epoch_starts_at = gauge_factory.next_epoch_starts_at;
         self.gauge_factory.bribe_index = gauge_factory.bribe_index;
+        self.gauge_factory.rewards_epoch = gauge_factory.rewards_epoch().ok();
+    }
+
+    pub fn save_rewarder(&mut self, rewarder: &quarry::Rewarder, pubkey: &Pubkey) {
+        self.rewarder.pubkey = pubkey.to_string();
+        self.rewarder.annual_rewards_rate = rewarder.annual_rewards_rate;
+        self.rewarder.total_rewards_shares = rewarder.total_rewards_shares;
     }
 
+    /// Save a scan of the factory. A full scan reconciles the cache: gauges which are gone,
+    /// closed or moved to another factory, are dropped with their quarry and pool.
+    /// A partial scan may miss live gauges, it is only upserted. Returns the dropped gauges
     pub fn save_gauges_and_quarries(
         &mut self,
         gauges: &Vec<(Pubkey, gauge::Gauge)>,
         quarries: &Vec<(Pubkey, quarry::Quarry)>,
+        is_full_scan: bool,
+    ) -> Vec<Pubkey> {
+        if !is_full_scan {
+            self.upsert_gauges_and_quarries(gauges, quarries);
+            return vec![];
+        }
+        let gauge_pks: HashSet<Pubkey> = gauges.iter().map(|x| x.0).collect();
+        let quarry_pks: HashSet<Pubkey> = gauges.iter().map(|x| x.1.quarry).collect();
+        let pool_pks: HashSet<Pubkey> = gauges.iter().map(|x| x.1.amm_pool).collect();
+
+        let removed: Vec<Pubkey> = self
+            .gauges
+            .keys()
+            .filter(|x| !gauge_pks.contains(x))
+            .cloned()
+            .collect();
+        self.gauges.retain(|pubkey, _| gauge_pks.contains(pubkey));
+        self.scanned_gauges
+            .retain(|pubkey, _| gauge_pks.contains(pubkey));
+        self.quarries
+            .retain(|pubkey, _| quarry_pks.contains(pubkey));
+        self.quarry_infos
+            .retain(|pubkey, _| quarry_pks.contains(pubkey));
+        self.pools.retain(|pubkey, _| pool_pks.contains(pubkey));
+
+        self.upsert_gauges_and_quarries(gauges, quarries);
+        removed
+    }
+
+    fn upsert_gauges_and_quarries(
+        &mut self,
+        gauges: &[(Pubkey, gauge::Gauge)],
+        quarries: &[(Pubkey, quarry::Quarry)],
     ) {
         for (pubkey, gauge) in gauges.iter() {
-            let gauge_state = GaugeState {
-                pubkey: pubkey.to_string(),
-                quarry: gauge.quarry.to_string(),
-                amm_pool: gauge.amm_pool.to_string(),
-                token_a_mint: gauge.token_a_mint.to_string(),
-                token_b_mint: gauge.token_b_mint.to_string(),
-                token_a_fee_key: gauge.token_a_fee_key.to_string(),
-                token_b_fee_key: gauge.token_b_fee_key.to_string(),
-                is_disabled: gauge.is_disabled,
-                cummulative_token_a_fee: gauge.cummulative_token_a_fee,
-                cummulative_token_b_fee: gauge.cummulative_token_b_fee,
-                cummulative_claimed_token_a_fee: gauge.cummulative_claimed_token_a_fee,
-                cummulative_claimed_token_b_fee: gauge.cummulative_claimed_token_b_fee,
-                amm_type: gauge.amm_type,
-            };
-
-            self.gauges.insert(*pubkey, gauge_state);
+            self.gauges.insert(*pubkey, GaugeState::new(pubkey, gauge));
+            self.scanned_gauges.insert(*pubkey, gauge.clone());
         }
 
         for (pubkey, quarry) in quarries.iter() {
@@ -135,6 +263,267 @@ impl DaoState {
     }
 }
 
+/// Lifecycle of a gauge in its factory, as recorded in gauge_event
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GaugeEventType {
+    Created,
+    Disabled,
+    Enabled,
+    Removed,
+    QuarryChanged,
+    TokenAFeeKeyChanged,
+    TokenBFeeKeyChanged,
+}
+
+impl GaugeEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GaugeEventType::Created => "created",
+            GaugeEventType::Disabled => "disabled",
+            GaugeEventType::Enabled => "enabled",
+            GaugeEventType::Removed => "removed",
+            GaugeEventType::QuarryChanged => "quarry_changed",
+            GaugeEventType::TokenAFeeKeyChanged => "token_a_fee_key_changed",
+            GaugeEventType::TokenBFeeKeyChanged => "token_b_fee_key_changed",
+        }
+    }
+
+    /// Status events move the recorded status of the gauge, the others are key changes
+    pub fn is_status(&self) -> bool {
+        matches!(
+            self,
+            GaugeEventType::Created
+                | GaugeEventType::Disabled
+                | GaugeEventType::Enabled
+                | GaugeEventType::Removed
+        )
+    }
+}
+
+impl FromStr for GaugeEventType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "created" => Ok(GaugeEventType::Created),
+            "disabled" => Ok(GaugeEventType::Disabled),
+            "enabled" => Ok(GaugeEventType::Enabled),
+            "removed" => Ok(GaugeEventType::Removed),
+            "quarry_changed" => Ok(GaugeEventType::QuarryChanged),
+            "token_a_fee_key_changed" => Ok(GaugeEventType::TokenAFeeKeyChanged),
+            "token_b_fee_key_changed" => Ok(GaugeEventType::TokenBFeeKeyChanged),
+            _ => Err(Error::msg(format!("unknown gauge event type {}", s))),
+        }
+    }
+}
+
+/// A gauge event to record, key changes carry the previous and the new key
+#[derive(PartialEq, Debug, Clone)]
+pub struct GaugeChange {
+    pub gauge: String,
+    pub event_type: GaugeEventType,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl GaugeChange {
+    fn status(gauge: &str, event_type: GaugeEventType) -> Self {
+        GaugeChange {
+            gauge: gauge.to_string(),
+            event_type,
+            old_value: None,
+            new_value: None,
+        }
+    }
+
+    fn key(gauge: &str, event_type: GaugeEventType, old_value: &str, new_value: String) -> Self {
+        GaugeChange {
+            gauge: gauge.to_string(),
+            event_type,
+            old_value: Some(old_value.to_string()),
+            new_value: Some(new_value),
+        }
+    }
+}
+
+/// Status of a scanned gauge, recorded without event by the first scan of a factory
+pub fn baseline_gauge_status(gauge: &gauge::Gauge) -> GaugeEventType {
+    if gauge.is_disabled {
+        GaugeEventType::Disabled
+    } else {
+        GaugeEventType::Enabled
+    }
+}
+
+/// Quarry and fee keys of a gauge, as recorded in gauge_snapshot
+#[derive(PartialEq, Debug, Clone)]
+pub struct GaugeKeys {
+    pub quarry: String,
+    pub token_a_fee_key: String,
+    pub token_b_fee_key: String,
+}
+
+impl GaugeKeys {
+    pub fn new(gauge: &gauge::Gauge) -> Self {
+        GaugeKeys {
+            quarry: gauge.quarry.to_string(),
+            token_a_fee_key: gauge.token_a_fee_key.to_string(),
+            token_b_fee_key: gauge.token_b_fee_key.to_string(),
+        }
+    }
+}
+
+/// Whether a scan holds less than half of the gauges known alive, like an empty answer of a
+/// lagging RPC node. Its missing gauges must not be recorded as removed
+pub fn is_partial_scan(known: usize, scanned: usize) -> bool {
+    known > 0 && scanned.saturating_mul(2) < known
+}
+
+/// Events moving the gauges from their latest recorded status to the scanned gauges.
+/// A gauge is enabled after created or enabled, and gone after removed
+pub fn diff_gauge_events(
+    latest: &HashMap<String, GaugeEventType>,
+    gauges: &[(Pubkey, gauge::Gauge)],
+) -> Vec<GaugeChange> {
+    let mut events = vec![];
+    let mut scanned = HashSet::new();
+    for (pubkey, gauge) in gauges.iter() {
+        let pubkey = pubkey.to_string();
+        match latest.get(&pubkey) {
+            None | Some(GaugeEventType::Removed) => {
+                events.push(GaugeChange::status(&pubkey, GaugeEventType::Created));
+                if gauge.is_disabled {
+                    events.push(GaugeChange::status(&pubkey, GaugeEventType::Disabled));
+                }
+            }
+            Some(GaugeEventType::Disabled) if !gauge.is_disabled => {
+                events.push(GaugeChange::status(&pubkey, GaugeEventType::Enabled));
+            }
+            Some(GaugeEventType::Created) | Some(GaugeEventType::Enabled) if gauge.is_disabled => {
+                events.push(GaugeChange::status(&pubkey, GaugeEventType::Disabled));
+            }
+            _ => {}
+        }
+        scanned.insert(pubkey);
+    }
+
+    let mut removed: Vec<String> = latest
+        .iter()
+        .filter(|(pubkey, event_type)| {
+            **event_type != GaugeEventType::Removed && !scanned.contains(*pubkey)
+        })
+        .map(|(pubkey, _)| pubkey.clone())
+        .collect();
+    removed.sort();
+    events.extend(
+        removed
+            .iter()
+            .map(|x| GaugeChange::status(x, GaugeEventType::Removed)),
+    );
+    events
+}
+
+/// Quarry and fee key changes of the scanned gauges against their recorded keys.
+/// Gauges without recorded keys have no change, their keys are recorded as is
+pub fn diff_gauge_keys(
+    recorded: &HashMap<String, GaugeKeys>,
+    gauges: &[(Pubkey, gauge::Gauge)],
+) -> Vec<GaugeChange> {
+    let mut events = vec![];
+    for (pubkey, gauge) in gauges.iter() {
+        let pubkey = pubkey.to_string();
+        let recorded_keys = match recorded.get(&pubkey) {
+            Some(value) => value,
+            None => continue,
+        };
+        let keys = GaugeKeys::new(gauge);
+        let keys = [
+            (
+                GaugeEventType::QuarryChanged,
+                &recorded_keys.quarry,
+                keys.quarry,
+            ),
+            (
+                GaugeEventType::TokenAFeeKeyChanged,
+                &recorded_keys.token_a_fee_key,
+                keys.token_a_fee_key,
+            ),
+            (
+                GaugeEventType::TokenBFeeKeyChanged,
+                &recorded_keys.token_b_fee_key,
+                keys.token_b_fee_key,
+            ),
+        ];
+        for (event_type, old_value, new_value) in keys.iter() {
+            if *old_value != new_value {
+                events.push(GaugeChange::key(
+                    &pubkey,
+                    *event_type,
+                    old_value,
+                    new_value.clone(),
+                ));
+            }
+        }
+    }
+    events
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct GaugeEventInfo {
+    /// Cursor of the event, pass it as after_id to get the next page
+    pub id: i64,
+    pub gauge: String,
+    pub event_type: GaugeEventType,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub slot: i64,
+    pub created_at: i64,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub address: String,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    /// Empty until set by hand, tokens without it have no price
+    pub coingecko_id: String,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct HealthInfo {
+    pub status: String,
+    pub is_leader: bool,
+    pub rpc_endpoints: Vec<EndpointHealth>,
+    pub should_crank: bool,
+    pub crank_paused: bool,
+    pub payer_balance: Option<PayerBalanceState>,
+    pub paused_tasks: Vec<String>,
+}
+
+/// Crawl coverage of the (gauge, voting epoch) pairs between the crawl pointers
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageInfo {
+    pub voting_epoch_down: i64,
+    pub voting_epoch_up: i64,
+    pub num_gauges: u64,
+    pub num_found: u64,
+    /// Checked, but the epoch gauge does not exist on chain
+    pub num_not_on_chain: u64,
+    /// Never checked, left to the backfill
+    pub num_unchecked: u64,
+    pub epochs: Vec<EpochCoverageInfo>,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EpochCoverageInfo {
+    pub voting_epoch: i64,
+    pub num_found: u64,
+    pub num_not_on_chain: u64,
+    pub unchecked_gauges: Vec<String>,
+}
+
 #[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct EpochGaugeInfoWrapper {
     pub epoch: u64,
@@ -165,17 +554,27 @@ pub struct BribeInfo {
 pub struct EpochInfos {
     pub epochs: HashMap<u64, Vec<GaugeInfo>>,
     pub max_cached: u64,
+    /// Latest invalidation applied to the cache, see the admin epoch cache invalidation
+    pub generation: i64,
 }
 
 pub fn init_epoch_infos() -> Arc<Mutex<EpochInfos>> {
     let e = EpochInfos {
         epochs: HashMap::new(),
         max_cached: 3,
+        generation: 0,
     };
     Arc::new(Mutex::new(e))
 }
 
 impl EpochInfos {
+    /// Drop the cached epochs when the invalidation was not applied yet
+    pub fn invalidate(&mut self, generation: i64) {
+        if generation > self.generation {
+            self.epochs.clear();
+            self.generation = generation;
+        }
+    }
     pub fn clear_old_epochs(&mut self, latest_epoch: u64) {
         let mut epochs = HashMap::new();
         let start_cached_epoch = if latest_epoch > self.max_cached {
@@ -251,3 +650,146 @@ pub struct QuarryInfo {
     /// Rewards shared allocated to this quarry
     pub rewards_share: u64,
 }
+
+#[cfg(test)]
+mod test_state {
+    use super::*;
+
+    fn new_gauge(quarry: Pubkey, is_disabled: bool) -> gauge::Gauge {
+        gauge::Gauge {
+            quarry,
+            is_disabled,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_gauges_and_quarries_drops_gone_gauges() {
+        let state = init_state();
+        let mut state = state.lock().unwrap();
+        let (kept, gone) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (kept_quarry, gone_quarry) = (Pubkey::new_unique(), Pubkey::new_unique());
+        state.save_gauges_and_quarries(
+            &vec![
+                (kept, new_gauge(kept_quarry, false)),
+                (gone, new_gauge(gone_quarry, false)),
+            ],
+            &vec![
+                (kept_quarry, quarry::Quarry::default()),
+                (gone_quarry, quarry::Quarry::default()),
+            ],
+            true,
+        );
+
+        // a partial scan keeps the gauges it misses
+        let removed = state.save_gauges_and_quarries(
+            &vec![(kept, new_gauge(kept_quarry, false))],
+            &vec![(kept_quarry, quarry::Quarry::default())],
+            false,
+        );
+        assert!(removed.is_empty());
+        assert_eq!(state.gauges.len(), 2);
+
+        let removed = state.save_gauges_and_quarries(
+            &vec![(kept, new_gauge(kept_quarry, false))],
+            &vec![(kept_quarry, quarry::Quarry::default())],
+            true,
+        );
+        assert_eq!(removed, vec![gone]);
+        assert_eq!(state.gauges.keys().collect::<Vec<_>>(), vec![&kept]);
+        assert_eq!(
+            state.quarries.keys().collect::<Vec<_>>(),
+            vec![&kept_quarry]
+        );
+    }
+
+    #[test]
+    fn test_diff_gauge_events() {
+        let (created, disabled, enabled, removed, closed) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut latest = HashMap::new();
+        latest.insert(disabled.to_string(), GaugeEventType::Created);
+        latest.insert(enabled.to_string(), GaugeEventType::Disabled);
+        latest.insert(removed.to_string(), GaugeEventType::Removed);
+        latest.insert(closed.to_string(), GaugeEventType::Enabled);
+
+        let quarry = Pubkey::default();
+        let events = diff_gauge_events(
+            &latest,
+            &[
+                (created, new_gauge(quarry, true)),
+                (disabled, new_gauge(quarry, true)),
+                (enabled, new_gauge(quarry, false)),
+                (removed, new_gauge(quarry, false)),
+            ],
+        );
+        let events: Vec<(String, GaugeEventType)> = events
+            .into_iter()
+            .map(|x| (x.gauge, x.event_type))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (created.to_string(), GaugeEventType::Created),
+                (created.to_string(), GaugeEventType::Disabled),
+                (disabled.to_string(), GaugeEventType::Disabled),
+                (enabled.to_string(), GaugeEventType::Enabled),
+                (removed.to_string(), GaugeEventType::Created),
+                (closed.to_string(), GaugeEventType::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_estimate_rollover_cost() {
+        // only the trigger_next_epoch transaction
+        assert_eq!(estimate_rollover_cost(0, 1_000_000), LAMPORTS_PER_SIGNATURE);
+        assert_eq!(estimate_rollover_cost(0, u64::MAX), LAMPORTS_PER_SIGNATURE);
+        assert_eq!(
+            estimate_rollover_cost(2, 1_000_000),
+            LAMPORTS_PER_SIGNATURE + 2 * (1_000_000 + 2 * LAMPORTS_PER_SIGNATURE)
+        );
+        assert_eq!(estimate_rollover_cost(u64::MAX, 1_000_000), u64::MAX);
+        assert_eq!(estimate_rollover_cost(1, u64::MAX), u64::MAX);
+        assert_eq!(estimate_rollover_cost(u64::MAX / 2, 0), u64::MAX);
+    }
+
+    #[test]
+    fn test_is_partial_scan() {
+        assert!(!is_partial_scan(0, 0));
+        assert!(is_partial_scan(4, 0));
+        assert!(is_partial_scan(5, 2));
+        assert!(!is_partial_scan(4, 2));
+        assert!(!is_partial_scan(4, 3));
+    }
+
+    #[test]
+    fn test_diff_gauge_keys() {
+        let gauge_pk = Pubkey::new_unique();
+        let gauge = new_gauge(Pubkey::new_unique(), false);
+        let mut recorded = HashMap::new();
+        assert!(diff_gauge_keys(&recorded, &[(gauge_pk, gauge.clone())]).is_empty());
+
+        recorded.insert(gauge_pk.to_string(), GaugeKeys::new(&gauge));
+        assert!(diff_gauge_keys(&recorded, &[(gauge_pk, gauge.clone())]).is_empty());
+
+        let moved = gauge::Gauge {
+            quarry: Pubkey::new_unique(),
+            ..gauge.clone()
+        };
+        assert_eq!(
+            diff_gauge_keys(&recorded, &[(gauge_pk, moved.clone())]),
+            vec![GaugeChange {
+                gauge: gauge_pk.to_string(),
+                event_type: GaugeEventType::QuarryChanged,
+                old_value: Some(gauge.quarry.to_string()),
+                new_value: Some(moved.quarry.to_string()),
+            }]
+        );
+    }
+}
@@ -1,110 +1,91 @@
-use anchor_client::solana_sdk::signature::read_keypair_file;
+use crate::account_source::AccountSource;
+use crate::core::find_epoch_gauge_addr;
+use crate::signer::{sign_transaction, CrankSigner};
+use anchor_client::solana_sdk::instruction::Instruction;
 use anchor_client::solana_sdk::signature::Signature;
-use anchor_client::solana_sdk::signer::keypair::Keypair;
-use anchor_client::solana_sdk::signer::Signer;
-use anchor_client::solana_sdk::transaction::Transaction;
-use anchor_client::Program;
-use anchor_client::RequestBuilder;
 use anchor_lang::prelude::Pubkey;
 use anchor_lang::solana_program::system_program;
+use anchor_lang::{InstructionData, ToAccountMetas};
 use anyhow::{Ok, Result};
-use std::sync::Arc;
+
+fn gauge_instruction(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: gauge::id(),
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
 
 pub async fn trigger_next_epoch(
-    program: &Program<Arc<Keypair>>,
-    keypair_url: &str,
+    chain: &dyn AccountSource,
+    signer: &dyn CrankSigner,
     gauge_factory: Pubkey,
-) -> Result<()> {
-    let builder = program
-        .request()
-        .accounts(gauge::accounts::TriggerNextEpoch { gauge_factory })
-        .args(gauge::instruction::TriggerNextEpoch {});
+) -> Result<Signature> {
+    let ix = gauge_instruction(
+        gauge::accounts::TriggerNextEpoch { gauge_factory },
+        gauge::instruction::TriggerNextEpoch {},
+    );
 
-    let signature = send_tx(keypair_url, program, &builder)?;
+    let signature = send_tx(signer, chain, &[ix]).await?;
 
     println!("trigger_next_epoch Signature {:?}", signature);
-    Ok(())
+    Ok(signature)
 }
 
-fn send_tx<C: Clone + std::ops::Deref<Target = impl Signer>>(
-    keypair_url: &str,
-    program: &Program<Arc<Keypair>>,
-    builder: &RequestBuilder<C>,
+async fn send_tx(
+    signer: &dyn CrankSigner,
+    chain: &dyn AccountSource,
+    instructions: &[Instruction],
 ) -> Result<Signature> {
-    let payer = read_keypair_file(keypair_url.clone()).expect("Requires a keypair file");
-    let rpc_client = program.rpc();
-    let ixs = builder.instructions()?;
-
-    let latest_blockhash = rpc_client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &builder.instructions()?,
-        Some(&payer.pubkey()),
-        &[&payer],
-        latest_blockhash,
-    );
+    let latest_blockhash = chain.get_latest_blockhash().await?;
+    let tx = sign_transaction(signer, instructions, latest_blockhash).await?;
 
-    let signature = rpc_client.send_and_confirm_transaction(&tx)?;
+    let signature = chain.send_and_confirm_transaction(&tx).await?;
     Ok(signature)
 }
 
-fn simulation_tx<C: Clone + std::ops::Deref<Target = impl Signer>>(
-    keypair_url: &str,
-    program: &Program<Arc<Keypair>>,
-    builder: &RequestBuilder<C>,
+async fn simulation_tx(
+    signer: &dyn CrankSigner,
+    chain: &dyn AccountSource,
+    instructions: &[Instruction],
 ) -> Result<()> {
-    let payer = read_keypair_file(keypair_url.clone()).expect("Requires a keypair file");
-    let rpc_client = program.rpc();
-    let ixs = builder.instructions()?;
-
-    let latest_blockhash = rpc_client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &builder.instructions()?,
-        Some(&payer.pubkey()),
-        &[&payer],
-        latest_blockhash,
-    );
+    let latest_blockhash = chain.get_latest_blockhash().await?;
+    let tx = sign_transaction(signer, instructions, latest_blockhash).await?;
 
-    let simulation = rpc_client.simulate_transaction(&tx)?;
+    let simulation = chain.simulate_transaction(&tx).await?;
     println!("{:?}", simulation);
     Ok(())
 }
 
 pub async fn sync_gauge(
-    program: &Program<Arc<Keypair>>,
-    keypair_url: &str,
+    chain: &dyn AccountSource,
+    signer: &dyn CrankSigner,
     gauge_factory: Pubkey,
     gauge_pk: Pubkey,
     voting_epoch: u32,
     is_simulation: bool,
 ) -> Result<()> {
-    let (epoch_gauge, _bump) = Pubkey::find_program_address(
-        &[
-            b"EpochGauge".as_ref(),
-            gauge_pk.as_ref(),
-            voting_epoch.to_le_bytes().as_ref(),
-        ],
-        &gauge::id(),
-    );
+    let epoch_gauge = find_epoch_gauge_addr(&gauge_pk, voting_epoch);
 
-    let gauge_factory_state: gauge::GaugeFactory = program.account(gauge_factory).await?;
-    let gauge_state: gauge::Gauge = program.account(gauge_pk).await?;
+    let gauge_factory_state: gauge::GaugeFactory = chain.get_anchor_account(&gauge_factory).await?;
+    let gauge_state: gauge::Gauge = chain.get_anchor_account(&gauge_pk).await?;
 
-    let builder = program
-        .request()
-        .accounts(gauge::accounts::SyncGauge {
+    let ix = gauge_instruction(
+        gauge::accounts::SyncGauge {
             gauge_factory,
             gauge: gauge_pk,
             epoch_gauge,
             quarry: gauge_state.quarry,
             rewarder: gauge_factory_state.rewarder,
             quarry_program: quarry::id(),
-        })
-        .args(gauge::instruction::SyncGauge {});
+        },
+        gauge::instruction::SyncGauge {},
+    );
 
     if is_simulation {
-        simulation_tx(keypair_url, program, &builder)?;
+        simulation_tx(signer, chain, &[ix]).await?;
     } else {
-        let signature = send_tx(keypair_url, program, &builder)?;
+        let signature = send_tx(signer, chain, &[ix]).await?;
         println!("sync_gauge {} Signature {:?}", gauge_pk, signature);
     }
 
@@ -112,43 +93,32 @@ pub async fn sync_gauge(
 }
 
 pub async fn create_epoch_gauge(
-    program: &Program<Arc<Keypair>>,
-    keypair_url: &str,
+    chain: &dyn AccountSource,
+    signer: &dyn CrankSigner,
     gauge_factory: Pubkey,
     gauge_pk: Pubkey,
 ) -> Result<()> {
-    let gauge_factory_state: gauge::GaugeFactory = program.account(gauge_factory).await?;
-    let (epoch_gauge, _bump) = Pubkey::find_program_address(
-        &[
-            b"EpochGauge".as_ref(),
-            gauge_pk.as_ref(),
-            gauge_factory_state
-                .current_voting_epoch
-                .to_le_bytes()
-                .as_ref(),
-        ],
-        &gauge::id(),
-    );
+    let gauge_factory_state: gauge::GaugeFactory = chain.get_anchor_account(&gauge_factory).await?;
+    let epoch_gauge = find_epoch_gauge_addr(&gauge_pk, gauge_factory_state.current_voting_epoch);
 
-    let gauge_state: gauge::Gauge = program.account(gauge_pk).await?;
+    let gauge_state: gauge::Gauge = chain.get_anchor_account(&gauge_pk).await?;
 
-    let builder = program
-        .request()
-        .accounts(gauge::accounts::CreateEpochGauge {
+    let ix = gauge_instruction(
+        gauge::accounts::CreateEpochGauge {
             gauge_factory,
             gauge: gauge_pk,
             epoch_gauge,
             amm_pool: gauge_state.amm_pool,
             token_a_fee: gauge_state.token_a_fee_key,
             token_b_fee: gauge_state.token_b_fee_key,
-            payer: program.payer(),
+            payer: signer.pubkey(),
             system_program: system_program::id(),
-        })
-        .args(gauge::instruction::CreateEpochGauge {});
+        },
+        gauge::instruction::CreateEpochGauge {},
+    );
 
-    let signature = send_tx(keypair_url, program, &builder)?;
+    let signature = send_tx(signer, chain, &[ix]).await?;
 
-    // let signature = builder.send().await?;
     println!(
         "create_epoch_gauge gauge: {} epoch: {} Signature: {:?}",
         gauge_pk, gauge_factory_state.current_voting_epoch, signature
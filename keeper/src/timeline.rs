@@ -0,0 +1,126 @@
+//! Start and end of the voting epochs, so clients can map an epoch to dates
+
+use crate::core::{current_unix_timestamp, Core};
+use crate::database::*;
+use crate::state::GaugeFactoryState;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EpochPeriod {
+    pub voting_epoch: u32,
+    pub started_at: u64,
+    /// The next epoch starts once the crank triggers it, which can be later
+    pub ends_at: u64,
+    /// trigger_next_epoch transaction which started the epoch, the start is derived without it
+    pub signature: Option<String>,
+}
+
+#[derive(Default, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EpochTimeline {
+    pub current_voting_epoch: u32,
+    /// None before the first epoch rollover
+    pub current_rewards_epoch: Option<u32>,
+    pub next_epoch_starts_at: u64,
+    pub seconds_until_next_epoch: u64,
+    /// Oldest first, up to the current voting epoch
+    pub epochs: Vec<EpochPeriod>,
+}
+
+/// Epochs from the first one, the current one ends at next_epoch_starts_at. An epoch without a
+/// recorded transition started one epoch duration before the start of the next one
+pub fn compute_epoch_timeline(
+    gauge_factory: &GaugeFactoryState,
+    transitions: &[EpochTransition],
+    now: u64,
+) -> Result<EpochTimeline> {
+    let mut recorded = HashMap::new();
+    for transition in transitions.iter() {
+        recorded.insert(
+            u32::try_from(transition.voting_epoch)?,
+            (
+                u64::try_from(transition.started_at)?,
+                transition.signature.clone(),
+            ),
+        );
+    }
+    let duration = u64::from(gauge_factory.epoch_duration_seconds);
+
+    let mut epochs = vec![];
+    let mut ends_at = gauge_factory.next_epoch_starts_at;
+    for voting_epoch in (1..=gauge_factory.current_voting_epoch).rev() {
+        let (started_at, signature) = match recorded.get(&voting_epoch) {
+            Some((started_at, signature)) => (*started_at, Some(signature.clone())),
+            None => (ends_at.saturating_sub(duration), None),
+        };
+        epochs.push(EpochPeriod {
+            voting_epoch,
+            started_at,
+            ends_at,
+            signature,
+        });
+        ends_at = started_at;
+    }
+    epochs.reverse();
+
+    Ok(EpochTimeline {
+        current_voting_epoch: gauge_factory.current_voting_epoch,
+        current_rewards_epoch: gauge_factory.rewards_epoch,
+        next_epoch_starts_at: gauge_factory.next_epoch_starts_at,
+        seconds_until_next_epoch: gauge_factory.next_epoch_starts_at.saturating_sub(now),
+        epochs,
+    })
+}
+
+impl Core {
+    pub async fn get_epoch_timeline(&self) -> Result<EpochTimeline> {
+        let transitions =
+            get_epoch_transitions(&self.pg_pool, &self.get_gauge_factory_key()).await?;
+        compute_epoch_timeline(
+            &self.get_gauge_factory(),
+            &transitions,
+            u64::try_from(current_unix_timestamp())?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_timeline {
+    use super::*;
+
+    #[test]
+    fn test_compute_epoch_timeline() {
+        let gauge_factory = GaugeFactoryState {
+            epoch_duration_seconds: 100,
+            current_voting_epoch: 4,
+            next_epoch_starts_at: 1_020,
+            rewards_epoch: Some(3),
+            ..Default::default()
+        };
+        let transition = |voting_epoch, started_at| EpochTransition {
+            gauge_factory: String::new(),
+            voting_epoch,
+            signature: format!("sig{}", voting_epoch),
+            started_at,
+        };
+        // the crank triggered epoch 4 ten seconds late
+        let transitions = vec![transition(3, 810), transition(4, 920)];
+
+        let timeline = compute_epoch_timeline(&gauge_factory, &transitions, 960).unwrap();
+        assert_eq!(timeline.current_rewards_epoch, Some(3));
+        assert_eq!(timeline.seconds_until_next_epoch, 60);
+        let periods: Vec<(u32, u64, u64)> = timeline
+            .epochs
+            .iter()
+            .map(|x| (x.voting_epoch, x.started_at, x.ends_at))
+            .collect();
+        assert_eq!(
+            periods,
+            vec![(1, 610, 710), (2, 710, 810), (3, 810, 920), (4, 920, 1_020)]
+        );
+        assert_eq!(timeline.epochs[2].signature, Some(String::from("sig3")));
+        assert_eq!(timeline.epochs[1].signature, None);
+    }
+}
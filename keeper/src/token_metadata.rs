@@ -0,0 +1,174 @@
+//! Decimals, symbol and name of the mints of the gauges and bribes, from the mint accounts
+//! and their Metaplex metadata
+
+use crate::core::Core;
+use crate::database::*;
+use crate::state::TokenInfo;
+use anchor_client::solana_sdk::account::Account;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use dao_common::mint::parse_mint_decimals;
+use std::collections::BTreeSet;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+pub const METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+/// key (1), update_authority (32) and mint (32)
+const METADATA_NAME_OFFSET: usize = 65;
+
+pub fn find_metadata_addr(mint: &Pubkey) -> Pubkey {
+    let metadata_program = Pubkey::from_str(METADATA_PROGRAM_ID).unwrap();
+    let (metadata, _bump) = Pubkey::find_program_address(
+        &[
+            b"metadata".as_ref(),
+            metadata_program.as_ref(),
+            mint.as_ref(),
+        ],
+        &metadata_program,
+    );
+    metadata
+}
+
+/// Borsh string, padded with null bytes by the metadata program
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len_bytes: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+    let len = usize::try_from(u32::from_le_bytes(len_bytes)).ok()?;
+    let start = offset + 4;
+    let bytes = data.get(start..start + len)?;
+    let value = String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string();
+    Some((value, start + len))
+}
+
+/// Name and symbol of a metadata account
+pub fn parse_metadata(account: &Account) -> Option<(String, String)> {
+    if account.owner.to_string() != METADATA_PROGRAM_ID {
+        return None;
+    }
+    let (name, offset) = read_string(&account.data, METADATA_NAME_OFFSET)?;
+    let (symbol, _) = read_string(&account.data, offset)?;
+    Some((name, symbol))
+}
+
+impl Core {
+    /// Mints of the cached gauges and of the stored bribes, sorted
+    async fn referenced_mints(&self) -> Result<Vec<String>> {
+        let mut mints = BTreeSet::new();
+        for gauge in self.get_gauges() {
+            mints.insert(gauge.token_a_mint);
+            mints.insert(gauge.token_b_mint);
+        }
+        let bribe_mints =
+            get_bribe_token_mints(&self.pg_pool, &self.get_gauge_factory_key()).await?;
+        mints.extend(bribe_mints);
+        Ok(mints.into_iter().collect())
+    }
+
+    /// Upsert the decimals, symbol and name of every referenced mint in the token table
+    pub async fn process_discover_tokens(&self) -> Result<()> {
+        if !self.leader.is_leader() {
+            return Ok(());
+        }
+        let mints: Vec<Pubkey> = self
+            .referenced_mints()
+            .await?
+            .iter()
+            .map(|x| Pubkey::from_str(x))
+            .collect::<Result<Vec<Pubkey>, _>>()?;
+        let metadata_keys: Vec<Pubkey> = mints.iter().map(find_metadata_addr).collect();
+        let mint_accounts = self.chain.get_multiple_accounts(&mints).await?;
+        let metadata_accounts = self.chain.get_multiple_accounts(&metadata_keys).await?;
+
+        for ((mint, mint_account), metadata_account) in mints
+            .iter()
+            .zip(mint_accounts.iter())
+            .zip(metadata_accounts.iter())
+        {
+            let decimals = match mint_account.as_ref().and_then(parse_mint_decimals) {
+                Some(value) => value,
+                None => {
+                    println!("cannot read mint {}", mint);
+                    continue;
+                }
+            };
+            let (name, symbol) = match metadata_account.as_ref().and_then(parse_metadata) {
+                Some((name, symbol)) => (Some(name), Some(symbol)),
+                None => (None, None),
+            };
+            upsert_token(
+                &self.pg_pool,
+                &mint.to_string(),
+                decimals.into(),
+                symbol,
+                name,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_tokens(&self) -> Result<Vec<TokenInfo>> {
+        let mut tokens = vec![];
+        for token in get_tokens(&self.pg_pool).await? {
+            tokens.push(token_info(token)?);
+        }
+        Ok(tokens)
+    }
+
+    pub async fn get_token(&self, mint: &str) -> Result<TokenInfo> {
+        let token = get_token(&self.pg_pool, mint)
+            .await?
+            .ok_or_else(|| anyhow::Error::msg(format!("token {} not found", mint)))?;
+        token_info(token)
+    }
+}
+
+fn token_info(token: Token) -> Result<TokenInfo> {
+    Ok(TokenInfo {
+        address: token.address,
+        symbol: token.symbol,
+        name: token.name,
+        decimals: u8::try_from(token.decimals)?,
+        coingecko_id: token.coingecko_id,
+    })
+}
+
+#[cfg(test)]
+mod test_token_metadata {
+    use super::*;
+
+    fn borsh_string(value: &str, padded_len: usize) -> Vec<u8> {
+        let mut bytes = (padded_len as u32).to_le_bytes().to_vec();
+        bytes.extend(value.as_bytes());
+        bytes.resize(4 + padded_len, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_mint_and_metadata() {
+        let mint = dao_common::mint::mint_account(6);
+        assert_eq!(parse_mint_decimals(&mint), Some(6));
+        let not_a_mint = Account {
+            owner: Pubkey::new_unique(),
+            ..mint.clone()
+        };
+        assert_eq!(parse_mint_decimals(&not_a_mint), None);
+
+        let mut data = vec![4u8; METADATA_NAME_OFFSET];
+        data.extend(borsh_string("USD Coin", 32));
+        data.extend(borsh_string("USDC", 10));
+        data.extend(borsh_string("", 200));
+        let metadata = Account {
+            data,
+            owner: Pubkey::from_str(METADATA_PROGRAM_ID).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_metadata(&metadata),
+            Some((String::from("USD Coin"), String::from("USDC")))
+        );
+    }
+}
@@ -1,10 +1,6 @@
 use std::str::FromStr;
 
 use crate::PostgresArgs;
-use anchor_client::{
-    solana_sdk::{pubkey::Pubkey, signer::Signer},
-    Client, Cluster, Program,
-};
 use anyhow::*;
 
 use log::error;
@@ -36,17 +32,3 @@ pub async fn create_pg_pool(args: PostgresArgs) -> Result<Pool<Postgres>> {
 pub fn log_error(message: &str, error: Error) {
     error!("ERROR: {}. Details: {}", message, error.to_string());
 }
-
-/// Create an anchor program instance
-pub fn create_program<C: Clone + std::ops::Deref<Target = impl Signer>>(
-    http_provider: String,
-    wss_provider: String,
-    program_id: Pubkey,
-    payer: C,
-) -> Result<Program<C>> {
-    let cluster = Cluster::Custom(http_provider, wss_provider);
-    let client = Client::new(cluster, payer);
-    let program = client.program(program_id)?;
-
-    Ok(program)
-}
@@ -0,0 +1,487 @@
+//! Consistency checker, comparing the stored epoch gauges and bribes with the chain
+
+use crate::core::{current_unix_timestamp, Core};
+use crate::database::*;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::str::FromStr;
+
+/// Rows of each kind checked by a sampled run
+const VERIFY_SAMPLE_SIZE: i64 = 200;
+
+/// Field of an issue about a stored row whose account does not exist or cannot be decoded on chain
+const ACCOUNT_FIELD: &str = "account";
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum VerifyMode {
+    Off,
+    /// Check a random sample of the stored rows on every run
+    Sample,
+    /// Check every stored row on every run
+    Full,
+}
+
+/// A stored value next to the chain one
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldComparison {
+    pub field: &'static str,
+    pub db_value: String,
+    pub chain_value: String,
+    pub matches: bool,
+}
+
+fn compare_str(
+    fields: &mut Vec<FieldComparison>,
+    field: &'static str,
+    db_value: &str,
+    chain_value: String,
+) {
+    fields.push(FieldComparison {
+        field,
+        db_value: db_value.to_string(),
+        matches: db_value == chain_value,
+        chain_value,
+    });
+}
+
+/// Amounts are stored as text, compare them as numbers
+fn compare_u64(
+    fields: &mut Vec<FieldComparison>,
+    field: &'static str,
+    db_value: &str,
+    chain_value: u64,
+) {
+    fields.push(FieldComparison {
+        field,
+        db_value: db_value.to_string(),
+        chain_value: chain_value.to_string(),
+        matches: db_value.parse::<u64>().ok() == Some(chain_value),
+    });
+}
+
+fn compare_i64(
+    fields: &mut Vec<FieldComparison>,
+    field: &'static str,
+    db_value: i64,
+    chain_value: i64,
+) {
+    fields.push(FieldComparison {
+        field,
+        db_value: db_value.to_string(),
+        chain_value: chain_value.to_string(),
+        matches: db_value == chain_value,
+    });
+}
+
+pub fn compare_epoch_gauge(row: &EpochGauge, chain: &gauge::EpochGauge) -> Vec<FieldComparison> {
+    let mut fields = vec![];
+    compare_str(&mut fields, "gauge", &row.gauge, chain.gauge.to_string());
+    compare_i64(
+        &mut fields,
+        "voting_epoch",
+        row.voting_epoch,
+        chain.voting_epoch.into(),
+    );
+    compare_u64(
+        &mut fields,
+        "total_power",
+        &row.total_power,
+        chain.total_power,
+    );
+    compare_u64(
+        &mut fields,
+        "token_a_fee",
+        &row.token_a_fee,
+        chain.token_a_fee,
+    );
+    compare_u64(
+        &mut fields,
+        "token_b_fee",
+        &row.token_b_fee,
+        chain.token_b_fee,
+    );
+    fields
+}
+
+pub fn compare_bribe(row: &Bribe, chain: &gauge::Bribe) -> Vec<FieldComparison> {
+    let mut fields = vec![];
+    compare_str(&mut fields, "gauge", &row.gauge, chain.gauge.to_string());
+    compare_str(
+        &mut fields,
+        "token_mint",
+        &row.token_mint,
+        chain.token_mint.to_string(),
+    );
+    compare_u64(
+        &mut fields,
+        "reward_each_epoch",
+        &row.reward_each_epoch,
+        chain.reward_each_epoch,
+    );
+    compare_str(&mut fields, "briber", &row.briber, chain.briber.to_string());
+    compare_str(
+        &mut fields,
+        "token_account_vault",
+        &row.token_account_vault,
+        chain.token_account_vault.to_string(),
+    );
+    compare_i64(
+        &mut fields,
+        "bribe_rewards_epoch_start",
+        row.bribe_rewards_epoch_start,
+        chain.bribe_rewards_epoch_start.into(),
+    );
+    compare_i64(
+        &mut fields,
+        "bribe_rewards_epoch_end",
+        row.bribe_rewards_epoch_end,
+        chain.bribe_rewards_epoch_end.into(),
+    );
+    compare_i64(
+        &mut fields,
+        "bribe_index",
+        row.bribe_index,
+        chain.bribe_index.into(),
+    );
+    fields
+}
+
+/// Fields of the stored epoch gauge which differ from the chain
+pub fn diff_epoch_gauge(row: &EpochGauge, chain: &gauge::EpochGauge) -> Vec<FieldComparison> {
+    compare_epoch_gauge(row, chain)
+        .into_iter()
+        .filter(|x| !x.matches)
+        .collect()
+}
+
+/// Fields of the stored bribe which differ from the chain
+pub fn diff_bribe(row: &Bribe, chain: &gauge::Bribe) -> Vec<FieldComparison> {
+    compare_bribe(row, chain)
+        .into_iter()
+        .filter(|x| !x.matches)
+        .collect()
+}
+
+fn missing_on_chain(address: &str) -> FieldComparison {
+    FieldComparison {
+        field: ACCOUNT_FIELD,
+        db_value: address.to_string(),
+        chain_value: String::from("missing"),
+        matches: false,
+    }
+}
+
+fn undecodable_on_chain(address: &str, err: &anyhow::Error) -> FieldComparison {
+    FieldComparison {
+        field: ACCOUNT_FIELD,
+        db_value: address.to_string(),
+        chain_value: format!("undecodable: {}", err),
+        matches: false,
+    }
+}
+
+impl Core {
+    /// Re-fetch stored epoch gauges and bribes from chain, and record the fields which differ.
+    /// In repair mode the stored rows are overwritten with the chain values.
+    pub async fn process_verify_consistency(&self) -> Result<()> {
+        if self.verify_mode == VerifyMode::Off || !self.leader.is_leader() {
+            return Ok(());
+        }
+        let current_voting_epoch: i64 = {
+            let state = self.state.lock().unwrap();
+            if !state.is_gauge_factory_initialized() {
+                return Ok(());
+            }
+            state.gauge_factory.current_voting_epoch.into()
+        };
+        let limit = match self.verify_mode {
+            VerifyMode::Sample => Some(VERIFY_SAMPLE_SIZE),
+            _ => None,
+        };
+        let gauge_factory_key = self.get_gauge_factory_key();
+
+        let epoch_gauges = get_ended_epoch_gauges(
+            &self.pg_pool,
+            &gauge_factory_key,
+            current_voting_epoch,
+            limit,
+        )
+        .await?;
+        let num_epoch_gauge_issues = self.verify_epoch_gauges(&epoch_gauges).await?;
+
+        let bribes = get_sampled_bribes(&self.pg_pool, &gauge_factory_key, limit).await?;
+        let num_bribe_issues = self.verify_bribes(&bribes).await?;
+
+        println!(
+            "verified {} epoch gauges and {} bribes of gauge factory {}: {} and {} inconsistent",
+            epoch_gauges.len(),
+            bribes.len(),
+            gauge_factory_key,
+            num_epoch_gauge_issues,
+            num_bribe_issues
+        );
+        for (kind, num_issues) in [
+            ("epoch_gauge", num_epoch_gauge_issues),
+            ("bribe", num_bribe_issues),
+        ] {
+            self.metrics.set(
+                "keeper_consistency_issues",
+                "Stored rows which differ from the chain in the last verifier run",
+                &[("gauge_factory", &gauge_factory_key), ("kind", kind)],
+                num_issues as f64,
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the number of inconsistent rows
+    async fn verify_epoch_gauges(&self, rows: &[EpochGauge]) -> Result<u64> {
+        let pubkeys = rows
+            .iter()
+            .map(|x| Pubkey::from_str(&x.address))
+            .collect::<std::result::Result<Vec<Pubkey>, _>>()?;
+        let accounts = self
+            .chain
+            .get_anchor_accounts::<gauge::EpochGauge>(&pubkeys)
+            .await?;
+
+        let mut num_issues = 0;
+        for (row, account) in rows.iter().zip(accounts.into_iter()) {
+            let (chain, diffs) = match account {
+                Ok(Some(chain)) => {
+                    let diffs = diff_epoch_gauge(row, &chain);
+                    (Some(chain), diffs)
+                }
+                Ok(None) => (None, vec![missing_on_chain(&row.address)]),
+                Err(err) => (None, vec![undecodable_on_chain(&row.address, &err)]),
+            };
+            if !diffs.is_empty() {
+                num_issues += 1;
+            }
+            self.record_consistency("epoch_gauge", &row.address, &diffs)
+                .await?;
+            if !self.verify_repair || diffs.is_empty() {
+                continue;
+            }
+            // a row missing or undecodable on chain is left for an operator
+            if let Some(chain) = chain {
+                repair_epoch_gauge(&self.pg_pool, &row.address, &chain).await?;
+                resolve_consistency_issues(
+                    &self.pg_pool,
+                    &row.address,
+                    current_unix_timestamp(),
+                    true,
+                )
+                .await?;
+            }
+        }
+        Ok(num_issues)
+    }
+
+    /// Returns the number of inconsistent rows
+    async fn verify_bribes(&self, rows: &[Bribe]) -> Result<u64> {
+        let pubkeys = rows
+            .iter()
+            .map(|x| Pubkey::from_str(&x.address))
+            .collect::<std::result::Result<Vec<Pubkey>, _>>()?;
+        let accounts = self
+            .chain
+            .get_anchor_accounts::<gauge::Bribe>(&pubkeys)
+            .await?;
+
+        let mut num_issues = 0;
+        for (row, account) in rows.iter().zip(accounts.into_iter()) {
+            let (chain, diffs) = match account {
+                Ok(Some(chain)) => {
+                    let diffs = diff_bribe(row, &chain);
+                    (Some(chain), diffs)
+                }
+                Ok(None) => (None, vec![missing_on_chain(&row.address)]),
+                Err(err) => (None, vec![undecodable_on_chain(&row.address, &err)]),
+            };
+            if !diffs.is_empty() {
+                num_issues += 1;
+            }
+            self.record_consistency("bribe", &row.address, &diffs)
+                .await?;
+            if !self.verify_repair || diffs.is_empty() {
+                continue;
+            }
+            // a row missing or undecodable on chain is left for an operator
+            if let Some(chain) = chain {
+                repair_bribe(&self.pg_pool, &row.address, &chain).await?;
+                resolve_consistency_issues(
+                    &self.pg_pool,
+                    &row.address,
+                    current_unix_timestamp(),
+                    true,
+                )
+                .await?;
+            }
+        }
+        Ok(num_issues)
+    }
+
+    /// Record the differences of a row, or close its open issues when it matches the chain
+    async fn record_consistency(
+        &self,
+        kind: &str,
+        address: &str,
+        diffs: &[FieldComparison],
+    ) -> Result<()> {
+        let now = current_unix_timestamp();
+        if diffs.is_empty() {
+            return resolve_consistency_issues(&self.pg_pool, address, now, false).await;
+        }
+        let gauge_factory_key = self.get_gauge_factory_key();
+        for diff in diffs.iter() {
+            println!(
+                "inconsistent {} {} {}: db {} chain {}",
+                kind, address, diff.field, diff.db_value, diff.chain_value
+            );
+            save_consistency_issue(
+                &self.pg_pool,
+                &gauge_factory_key,
+                kind,
+                address,
+                diff.field,
+                &diff.db_value,
+                &diff.chain_value,
+                now,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_verifier {
+    use super::*;
+
+    fn epoch_gauge_row(chain: &gauge::EpochGauge) -> EpochGauge {
+        EpochGauge {
+            address: Pubkey::new_unique().to_string(),
+            gauge: chain.gauge.to_string(),
+            total_power: chain.total_power.to_string(),
+            token_a_fee: chain.token_a_fee.to_string(),
+            token_b_fee: chain.token_b_fee.to_string(),
+            voting_epoch: chain.voting_epoch.into(),
+            gauge_factory: Pubkey::new_unique().to_string(),
+        }
+    }
+
+    fn bribe_row(chain: &gauge::Bribe) -> Bribe {
+        Bribe {
+            address: Pubkey::new_unique().to_string(),
+            gauge: chain.gauge.to_string(),
+            token_mint: chain.token_mint.to_string(),
+            reward_each_epoch: chain.reward_each_epoch.to_string(),
+            briber: chain.briber.to_string(),
+            token_account_vault: chain.token_account_vault.to_string(),
+            bribe_rewards_epoch_start: chain.bribe_rewards_epoch_start.into(),
+            bribe_rewards_epoch_end: chain.bribe_rewards_epoch_end.into(),
+            bribe_index: chain.bribe_index.into(),
+            gauge_factory: Pubkey::new_unique().to_string(),
+        }
+    }
+
+    fn fields(comparisons: &[FieldComparison]) -> Vec<&'static str> {
+        comparisons.iter().map(|x| x.field).collect()
+    }
+
+    #[test]
+    fn test_diff_epoch_gauge() {
+        let chain = gauge::EpochGauge {
+            gauge: Pubkey::new_unique(),
+            voting_epoch: 3,
+            total_power: 10,
+            token_a_fee: 20,
+            token_b_fee: 30,
+            ..Default::default()
+        };
+        let row = epoch_gauge_row(&chain);
+        let comparisons = compare_epoch_gauge(&row, &chain);
+        assert_eq!(comparisons.len(), 5);
+        assert!(comparisons.iter().all(|x| x.matches));
+        assert!(diff_epoch_gauge(&row, &chain).is_empty());
+
+        let mismatch = EpochGauge {
+            total_power: String::from("11"),
+            voting_epoch: 4,
+            ..row.clone()
+        };
+        let diff = diff_epoch_gauge(&mismatch, &chain);
+        assert_eq!(fields(&diff), vec!["voting_epoch", "total_power"]);
+        assert_eq!(diff[1].db_value, "11");
+        assert_eq!(diff[1].chain_value, "10");
+
+        // an empty or unreadable amount never matches
+        let unreadable = EpochGauge {
+            token_a_fee: String::new(),
+            token_b_fee: String::from("30.0"),
+            ..row
+        };
+        assert_eq!(
+            fields(&diff_epoch_gauge(&unreadable, &chain)),
+            vec!["token_a_fee", "token_b_fee"]
+        );
+    }
+
+    #[test]
+    fn test_diff_bribe() {
+        let chain = gauge::Bribe {
+            gauge: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            reward_each_epoch: 1_000,
+            briber: Pubkey::new_unique(),
+            token_account_vault: Pubkey::new_unique(),
+            bribe_rewards_epoch_start: 2,
+            bribe_rewards_epoch_end: 5,
+            bribe_index: 7,
+            ..Default::default()
+        };
+        let row = bribe_row(&chain);
+        let comparisons = compare_bribe(&row, &chain);
+        assert_eq!(comparisons.len(), 8);
+        assert!(comparisons.iter().all(|x| x.matches));
+        assert!(diff_bribe(&row, &chain).is_empty());
+
+        let mismatch = Bribe {
+            token_mint: Pubkey::new_unique().to_string(),
+            reward_each_epoch: String::from("999"),
+            bribe_rewards_epoch_end: 6,
+            ..row
+        };
+        assert_eq!(
+            fields(&diff_bribe(&mismatch, &chain)),
+            vec!["token_mint", "reward_each_epoch", "bribe_rewards_epoch_end"]
+        );
+    }
+
+    #[test]
+    fn test_missing_on_chain() {
+        let address = Pubkey::new_unique().to_string();
+        assert_eq!(
+            missing_on_chain(&address),
+            FieldComparison {
+                field: ACCOUNT_FIELD,
+                db_value: address.clone(),
+                chain_value: String::from("missing"),
+                matches: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_undecodable_on_chain() {
+        let address = Pubkey::new_unique().to_string();
+        let err = anyhow::Error::msg("invalid discriminator");
+        let diff = undecodable_on_chain(&address, &err);
+        assert_eq!(diff.field, ACCOUNT_FIELD);
+        assert_eq!(diff.db_value, address);
+        assert_eq!(diff.chain_value, "undecodable: invalid discriminator");
+        assert!(!diff.matches);
+    }
+}
@@ -0,0 +1,133 @@
+//! Accounts and clock of the mock cluster
+
+use anchor_client::solana_client::rpc_filter::RpcFilterType;
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::rent::Rent;
+use anchor_client::solana_sdk::signature::Signature;
+use anchor_client::solana_sdk::sysvar::{self, clock::Clock};
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AccountDeserialize, AccountSerialize, Owner};
+use anyhow::Result;
+use dao_common::account::{anchor_account, filter_matches};
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+/// Slot time of the real clusters
+const MS_PER_SLOT: u128 = 400;
+const SLOTS_PER_EPOCH: u64 = 432_000;
+
+#[derive(Clone)]
+pub struct Bank {
+    accounts: BTreeMap<Pubkey, Account>,
+    started_at: Instant,
+    /// Unix timestamp of the clock at startup
+    start_timestamp: i64,
+    /// Seconds added to the clock by scripted changes
+    clock_offset: i64,
+    signatures: HashSet<Signature>,
+}
+
+impl Bank {
+    pub fn new(start_timestamp: i64) -> Self {
+        Bank {
+            accounts: BTreeMap::new(),
+            started_at: Instant::now(),
+            start_timestamp,
+            clock_offset: 0,
+            signatures: HashSet::new(),
+        }
+    }
+
+    /// Slots move with the wall clock, not with the scripted offset
+    pub fn slot(&self) -> u64 {
+        (self.started_at.elapsed().as_millis() / MS_PER_SLOT) as u64
+    }
+
+    pub fn clock(&self) -> Clock {
+        let slot = self.slot();
+        Clock {
+            slot,
+            epoch_start_timestamp: self.start_timestamp,
+            epoch: slot / SLOTS_PER_EPOCH,
+            leader_schedule_epoch: slot / SLOTS_PER_EPOCH + 1,
+            unix_timestamp: self.start_timestamp
+                + self.started_at.elapsed().as_secs() as i64
+                + self.clock_offset,
+        }
+    }
+
+    pub fn advance_clock(&mut self, seconds: i64) {
+        self.clock_offset += seconds;
+    }
+
+    /// The clock sysvar is built on every read, so it follows the slot and the offset
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        if *pubkey == sysvar::clock::id() {
+            let data = bincode::serialize(&self.clock()).unwrap();
+            return Some(Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: sysvar::id(),
+                executable: false,
+                rent_epoch: 0,
+            });
+        }
+        self.accounts.get(pubkey).cloned()
+    }
+
+    pub fn set_account(&mut self, pubkey: Pubkey, account: Account) {
+        self.accounts.insert(pubkey, account);
+    }
+
+    pub fn remove_account(&mut self, pubkey: &Pubkey) {
+        self.accounts.remove(pubkey);
+    }
+
+    /// Stub of a deployed program, unless the fixtures hold the real one
+    pub fn add_program(&mut self, program_id: Pubkey) {
+        self.accounts.entry(program_id).or_insert_with(|| Account {
+            lamports: 1,
+            data: vec![],
+            owner: anchor_lang::solana_program::bpf_loader_upgradeable::id(),
+            executable: true,
+            rent_epoch: 0,
+        });
+    }
+
+    pub fn program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &[RpcFilterType],
+    ) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| filters.iter().all(|x| filter_matches(x, &account.data)))
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect()
+    }
+
+    pub fn get_anchor_account<T: AccountDeserialize>(&self, pubkey: &Pubkey) -> Result<T> {
+        let account = self
+            .get_account(pubkey)
+            .ok_or_else(|| anyhow::Error::msg(format!("account {} not found", pubkey)))?;
+        Ok(T::try_deserialize(&mut account.data.as_ref())?)
+    }
+
+    pub fn set_anchor_account<T: AccountSerialize + Owner>(
+        &mut self,
+        pubkey: Pubkey,
+        value: &T,
+    ) -> Result<()> {
+        self.set_account(pubkey, anchor_account(value)?);
+        Ok(())
+    }
+
+    pub fn record_signature(&mut self, signature: Signature) {
+        self.signatures.insert(signature);
+    }
+
+    pub fn has_signature(&self, signature: &Signature) -> bool {
+        self.signatures.contains(signature)
+    }
+}
@@ -0,0 +1,43 @@
+//! Generate the fixtures of a gauge factory from a spec, for the mock RPC
+
+use clap::Parser;
+use dao_mock_rpc::generator::{generate, FixtureSpec};
+use std::path::Path;
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// JSON spec, like {"num_gauges": 10, "num_epochs": 4, "bribes_per_epoch": 3, "total_power_per_epoch": 1000000, "next_epoch_starts_at": 1700000000}
+    #[clap(long)]
+    spec: String,
+    /// Directory the fixture files are written to, created if missing
+    #[clap(long)]
+    out: String,
+}
+
+fn main() {
+    let Args { spec, out } = Args::parse();
+
+    let content = std::fs::read_to_string(&spec).expect("Cannot read spec");
+    let spec: FixtureSpec = serde_json::from_str(&content).expect("Cannot parse spec");
+    let fixtures = generate(&spec).expect("Cannot generate fixtures");
+
+    let out = Path::new(&out);
+    std::fs::create_dir_all(out).expect("Cannot create output directory");
+    fixtures.write(out).expect("Cannot write fixtures");
+
+    println!("base {}", fixtures.base);
+    println!("gauge factory {}", fixtures.gauge_factory);
+    println!(
+        "{} gauges, {} quarries, {} epoch gauges, {} bribes, current voting epoch {}",
+        fixtures.gauges.len(),
+        fixtures.quarries.len(),
+        fixtures.epoch_gauges.len(),
+        fixtures.bribes.len(),
+        fixtures.current_voting_epoch
+    );
+    println!(
+        "fixtures written to {}, serve them with: dao-mock-rpc --fixtures {}",
+        out.display(),
+        out.display()
+    );
+}
@@ -0,0 +1,81 @@
+//! Account fixtures, in the JSON format of `solana account --output json`
+
+use anchor_client::solana_sdk::account::Account;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureAccount {
+    pub pubkey: String,
+    pub account: UiAccount,
+}
+
+/// A fixture file holds one account or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum FixtureFile {
+    One(FixtureAccount),
+    Many(Vec<FixtureAccount>),
+}
+
+impl FixtureAccount {
+    pub fn new(pubkey: &Pubkey, account: &Account) -> Self {
+        FixtureAccount {
+            pubkey: pubkey.to_string(),
+            account: UiAccount::encode(pubkey, account, UiAccountEncoding::Base64, None, None),
+        }
+    }
+
+    pub fn decode(&self) -> Result<(Pubkey, Account)> {
+        let pubkey = Pubkey::from_str(&self.pubkey)?;
+        let account: Account = self.account.decode().ok_or_else(|| {
+            anyhow::Error::msg(format!("cannot decode fixture account {}", self.pubkey))
+        })?;
+        Ok((pubkey, account))
+    }
+}
+
+/// Load every .json file of the directory
+pub fn load_fixtures(dir: &Path) -> Result<Vec<(Pubkey, Account)>> {
+    let mut accounts = vec![];
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<std::result::Result<_, _>>()?;
+    paths.sort();
+    for path in paths {
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        let fixtures = match serde_json::from_str::<FixtureFile>(&content) {
+            Ok(FixtureFile::One(fixture)) => vec![fixture],
+            Ok(FixtureFile::Many(fixtures)) => fixtures,
+            Err(err) => {
+                return Err(anyhow::Error::msg(format!(
+                    "cannot parse fixture {}: {}",
+                    path.display(),
+                    err
+                )))
+            }
+        };
+        for fixture in fixtures.iter() {
+            accounts.push(fixture.decode()?);
+        }
+    }
+    Ok(accounts)
+}
+
+/// Write the accounts as a list in a single fixture file
+pub fn write_fixtures(path: &Path, accounts: &[(Pubkey, Account)]) -> Result<()> {
+    let fixtures: Vec<FixtureAccount> = accounts
+        .iter()
+        .map(|(pubkey, account)| FixtureAccount::new(pubkey, account))
+        .collect();
+    fs::write(path, serde_json::to_string_pretty(&fixtures)?)?;
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Consistent sets of gauge program accounts generated from a spec, for fixtures and load tests
+
+use crate::fixtures::write_fixtures;
+use anchor_client::solana_sdk::account::Account;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use dao_common::account::anchor_account;
+use dao_common::mint::mint_account;
+use dao_common::pda::{find_bribe_addr, find_epoch_gauge_addr, find_gauge_factory_addr};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
+use std::path::Path;
+
+/// Mints the generated bribes pay in
+const NUM_BRIBE_MINTS: usize = 4;
+/// Most epochs a generated bribe lasts
+const MAX_BRIBE_EPOCHS: u32 = 3;
+/// Decimals the generated mints are drawn from
+const MINT_DECIMALS: [u8; 3] = [6, 8, 9];
+
+/// How the power of an epoch is split between the enabled gauges
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteDistribution {
+    /// Every gauge gets the same power
+    Uniform,
+    /// Random weights, drawn again every epoch
+    Random,
+    /// The n-th gauge gets a weight of 1/n, like a few popular pools and a long tail
+    Zipf,
+}
+
+fn default_vote_distribution() -> VoteDistribution {
+    VoteDistribution::Uniform
+}
+
+fn default_epoch_duration_seconds() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_annual_rewards_rate() -> u64 {
+    1_000_000_000_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureSpec {
+    /// Seed of the keys and votes, the same spec always generates the same accounts
+    #[serde(default)]
+    pub seed: u64,
+    pub num_gauges: usize,
+    /// The last gauges are disabled, they get no votes nor bribes
+    #[serde(default)]
+    pub num_disabled_gauges: usize,
+    /// Voting epochs with epoch gauges, from 1. The last one is the current voting epoch
+    pub num_epochs: u32,
+    #[serde(default)]
+    pub bribes_per_epoch: usize,
+    #[serde(default = "default_vote_distribution")]
+    pub vote_distribution: VoteDistribution,
+    /// Power voted in every epoch, split between the enabled gauges
+    pub total_power_per_epoch: u64,
+    #[serde(default = "default_epoch_duration_seconds")]
+    pub epoch_duration_seconds: u64,
+    /// Unix timestamp the current voting epoch ends at
+    pub next_epoch_starts_at: u64,
+    /// Rewards of the rewarder per year, split between the quarries by rewards share
+    #[serde(default = "default_annual_rewards_rate")]
+    pub annual_rewards_rate: u64,
+}
+
+pub struct GeneratedFixtures {
+    pub base: Pubkey,
+    pub gauge_factory: Pubkey,
+    pub current_voting_epoch: u32,
+    pub gauge_factory_account: Account,
+    pub rewarder: Pubkey,
+    pub rewarder_account: Account,
+    pub gauges: Vec<(Pubkey, Account)>,
+    pub quarries: Vec<(Pubkey, Account)>,
+    pub epoch_gauges: Vec<(Pubkey, Account)>,
+    pub bribes: Vec<(Pubkey, Account)>,
+    /// Token mints of the gauges and of the bribes
+    pub mints: Vec<(Pubkey, Account)>,
+}
+
+impl GeneratedFixtures {
+    /// One fixture file per account type
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        write_fixtures(
+            &dir.join("gauge_factory.json"),
+            &[(self.gauge_factory, self.gauge_factory_account.clone())],
+        )?;
+        write_fixtures(
+            &dir.join("rewarder.json"),
+            &[(self.rewarder, self.rewarder_account.clone())],
+        )?;
+        write_fixtures(&dir.join("gauges.json"), &self.gauges)?;
+        write_fixtures(&dir.join("quarries.json"), &self.quarries)?;
+        write_fixtures(&dir.join("epoch_gauges.json"), &self.epoch_gauges)?;
+        write_fixtures(&dir.join("bribes.json"), &self.bribes)?;
+        write_fixtures(&dir.join("mints.json"), &self.mints)?;
+        Ok(())
+    }
+}
+
+fn random_pubkey(rng: &mut StdRng) -> Pubkey {
+    Pubkey::new_from_array(rng.gen())
+}
+
+/// Split the power by the weights, the rounding remainder goes to the first gauge
+fn split_power(total_power: u64, weights: &[f64]) -> Vec<u64> {
+    let total_weight: f64 = weights.iter().sum();
+    if weights.is_empty() || total_weight <= 0.0 {
+        return vec![0; weights.len()];
+    }
+    let mut powers: Vec<u64> = weights
+        .iter()
+        .map(|x| (total_power as f64 * x / total_weight) as u64)
+        .collect();
+    let remainder = total_power.saturating_sub(powers.iter().sum());
+    powers[0] += remainder;
+    powers
+}
+
+fn vote_weights(distribution: VoteDistribution, num_gauges: usize, rng: &mut StdRng) -> Vec<f64> {
+    (0..num_gauges)
+        .map(|i| match distribution {
+            VoteDistribution::Uniform => 1.0,
+            VoteDistribution::Random => rng.gen_range(0.0..1.0),
+            VoteDistribution::Zipf => 1.0 / (i + 1) as f64,
+        })
+        .collect()
+}
+
+pub fn generate(spec: &FixtureSpec) -> Result<GeneratedFixtures> {
+    if spec.num_gauges == 0 || spec.num_epochs == 0 {
+        return Err(anyhow::Error::msg(
+            "at least one gauge and one epoch are required",
+        ));
+    }
+    if spec.num_disabled_gauges > spec.num_gauges {
+        return Err(anyhow::Error::msg("more disabled gauges than gauges"));
+    }
+    let mut rng = StdRng::seed_from_u64(spec.seed);
+    let base = random_pubkey(&mut rng);
+    let gauge_factory = find_gauge_factory_addr(&base);
+    let rewarder = random_pubkey(&mut rng);
+    let current_voting_epoch = spec.num_epochs;
+    let num_enabled_gauges = spec.num_gauges - spec.num_disabled_gauges;
+
+    let mut gauge_states = vec![];
+    for i in 0..spec.num_gauges {
+        let gauge_pk = random_pubkey(&mut rng);
+        let gauge_state = gauge::Gauge {
+            gauge_factory,
+            quarry: random_pubkey(&mut rng),
+            amm_pool: random_pubkey(&mut rng),
+            token_a_mint: random_pubkey(&mut rng),
+            token_b_mint: random_pubkey(&mut rng),
+            token_a_fee_key: random_pubkey(&mut rng),
+            token_b_fee_key: random_pubkey(&mut rng),
+            is_disabled: i >= num_enabled_gauges,
+            ..Default::default()
+        };
+        gauge_states.push((gauge_pk, gauge_state));
+    }
+    let enabled_gauges = &gauge_states[..num_enabled_gauges];
+
+    // epoch gauges of the enabled gauges, and the power of the rewards epoch for the quarries
+    let mut epoch_gauges = vec![];
+    let mut rewards_shares = vec![0; num_enabled_gauges];
+    for voting_epoch in 1..=spec.num_epochs {
+        let weights = vote_weights(spec.vote_distribution, num_enabled_gauges, &mut rng);
+        let powers = split_power(spec.total_power_per_epoch, &weights);
+        for (i, ((gauge_pk, _), total_power)) in enabled_gauges.iter().zip(powers).enumerate() {
+            let epoch_gauge = gauge::EpochGauge {
+                gauge: *gauge_pk,
+                voting_epoch,
+                total_power,
+                token_a_fee: rng.gen_range(0..1_000_000),
+                token_b_fee: rng.gen_range(0..1_000_000),
+                ..Default::default()
+            };
+            epoch_gauges.push((
+                find_epoch_gauge_addr(gauge_pk, voting_epoch),
+                anchor_account(&epoch_gauge)?,
+            ));
+            // quarries are synced with the rewards epoch, the one before the current voting epoch
+            if voting_epoch + 1 == current_voting_epoch {
+                rewards_shares[i] = total_power;
+            }
+        }
+    }
+
+    let total_rewards_shares: u64 = rewards_shares.iter().sum();
+    let mut gauges = vec![];
+    let mut quarries = vec![];
+    for (i, (gauge_pk, gauge_state)) in gauge_states.iter().enumerate() {
+        gauges.push((*gauge_pk, anchor_account(gauge_state)?));
+        let rewards_share = rewards_shares.get(i).cloned().unwrap_or(0);
+        let annual_rewards_rate = if total_rewards_shares == 0 {
+            0
+        } else {
+            u64::try_from(
+                u128::from(spec.annual_rewards_rate) * u128::from(rewards_share)
+                    / u128::from(total_rewards_shares),
+            )?
+        };
+        let quarry_state = quarry::Quarry {
+            rewarder,
+            rewards_share,
+            annual_rewards_rate,
+            ..Default::default()
+        };
+        quarries.push((gauge_state.quarry, anchor_account(&quarry_state)?));
+    }
+
+    let bribe_mints: Vec<Pubkey> = (0..NUM_BRIBE_MINTS)
+        .map(|_| random_pubkey(&mut rng))
+        .collect();
+    let mut bribes = vec![];
+    if num_enabled_gauges > 0 {
+        for voting_epoch in 1..=spec.num_epochs {
+            for _ in 0..spec.bribes_per_epoch {
+                let bribe_index = u32::try_from(bribes.len())?;
+                let (gauge_pk, _) = enabled_gauges[rng.gen_range(0..num_enabled_gauges)];
+                let bribe = gauge::Bribe {
+                    gauge: gauge_pk,
+                    token_mint: bribe_mints[rng.gen_range(0..NUM_BRIBE_MINTS)],
+                    reward_each_epoch: rng.gen_range(1..1_000_000_000),
+                    briber: random_pubkey(&mut rng),
+                    token_account_vault: random_pubkey(&mut rng),
+                    bribe_rewards_epoch_start: voting_epoch,
+                    bribe_rewards_epoch_end: voting_epoch + rng.gen_range(0..MAX_BRIBE_EPOCHS),
+                    bribe_index,
+                    ..Default::default()
+                };
+                bribes.push((
+                    find_bribe_addr(&gauge_factory, bribe_index),
+                    anchor_account(&bribe)?,
+                ));
+            }
+        }
+    }
+
+    let gauge_factory_state = gauge::GaugeFactory {
+        rewarder,
+        locker: random_pubkey(&mut rng),
+        foreman: random_pubkey(&mut rng),
+        epoch_duration_seconds: spec.epoch_duration_seconds.try_into()?,
+        current_voting_epoch,
+        next_epoch_starts_at: spec.next_epoch_starts_at,
+        bribe_index: u32::try_from(bribes.len())?,
+        ..Default::default()
+    };
+
+    // mints are drawn last, so the other accounts of a spec do not change with them
+    let mut mints = vec![];
+    for mint in gauge_states
+        .iter()
+        .flat_map(|(_, x)| vec![x.token_a_mint, x.token_b_mint])
+        .chain(bribe_mints.iter().cloned())
+    {
+        let decimals = MINT_DECIMALS[rng.gen_range(0..MINT_DECIMALS.len())];
+        mints.push((mint, mint_account(decimals)));
+    }
+
+    let rewarder_state = quarry::Rewarder {
+        annual_rewards_rate: spec.annual_rewards_rate,
+        total_rewards_shares,
+        num_quarries: u16::try_from(quarries.len())?,
+        ..Default::default()
+    };
+
+    Ok(GeneratedFixtures {
+        base,
+        gauge_factory,
+        current_voting_epoch,
+        gauge_factory_account: anchor_account(&gauge_factory_state)?,
+        rewarder,
+        rewarder_account: anchor_account(&rewarder_state)?,
+        gauges,
+        quarries,
+        epoch_gauges,
+        bribes,
+        mints,
+    })
+}
+
+#[cfg(test)]
+mod test_generator {
+    use super::*;
+    use anchor_lang::AccountDeserialize;
+    use dao_common::mint::parse_mint_decimals;
+    use std::collections::HashMap;
+
+    fn new_spec(vote_distribution: VoteDistribution) -> FixtureSpec {
+        FixtureSpec {
+            seed: 7,
+            num_gauges: 5,
+            num_disabled_gauges: 1,
+            num_epochs: 3,
+            bribes_per_epoch: 2,
+            vote_distribution,
+            total_power_per_epoch: 1_000,
+            epoch_duration_seconds: 3600,
+            next_epoch_starts_at: 10_000,
+            annual_rewards_rate: 365_000,
+        }
+    }
+
+    #[test]
+    fn test_split_power() {
+        assert_eq!(split_power(10, &[1.0, 1.0, 1.0]), vec![4, 3, 3]);
+        assert_eq!(split_power(10, &[]), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_generate_consistent_accounts() {
+        let fixtures = generate(&new_spec(VoteDistribution::Random)).unwrap();
+        assert_eq!(fixtures.gauges.len(), 5);
+        assert_eq!(fixtures.quarries.len(), 5);
+        // the disabled gauge has no epoch gauge
+        assert_eq!(fixtures.epoch_gauges.len(), 4 * 3);
+        assert_eq!(fixtures.bribes.len(), 2 * 3);
+
+        for voting_epoch in 1..=3 {
+            let total_power: u64 = fixtures
+                .epoch_gauges
+                .iter()
+                .map(|(pubkey, account)| {
+                    let epoch_gauge =
+                        gauge::EpochGauge::try_deserialize(&mut account.data.as_ref()).unwrap();
+                    assert_eq!(
+                        *pubkey,
+                        find_epoch_gauge_addr(&epoch_gauge.gauge, epoch_gauge.voting_epoch)
+                    );
+                    epoch_gauge
+                })
+                .filter(|x| x.voting_epoch == voting_epoch)
+                .map(|x| x.total_power)
+                .sum();
+            assert_eq!(total_power, 1_000);
+        }
+
+        let gauge_factory_state =
+            gauge::GaugeFactory::try_deserialize(&mut fixtures.gauge_factory_account.data.as_ref())
+                .unwrap();
+        assert_eq!(gauge_factory_state.current_voting_epoch, 3);
+        assert_eq!(gauge_factory_state.rewarder, fixtures.rewarder);
+
+        // the quarries hold the votes of the rewards epoch
+        let rewarder_state =
+            quarry::Rewarder::try_deserialize(&mut fixtures.rewarder_account.data.as_ref())
+                .unwrap();
+        assert_eq!(rewarder_state.annual_rewards_rate, 365_000);
+        assert_eq!(rewarder_state.total_rewards_shares, 1_000);
+        assert_eq!(gauge_factory_state.bribe_index, 6);
+        assert_eq!(
+            fixtures.bribes[5].0,
+            find_bribe_addr(&fixtures.gauge_factory, 5)
+        );
+
+        // every mint of the gauges and bribes is readable by the token discovery
+        assert_eq!(fixtures.mints.len(), 5 * 2 + NUM_BRIBE_MINTS);
+        let mints: HashMap<Pubkey, Account> = fixtures.mints.iter().cloned().collect();
+        for (_, account) in fixtures.gauges.iter() {
+            let gauge_state = gauge::Gauge::try_deserialize(&mut account.data.as_ref()).unwrap();
+            for mint in [gauge_state.token_a_mint, gauge_state.token_b_mint].iter() {
+                assert!(parse_mint_decimals(&mints[mint]).is_some());
+            }
+        }
+        for (_, account) in fixtures.bribes.iter() {
+            let bribe = gauge::Bribe::try_deserialize(&mut account.data.as_ref()).unwrap();
+            assert!(parse_mint_decimals(&mints[&bribe.token_mint]).is_some());
+        }
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let first = generate(&new_spec(VoteDistribution::Zipf)).unwrap();
+        let second = generate(&new_spec(VoteDistribution::Zipf)).unwrap();
+        assert_eq!(first.base, second.base);
+        assert_eq!(first.epoch_gauges, second.epoch_gauges);
+        assert_eq!(first.bribes, second.bribes);
+    }
+}
@@ -0,0 +1,8 @@
+//! Mock Solana cluster for local development: accounts, fixtures and the gauge instructions of the crank
+
+pub mod bank;
+pub mod fixtures;
+pub mod generator;
+pub mod program;
+pub mod rpc;
+pub mod script;
@@ -0,0 +1,140 @@
+//! Mock of the Solana JSON-RPC methods the keeper uses, serving accounts from fixtures,
+//! so the keeper runs on a laptop without a cluster
+
+use clap::Parser;
+use dao_mock_rpc::bank::Bank;
+use dao_mock_rpc::fixtures::load_fixtures;
+use dao_mock_rpc::rpc;
+use dao_mock_rpc::script::{run_script, Action, ScriptStep};
+use hyper::{body::to_bytes, Body, Request, Response, Server, StatusCode};
+use routerify::prelude::*;
+use routerify::{Router, RouterService};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Directory of account fixtures, JSON files in the format of `solana account --output json`
+    #[clap(long)]
+    fixtures: Option<String>,
+    /// Socket address to bind to, the localnet RPC address by default
+    #[clap(long, default_value_t = String::from("127.0.0.1:8899"))]
+    socket_address: String,
+    /// JSON list of changes to play, like {"after_seconds": 60, "action": "advance_epoch", "gauge_factory": "..."}
+    #[clap(long)]
+    script: Option<String>,
+    /// Unix timestamp of the clock at startup. Defaults to now
+    #[clap(long)]
+    start_timestamp: Option<i64>,
+}
+
+#[tokio::main]
+async fn main() {
+    let Args {
+        fixtures,
+        socket_address,
+        script,
+        start_timestamp,
+    } = Args::parse();
+
+    let start_timestamp = start_timestamp.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    });
+    let mut bank = Bank::new(start_timestamp);
+    if let Some(fixtures) = fixtures {
+        let accounts = load_fixtures(Path::new(&fixtures)).expect("Cannot load fixtures");
+        println!("loaded {} accounts from {}", accounts.len(), fixtures);
+        for (pubkey, account) in accounts {
+            bank.set_account(pubkey, account);
+        }
+    }
+    // the keeper checks these are deployed at startup
+    for program_id in [gauge::id(), quarry::id(), moc_amm::id()] {
+        bank.add_program(program_id);
+    }
+    let bank = Arc::new(Mutex::new(bank));
+
+    if let Some(script) = script {
+        let content = std::fs::read_to_string(&script).expect("Cannot read script");
+        let steps: Vec<ScriptStep> = serde_json::from_str(&content).expect("Cannot parse script");
+        println!("playing {} steps from {}", steps.len(), script);
+        tokio::spawn(run_script(bank.clone(), steps));
+    }
+
+    let router = Router::builder()
+        .data(bank)
+        .post("/", post_rpc)
+        .post("/mock/action", post_action)
+        .build()
+        .unwrap();
+    let service = RouterService::new(router).unwrap();
+    let addr: SocketAddr = socket_address.parse().expect("Invalid socket address");
+    println!("mock rpc listening on {}", addr);
+    Server::bind(&addr).serve(service).await.unwrap();
+}
+
+fn json_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn post_rpc(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let bank = req.data::<Arc<Mutex<Bank>>>().unwrap().clone();
+    let body = to_bytes(req.into_body()).await.unwrap_or_default();
+    let request: Value = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => {
+            return Ok(json_response(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": err.to_string() }).to_string(),
+            ))
+        }
+    };
+    let response = match request {
+        Value::Array(requests) => Value::Array(
+            requests
+                .iter()
+                .map(|x| rpc::handle_request(&bank, x))
+                .collect(),
+        ),
+        request => rpc::handle_request(&bank, &request),
+    };
+    Ok(json_response(StatusCode::OK, response.to_string()))
+}
+
+async fn post_action(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let bank = req.data::<Arc<Mutex<Bank>>>().unwrap().clone();
+    let body = to_bytes(req.into_body()).await.unwrap_or_default();
+    let action: Action = match serde_json::from_slice(&body) {
+        Ok(action) => action,
+        Err(err) => {
+            return Ok(json_response(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": err.to_string() }).to_string(),
+            ))
+        }
+    };
+    let result = action.apply(&mut bank.lock().unwrap());
+    Ok(match result {
+        Ok(message) => {
+            println!("action: {}", message);
+            json_response(
+                StatusCode::OK,
+                serde_json::json!({ "result": message }).to_string(),
+            )
+        }
+        Err(err) => json_response(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({ "error": err.to_string() }).to_string(),
+        ),
+    })
+}
@@ -0,0 +1,164 @@
+//! The gauge instructions sent by the keeper crank, played on the mock accounts.
+//! Only the account changes the keeper reads are applied, there is no runtime.
+
+use crate::bank::Bank;
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::InstructionData;
+use anyhow::Result;
+use dao_common::pda::find_bribe_addr;
+use std::convert::TryFrom;
+
+/// Apply the gauge instructions of the transaction, instructions of other programs are ignored
+pub fn apply_transaction(bank: &mut Bank, tx: &Transaction) -> Result<()> {
+    let keys = &tx.message.account_keys;
+    let trigger_next_epoch_data = gauge::instruction::TriggerNextEpoch {}.data();
+    let sync_gauge_data = gauge::instruction::SyncGauge {}.data();
+    let create_epoch_gauge_data = gauge::instruction::CreateEpochGauge {}.data();
+    for ix in tx.message.instructions.iter() {
+        if keys[ix.program_id_index as usize] != gauge::id() {
+            continue;
+        }
+        let account = |i: usize| -> Result<Pubkey> {
+            ix.accounts
+                .get(i)
+                .map(|x| keys[*x as usize])
+                .ok_or_else(|| anyhow::Error::msg("missing instruction account"))
+        };
+        if ix.data == trigger_next_epoch_data {
+            trigger_next_epoch(bank, &account(0)?)?;
+        } else if ix.data == sync_gauge_data {
+            // gauge_factory, gauge, epoch_gauge, quarry
+            sync_gauge(bank, &account(2)?, &account(3)?)?;
+        } else if ix.data == create_epoch_gauge_data {
+            // gauge_factory, gauge, epoch_gauge
+            create_epoch_gauge(bank, &account(0)?, &account(1)?, &account(2)?)?;
+        } else {
+            println!("ignored gauge instruction {:?}", ix.data);
+        }
+    }
+    Ok(())
+}
+
+pub fn trigger_next_epoch(bank: &mut Bank, gauge_factory: &Pubkey) -> Result<()> {
+    let mut state: gauge::GaugeFactory = bank.get_anchor_account(gauge_factory)?;
+    let now = u64::try_from(bank.clock().unix_timestamp)?;
+    if now < state.next_epoch_starts_at {
+        return Err(anyhow::Error::msg(format!(
+            "epoch of gauge factory {} ends at {}, now is {}",
+            gauge_factory, state.next_epoch_starts_at, now
+        )));
+    }
+    state.current_voting_epoch += 1;
+    state.next_epoch_starts_at = now + u64::from(state.epoch_duration_seconds);
+    bank.set_anchor_account(*gauge_factory, &state)?;
+    println!(
+        "gauge factory {} moved to voting epoch {}",
+        gauge_factory, state.current_voting_epoch
+    );
+    Ok(())
+}
+
+/// Jump the clock to the end of the epoch and trigger the next one
+pub fn advance_epoch(bank: &mut Bank, gauge_factory: &Pubkey) -> Result<()> {
+    let state: gauge::GaugeFactory = bank.get_anchor_account(gauge_factory)?;
+    let now = bank.clock().unix_timestamp;
+    let ends_at = i64::try_from(state.next_epoch_starts_at)?;
+    if now < ends_at {
+        bank.advance_clock(ends_at - now);
+    }
+    trigger_next_epoch(bank, gauge_factory)
+}
+
+fn sync_gauge(bank: &mut Bank, epoch_gauge: &Pubkey, quarry_pk: &Pubkey) -> Result<()> {
+    let epoch_gauge_state: gauge::EpochGauge = bank.get_anchor_account(epoch_gauge)?;
+    let mut quarry_state: quarry::Quarry = bank.get_anchor_account(quarry_pk)?;
+    quarry_state.rewards_share = epoch_gauge_state.total_power;
+    bank.set_anchor_account(*quarry_pk, &quarry_state)
+}
+
+fn create_epoch_gauge(
+    bank: &mut Bank,
+    gauge_factory: &Pubkey,
+    gauge_pk: &Pubkey,
+    epoch_gauge: &Pubkey,
+) -> Result<()> {
+    if bank.get_account(epoch_gauge).is_some() {
+        return Err(anyhow::Error::msg(format!(
+            "epoch gauge {} already exists",
+            epoch_gauge
+        )));
+    }
+    let state: gauge::GaugeFactory = bank.get_anchor_account(gauge_factory)?;
+    bank.set_anchor_account(
+        *epoch_gauge,
+        &gauge::EpochGauge {
+            gauge: *gauge_pk,
+            voting_epoch: state.current_voting_epoch,
+            ..Default::default()
+        },
+    )
+}
+
+/// Create a bribe at the next bribe index of the factory
+pub fn add_bribe(bank: &mut Bank, gauge_factory: &Pubkey, bribe: gauge::Bribe) -> Result<Pubkey> {
+    let mut state: gauge::GaugeFactory = bank.get_anchor_account(gauge_factory)?;
+    let bribe_pk = find_bribe_addr(gauge_factory, state.bribe_index);
+    bank.set_anchor_account(
+        bribe_pk,
+        &gauge::Bribe {
+            bribe_index: state.bribe_index,
+            ..bribe
+        },
+    )?;
+    state.bribe_index += 1;
+    bank.set_anchor_account(*gauge_factory, &state)?;
+    Ok(bribe_pk)
+}
+
+#[cfg(test)]
+mod test_program {
+    use super::*;
+
+    fn new_bank_with_factory(next_epoch_starts_at: u64) -> (Bank, Pubkey) {
+        let mut bank = Bank::new(1_000);
+        let gauge_factory = Pubkey::new_unique();
+        bank.set_anchor_account(
+            gauge_factory,
+            &gauge::GaugeFactory {
+                current_voting_epoch: 1,
+                next_epoch_starts_at,
+                epoch_duration_seconds: 3600,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        (bank, gauge_factory)
+    }
+
+    #[test]
+    fn test_trigger_next_epoch_waits_for_the_clock() {
+        let (mut bank, gauge_factory) = new_bank_with_factory(2_000);
+        assert!(trigger_next_epoch(&mut bank, &gauge_factory).is_err());
+
+        advance_epoch(&mut bank, &gauge_factory).unwrap();
+        let state: gauge::GaugeFactory = bank.get_anchor_account(&gauge_factory).unwrap();
+        assert_eq!(state.current_voting_epoch, 2);
+        assert!(bank.clock().unix_timestamp >= 2_000);
+        assert!(state.next_epoch_starts_at >= 2_000 + 3600);
+    }
+
+    #[test]
+    fn test_add_bribe_at_next_index() {
+        let (mut bank, gauge_factory) = new_bank_with_factory(2_000);
+        let first = add_bribe(&mut bank, &gauge_factory, gauge::Bribe::default()).unwrap();
+        let second = add_bribe(&mut bank, &gauge_factory, gauge::Bribe::default()).unwrap();
+        assert_eq!(first, find_bribe_addr(&gauge_factory, 0));
+        assert_eq!(second, find_bribe_addr(&gauge_factory, 1));
+
+        let bribe: gauge::Bribe = bank.get_anchor_account(&second).unwrap();
+        assert_eq!(bribe.bribe_index, 1);
+        let state: gauge::GaugeFactory = bank.get_anchor_account(&gauge_factory).unwrap();
+        assert_eq!(state.bribe_index, 2);
+    }
+}
@@ -0,0 +1,254 @@
+//! JSON-RPC methods the keeper uses, answered from the bank
+
+use crate::bank::Bank;
+use crate::program::apply_transaction;
+use anchor_client::solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::hash::{hash, Hash};
+use anchor_client::solana_sdk::rent::Rent;
+use anchor_client::solana_sdk::signature::Signature;
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Error code of a transaction failing its preflight simulation
+const SEND_TRANSACTION_PREFLIGHT_FAILURE: i64 = -32002;
+
+/// Version reported to the clients, they pick the request encodings from it
+const SOLANA_CORE_VERSION: &str = "1.16.0";
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        RpcError {
+            code: INVALID_PARAMS,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Answer a JSON-RPC request
+pub fn handle_request(bank: &Mutex<Bank>, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request.get("method").and_then(|x| x.as_str()).unwrap_or("");
+    let params = request
+        .get("params")
+        .cloned()
+        .unwrap_or_else(|| Value::Array(vec![]));
+
+    let mut bank = bank.lock().unwrap();
+    match dispatch(&mut bank, method, &params) {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(err) => {
+            println!("rpc {} err {}", method, err.message);
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": err.code, "message": err.message},
+            })
+        }
+    }
+}
+
+fn dispatch(bank: &mut Bank, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let result = match method {
+        "getAccountInfo" => {
+            let pubkey = parse_pubkey(param(params, 0)?)?;
+            let config: Option<RpcAccountInfoConfig> = optional_param(params, 1)?;
+            let value = bank
+                .get_account(&pubkey)
+                .map(|account| encode_account(&pubkey, &account, config.as_ref()));
+            with_context(bank, json!(value))
+        }
+        "getMultipleAccounts" => {
+            let pubkeys: Vec<String> = param(params, 0)?;
+            let config: Option<RpcAccountInfoConfig> = optional_param(params, 1)?;
+            let mut values = vec![];
+            for pubkey in pubkeys.iter() {
+                let pubkey = parse_pubkey(pubkey.clone())?;
+                values.push(
+                    bank.get_account(&pubkey)
+                        .map(|account| encode_account(&pubkey, &account, config.as_ref())),
+                );
+            }
+            with_context(bank, json!(values))
+        }
+        "getProgramAccounts" => {
+            let program_id = parse_pubkey(param(params, 0)?)?;
+            let config: RpcProgramAccountsConfig = optional_param(params, 1)?.unwrap_or_default();
+            let filters = config.filters.clone().unwrap_or_default();
+            let accounts: Vec<Value> = bank
+                .program_accounts(&program_id, &filters)
+                .iter()
+                .map(|(pubkey, account)| {
+                    json!({
+                        "pubkey": pubkey.to_string(),
+                        "account": encode_account(pubkey, account, Some(&config.account_config)),
+                    })
+                })
+                .collect();
+            if config.with_context.unwrap_or(false) {
+                with_context(bank, json!(accounts))
+            } else {
+                json!(accounts)
+            }
+        }
+        "getBalance" => {
+            let pubkey = parse_pubkey(param(params, 0)?)?;
+            let balance = bank.get_account(&pubkey).map(|x| x.lamports).unwrap_or(0);
+            with_context(bank, json!(balance))
+        }
+        "getMinimumBalanceForRentExemption" => {
+            let data_len: usize = param(params, 0)?;
+            json!(Rent::default().minimum_balance(data_len))
+        }
+        "getLatestBlockhash" => with_context(
+            bank,
+            json!({
+                "blockhash": blockhash(bank).to_string(),
+                "lastValidBlockHeight": bank.slot() + 150,
+            }),
+        ),
+        "isBlockhashValid" => with_context(bank, json!(true)),
+        "sendTransaction" => {
+            let tx = decode_transaction(params)?;
+            let signature = tx.signatures.get(0).cloned().unwrap_or_default();
+            if let Err(err) = apply_transaction(bank, &tx) {
+                return Err(RpcError {
+                    code: SEND_TRANSACTION_PREFLIGHT_FAILURE,
+                    message: format!("Transaction simulation failed: {}", err),
+                });
+            }
+            println!("transaction {} applied", signature);
+            bank.record_signature(signature);
+            json!(signature.to_string())
+        }
+        "simulateTransaction" => {
+            let tx = decode_transaction(params)?;
+            // play it on a copy, so nothing changes
+            let mut copy = bank.clone();
+            let (err, logs) = match apply_transaction(&mut copy, &tx) {
+                Ok(()) => (
+                    Value::Null,
+                    vec![String::from("mock: simulation succeeded")],
+                ),
+                Err(err) => (
+                    json!({"InstructionError": [0, {"Custom": 0}]}),
+                    vec![format!("mock: {}", err)],
+                ),
+            };
+            with_context(
+                bank,
+                json!({
+                    "err": err,
+                    "logs": logs,
+                    "accounts": null,
+                    "unitsConsumed": 0,
+                    "returnData": null,
+                }),
+            )
+        }
+        "getSignatureStatuses" => {
+            let signatures: Vec<String> = param(params, 0)?;
+            let mut statuses = vec![];
+            for signature in signatures.iter() {
+                let signature = Signature::from_str(signature)
+                    .map_err(|err| anyhow::Error::msg(err.to_string()))?;
+                statuses.push(if bank.has_signature(&signature) {
+                    json!({
+                        "slot": bank.slot(),
+                        "confirmations": null,
+                        "err": null,
+                        "status": {"Ok": null},
+                        "confirmationStatus": "finalized",
+                    })
+                } else {
+                    Value::Null
+                });
+            }
+            with_context(bank, json!(statuses))
+        }
+        "getSlot" | "getBlockHeight" => json!(bank.slot()),
+        "getHealth" => json!("ok"),
+        "getVersion" => json!({"solana-core": SOLANA_CORE_VERSION, "feature-set": 0}),
+        _ => {
+            return Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {}", method),
+            })
+        }
+    };
+    Ok(result)
+}
+
+fn with_context(bank: &Bank, value: Value) -> Value {
+    json!({"context": {"slot": bank.slot()}, "value": value})
+}
+
+/// Blockhash of the current slot, so it changes like on a real cluster
+fn blockhash(bank: &Bank) -> Hash {
+    hash(&bank.slot().to_le_bytes())
+}
+
+fn param<T: DeserializeOwned>(params: &Value, i: usize) -> Result<T> {
+    let value = params
+        .get(i)
+        .ok_or_else(|| anyhow::Error::msg(format!("missing param {}", i)))?;
+    Ok(serde_json::from_value(value.clone())?)
+}
+
+fn optional_param<T: DeserializeOwned>(params: &Value, i: usize) -> Result<Option<T>> {
+    match params.get(i) {
+        Some(Value::Null) | None => Ok(None),
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+    }
+}
+
+fn parse_pubkey(pubkey: String) -> Result<Pubkey> {
+    Ok(Pubkey::from_str(&pubkey)?)
+}
+
+/// Accounts are encoded as asked, base64 when the encoding is not set or jsonParsed
+fn encode_account(
+    pubkey: &Pubkey,
+    account: &Account,
+    config: Option<&RpcAccountInfoConfig>,
+) -> UiAccount {
+    let encoding = match config.and_then(|x| x.encoding) {
+        Some(UiAccountEncoding::Base64Zstd) => UiAccountEncoding::Base64Zstd,
+        Some(UiAccountEncoding::Base58) | Some(UiAccountEncoding::Binary) => {
+            UiAccountEncoding::Base58
+        }
+        _ => UiAccountEncoding::Base64,
+    };
+    let data_slice = config.and_then(|x| x.data_slice);
+    UiAccount::encode(pubkey, account, encoding, None, data_slice)
+}
+
+/// Transactions are sent as the first param, base64 or base58 as told by the config
+fn decode_transaction(params: &Value) -> Result<Transaction> {
+    let encoded: String = param(params, 0)?;
+    let config: Option<Value> = optional_param(params, 1)?;
+    let encoding = config
+        .as_ref()
+        .and_then(|x| x.get("encoding"))
+        .and_then(|x| x.as_str())
+        .unwrap_or("base58");
+    let bytes = match encoding {
+        "base64" => base64::engine::general_purpose::STANDARD.decode(encoded)?,
+        _ => bs58::decode(encoded).into_vec()?,
+    };
+    Ok(bincode::deserialize(&bytes)?)
+}
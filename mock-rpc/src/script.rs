@@ -0,0 +1,105 @@
+//! Scripted changes of the mock cluster, played at a time or posted to /mock/action
+
+use crate::bank::Bank;
+use crate::fixtures::FixtureAccount;
+use crate::program::{add_bribe, advance_epoch};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Move the clock forward
+    AdvanceClock {
+        seconds: i64,
+    },
+    /// Jump the clock to the end of the epoch of the gauge factory, and trigger the next one
+    AdvanceEpoch {
+        gauge_factory: String,
+    },
+    /// Create a bribe at the next bribe index of the gauge factory
+    AddBribe {
+        gauge_factory: String,
+        gauge: String,
+        token_mint: String,
+        reward_each_epoch: u64,
+        epoch_start: u32,
+        epoch_end: u32,
+    },
+    SetAccount(FixtureAccount),
+    RemoveAccount {
+        pubkey: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStep {
+    /// Seconds after startup the action is played at
+    pub after_seconds: u64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl Action {
+    /// Play the action, returning what changed
+    pub fn apply(&self, bank: &mut Bank) -> Result<String> {
+        match self {
+            Action::AdvanceClock { seconds } => {
+                bank.advance_clock(*seconds);
+                Ok(format!("clock moved to {}", bank.clock().unix_timestamp))
+            }
+            Action::AdvanceEpoch { gauge_factory } => {
+                advance_epoch(bank, &Pubkey::from_str(gauge_factory)?)?;
+                Ok(format!("epoch of gauge factory {} advanced", gauge_factory))
+            }
+            Action::AddBribe {
+                gauge_factory,
+                gauge,
+                token_mint,
+                reward_each_epoch,
+                epoch_start,
+                epoch_end,
+            } => {
+                let bribe = gauge::Bribe {
+                    gauge: Pubkey::from_str(gauge)?,
+                    token_mint: Pubkey::from_str(token_mint)?,
+                    reward_each_epoch: *reward_each_epoch,
+                    bribe_rewards_epoch_start: *epoch_start,
+                    bribe_rewards_epoch_end: *epoch_end,
+                    ..Default::default()
+                };
+                let bribe_pk = add_bribe(bank, &Pubkey::from_str(gauge_factory)?, bribe)?;
+                Ok(format!("bribe {} added", bribe_pk))
+            }
+            Action::SetAccount(fixture) => {
+                let (pubkey, account) = fixture.decode()?;
+                bank.set_account(pubkey, account);
+                Ok(format!("account {} set", pubkey))
+            }
+            Action::RemoveAccount { pubkey } => {
+                bank.remove_account(&Pubkey::from_str(pubkey)?);
+                Ok(format!("account {} removed", pubkey))
+            }
+        }
+    }
+}
+
+/// Play the steps in order of time, a failing step is logged and skipped
+pub async fn run_script(bank: Arc<Mutex<Bank>>, mut steps: Vec<ScriptStep>) {
+    steps.sort_by_key(|x| x.after_seconds);
+    let mut elapsed = 0;
+    for step in steps {
+        sleep(Duration::from_secs(step.after_seconds - elapsed)).await;
+        elapsed = step.after_seconds;
+        let mut bank = bank.lock().unwrap();
+        match step.action.apply(&mut bank) {
+            Ok(message) => println!("script at {}s: {}", step.after_seconds, message),
+            Err(err) => println!("script at {}s err {}", step.after_seconds, err),
+        }
+    }
+}